@@ -0,0 +1,537 @@
+use std::fs::File;
+use std::io::Write;
+
+use anyhow::{anyhow, Context, Result};
+use chrono::SecondsFormat;
+use clap::{Parser, Subcommand};
+use itertools::Itertools;
+
+use crate::csv_io;
+use crate::db::JiraDatabase;
+use crate::jira_import::{self, JiraFormat, JiraMapping};
+use crate::models::{Epic, Priority, Status, Story};
+use crate::output::{self, OutputFormat};
+use crate::query::Query;
+use crate::report::{self, ReportFormat};
+
+/// A small Jira clone. Starts the interactive board when no subcommand is given.
+#[derive(Parser, Debug)]
+pub struct Cli {
+    /// Database file to use (a .sqlite file selects the SQLite backend when built with the sqlite feature)
+    #[arg(long, global = true, default_value = "./data/db.json")]
+    pub db: String,
+
+    /// Who you are: the reporter of stories you create and whose stories "my stories" shows (defaults to $JIRA_USER)
+    #[arg(long, global = true)]
+    pub user: Option<String>,
+
+    /// JSON file listing the statuses and the allowed status changes of stories (every change is allowed when it doesn't exist)
+    #[arg(long, global = true, default_value = "./data/workflow.json")]
+    pub workflow: String,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+impl Cli {
+    pub fn current_user(&self) -> Option<String> {
+        self.user.clone().or_else(|| std::env::var("JIRA_USER").ok()).filter(|user| !user.trim().is_empty())
+    }
+}
+
+#[derive(Subcommand, Debug, PartialEq)]
+pub enum Command {
+    /// Create, list and update epics
+    #[command(subcommand)]
+    Epic(EpicCommand),
+    /// Create, list and update stories
+    #[command(subcommand)]
+    Story(StoryCommand),
+    /// Export epics and stories to a file
+    #[command(subcommand)]
+    Export(ExportCommand),
+    /// Import epics and stories from a file, allocating new ids
+    #[command(subcommand)]
+    Import(ImportCommand),
+    /// Show the change history of an epic or story
+    History {
+        id: u32,
+        /// text prints tab separated time, user, field, old and new value; json prints the full records
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+    },
+    /// Render an epic and its stories, grouped by status, for sprint reviews
+    Report {
+        epic: u32,
+        /// File to write, prints to stdout when omitted
+        file: Option<String>,
+        #[arg(long, value_enum, default_value_t = ReportFormat::Markdown)]
+        format: ReportFormat,
+    },
+    /// Report integrity problems in the database, exits with an error if there are any
+    Check {
+        /// Fix the problems instead of only reporting them
+        #[arg(long)]
+        repair: bool,
+    },
+}
+
+#[derive(Subcommand, Debug, PartialEq)]
+pub enum EpicCommand {
+    /// Create an epic and print its id
+    Create {
+        #[arg(long)]
+        name: String,
+        #[arg(long, default_value = "")]
+        desc: String,
+        #[arg(long)]
+        owner: Option<String>,
+    },
+    /// List every epic
+    List {
+        /// text prints tab separated id, name and status; json prints the full records
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+    },
+    /// Set the status of an epic
+    Status { id: u32, status: Status },
+}
+
+#[derive(Subcommand, Debug, PartialEq)]
+pub enum StoryCommand {
+    /// Create a story inside an epic and print its id
+    Create {
+        #[arg(long)]
+        epic: u32,
+        #[arg(long)]
+        name: String,
+        #[arg(long, default_value = "")]
+        desc: String,
+        #[arg(long)]
+        assignee: Option<String>,
+        /// Defaults to the current user
+        #[arg(long)]
+        reporter: Option<String>,
+        #[arg(long, default_value_t = Priority::Medium)]
+        priority: Priority,
+        #[arg(long)]
+        points: Option<u32>,
+    },
+    /// List stories
+    List {
+        /// Only list the stories of this epic
+        #[arg(long)]
+        epic: Option<u32>,
+        /// Only list the stories assigned to this person
+        #[arg(long, conflicts_with = "mine")]
+        assignee: Option<String>,
+        /// Only list the stories assigned to the current user
+        #[arg(long)]
+        mine: bool,
+        /// Only list the stories matching a filter, e.g. 'status = "IN PROGRESS" AND label = backend'
+        #[arg(long)]
+        query: Option<Query>,
+        /// text prints tab separated id, name and status; json prints the full records
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+    },
+    /// Set the status of a story
+    Status { id: u32, status: Status },
+    /// Set the priority and/or story points of a story
+    Estimate {
+        id: u32,
+        #[arg(long)]
+        priority: Option<Priority>,
+        #[arg(long, conflicts_with = "clear_points")]
+        points: Option<u32>,
+        /// Mark the story as not estimated
+        #[arg(long)]
+        clear_points: bool,
+    },
+    /// Assign a story, or unassign it when no assignee is given
+    Assign { id: u32, assignee: Option<String> },
+}
+
+#[derive(Subcommand, Debug, PartialEq)]
+pub enum ExportCommand {
+    /// One row per epic and story: type,id,epic_id,name,description,status
+    Csv {
+        /// File to write, prints to stdout when omitted
+        file: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Debug, PartialEq)]
+pub enum ImportCommand {
+    /// Rows in the format written by `export csv`; epic_id may also name an epic already in the database
+    Csv {
+        file: String,
+    },
+    /// A Jira JSON (REST search response or issue array) or CSV export, detected by the .csv extension
+    Jira {
+        file: String,
+        /// JSON file overriding the issue type, status and epic link mapping
+        #[arg(long)]
+        mapping: Option<String>,
+    },
+}
+
+impl Command {
+    fn is_read_only(&self) -> bool {
+        matches!(self, Command::Epic(EpicCommand::List { .. }) | Command::Story(StoryCommand::List { .. }) | Command::Export(_) | Command::Report { .. } | Command::History { .. } | Command::Check { .. })
+    }
+}
+
+// Runs one non-interactive command against the database, writing its output to 'out'
+pub fn run(command: Command, db: &JiraDatabase, current_user: Option<&str>, out: &mut dyn Write) -> Result<()> {
+    if !matches!(command, Command::Check { .. }) { //same load-time check as the interactive app, check itself has to run on a broken db
+        let issues = db.validate()?;
+        if !issues.is_empty() {
+            let message = format!("found {} integrity problem(s), run check --repair to fix them:\n{}", issues.len(), issues.iter().join("\n"));
+            if !command.is_read_only() { //writing on top of a broken db would only bury the problem
+                return Err(anyhow!(message));
+            }
+            eprintln!("warning: {}", message); //stderr, so the listing on 'out' stays parseable
+        }
+    }
+    match command {
+        Command::Epic(EpicCommand::Create { name, desc, owner }) => {
+            let mut epic = Epic::new(name, desc);
+            epic.owner = owner;
+            let epic_id = db.create_epic(epic)?;
+            writeln!(out, "{}", epic_id)?; //just the id, so scripts can capture it
+        }
+        Command::Epic(EpicCommand::List { format }) => {
+            let epics = output::epics(&db.read_db()?);
+            match format {
+                OutputFormat::Text => for epic in epics {
+                    writeln!(out, "{}\t{}\t{}", epic.id, epic.name, epic.status)?;
+                },
+                OutputFormat::Json => writeln!(out, "{}", serde_json::to_string_pretty(&epics)?)?,
+            }
+        }
+        Command::Epic(EpicCommand::Status { id, status }) => {
+            db.update_epic_status(id, status)?;
+        }
+        Command::Story(StoryCommand::Create { epic, name, desc, assignee, reporter, priority, points }) => {
+            let mut story = Story::new(name, desc);
+            story.priority = priority;
+            story.points = points;
+            story.assignee = assignee;
+            story.reporter = reporter.or_else(|| current_user.map(str::to_owned));
+            let story_id = db.create_story(story, epic)?;
+            writeln!(out, "{}", story_id)?;
+        }
+        Command::Story(StoryCommand::List { epic, assignee, mine, query, format }) => {
+            let db_state = db.read_db()?;
+            let mut story_ids: Vec<u32> = match epic {
+                Some(epic_id) => db_state.epics.get(&epic_id).filter(|epic| !epic.archived).ok_or_else(|| anyhow!("could not find epic {}!", epic_id))?.stories.clone(),
+                None => db_state.stories.keys().copied().collect(),
+            };
+            let assignee = match mine {
+                true => Some(current_user.ok_or_else(|| anyhow!("no current user, pass --user or set JIRA_USER"))?.to_owned()),
+                false => assignee,
+            };
+            if let Some(assignee) = assignee {
+                story_ids.retain(|id| db_state.stories.get(id).is_some_and(|story| story.assignee.as_ref() == Some(&assignee)));
+            }
+            if let Some(query) = query {
+                let matching = query.stories(&db_state);
+                story_ids.retain(|id| matching.binary_search(id).is_ok()); //sorted by id
+            }
+            let stories = output::stories(&db_state, &story_ids);
+            match format {
+                OutputFormat::Text => for story in stories {
+                    writeln!(out, "{}\t{}\t{}", story.id, story.name, story.status)?;
+                },
+                OutputFormat::Json => writeln!(out, "{}", serde_json::to_string_pretty(&stories)?)?,
+            }
+        }
+        Command::Story(StoryCommand::Status { id, status }) => {
+            db.update_story_status(id, status)?;
+        }
+        Command::Story(StoryCommand::Estimate { id, priority, points, clear_points }) => {
+            let story = db.read_db()?.stories.remove(&id).ok_or_else(|| anyhow!("could not find story {}!", id))?;
+            let points = if clear_points { None } else { points.or(story.points) }; //options left out keep their current value
+            db.estimate_story(id, priority.unwrap_or(story.priority), points)?;
+        }
+        Command::Story(StoryCommand::Assign { id, assignee }) => {
+            db.assign_story(id, assignee)?;
+        }
+        Command::Export(ExportCommand::Csv { file }) => {
+            let db_state = db.read_db()?;
+            match file {
+                Some(file) => csv_io::export(&db_state, File::create(&file).with_context(|| format!("could not create {}", file))?)?,
+                None => csv_io::export(&db_state, out)?,
+            }
+        }
+        Command::Import(ImportCommand::Csv { file }) => {
+            let report = csv_io::import(db, File::open(&file).with_context(|| format!("could not open {}", file))?)?;
+            writeln!(out, "imported {} epic(s) and {} story(s)", report.epics_created, report.stories_created)?;
+            for (line, reason) in &report.invalid_rows {
+                writeln!(out, "skipped line {}: {}", line, reason)?;
+            }
+        }
+        Command::Import(ImportCommand::Jira { file, mapping }) => {
+            let mapping = match mapping {
+                Some(mapping) => JiraMapping::from_file(&mapping)?,
+                None => JiraMapping::default(),
+            };
+            let input = File::open(&file).with_context(|| format!("could not open {}", file))?;
+            let import = jira_import::import(input, JiraFormat::from_path(&file), &mapping)?;
+            let (epics, stories) = (import.state.epics.len(), import.state.stories.len());
+            db.merge(import.state)?;
+            writeln!(out, "imported {} epic(s) and {} story(s)", epics, stories)?;
+            for skipped in &import.skipped {
+                writeln!(out, "skipped {}", skipped)?;
+            }
+        }
+        Command::History { id, format } => {
+            let history = output::history(&db.read_db()?, id);
+            match format {
+                OutputFormat::Text => for entry in history {
+                    writeln!(out, "{}\t{}\t{}\t{}\t{}", entry.at.to_rfc3339_opts(SecondsFormat::Secs, true), entry.user.as_deref().unwrap_or("-"), entry.field, entry.old, entry.new)?;
+                },
+                OutputFormat::Json => writeln!(out, "{}", serde_json::to_string_pretty(&history)?)?,
+            }
+        }
+        Command::Report { epic, file, format } => {
+            let report = report::epic_report(&db.read_db()?, epic, format)?;
+            match file {
+                Some(file) => std::fs::write(&file, report).with_context(|| format!("could not write {}", file))?,
+                None => write!(out, "{}", report)?,
+            }
+        }
+        Command::Check { repair } => {
+            let issues = if repair { db.repair()? } else { db.validate()? };
+            for issue in &issues {
+                writeln!(out, "{}", issue)?;
+            }
+            if !repair && !issues.is_empty() {
+                return Err(anyhow!("found {} integrity problem(s), run with --repair to fix them", issues.len()));
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_utils::MockDB;
+    use crate::workflow::Workflow;
+
+    fn run_to_string(command: Command, db: &JiraDatabase) -> Result<String> {
+        let mut out = vec![];
+        run(command, db, None, &mut out)?;
+        Ok(String::from_utf8(out).unwrap())
+    }
+
+    fn parse(args: &[&str]) -> Cli {
+        Cli::try_parse_from(std::iter::once("jira").chain(args.iter().copied())).unwrap()
+    }
+
+    #[test]
+    fn parse_should_default_to_interactive_mode() {
+        let cli = parse(&[]);
+        assert_eq!(cli.command, None);
+        assert_eq!(cli.db, "./data/db.json");
+    }
+
+    #[test]
+    fn parse_should_read_subcommands_and_db_path() {
+        let cli = parse(&["story", "status", "3", "in-progress", "--db", "other.json"]);
+        assert_eq!(cli.command, Some(Command::Story(StoryCommand::Status { id: 3, status: Status::InProgress })));
+        assert_eq!(cli.db, "other.json");
+
+        assert!(Cli::try_parse_from(["jira", "story", "status", "3", "done"]).is_err());
+    }
+
+    #[test]
+    fn create_and_list_should_work() {
+        let db = JiraDatabase { database: Box::new(MockDB::new()), user: None, workflow: Workflow::default() };
+
+        let epic_id = run_to_string(parse(&["epic", "create", "--name", "epic 1", "--desc", "d"]).command.unwrap(), &db).unwrap();
+        assert_eq!(epic_id, "1\n");
+        let story_id = run_to_string(parse(&["story", "create", "--epic", "1", "--name", "story 1"]).command.unwrap(), &db).unwrap();
+        assert_eq!(story_id, "2\n");
+        run_to_string(parse(&["story", "status", "2", "closed"]).command.unwrap(), &db).unwrap();
+
+        assert_eq!(run_to_string(parse(&["epic", "list"]).command.unwrap(), &db).unwrap(), "1\tepic 1\tOPEN\n");
+        assert_eq!(run_to_string(parse(&["story", "list", "--epic", "1"]).command.unwrap(), &db).unwrap(), "2\tstory 1\tCLOSED\n");
+        assert!(run_to_string(parse(&["story", "list", "--epic", "999"]).command.unwrap(), &db).is_err());
+    }
+
+    #[test]
+    fn list_should_hide_the_trash() {
+        let db = JiraDatabase { database: Box::new(MockDB::new()), user: None, workflow: Workflow::default() };
+        let epic_id = db.create_epic(Epic::new("epic".to_owned(), "".to_owned())).unwrap();
+        let story_id = db.create_story(Story::new("story".to_owned(), "".to_owned()), epic_id).unwrap();
+        let deleted_story_id = db.create_story(Story::new("deleted".to_owned(), "".to_owned()), epic_id).unwrap();
+        db.delete_story(epic_id, deleted_story_id).unwrap();
+
+        assert_eq!(run_to_string(parse(&["story", "list"]).command.unwrap(), &db).unwrap(), format!("{}\tstory\tOPEN\n", story_id));
+        db.delete_epic(epic_id).unwrap();
+        assert_eq!(run_to_string(parse(&["epic", "list"]).command.unwrap(), &db).unwrap(), "");
+        assert_eq!(run_to_string(parse(&["story", "list"]).command.unwrap(), &db).unwrap(), "");
+        assert!(run_to_string(parse(&["story", "list", "--epic", "1"]).command.unwrap(), &db).is_err());
+    }
+
+    #[test]
+    fn list_should_filter_by_query() {
+        let db = JiraDatabase { database: Box::new(MockDB::new()), user: None, workflow: Workflow::default() };
+        let epic_id = db.create_epic(Epic::new("epic".to_owned(), "".to_owned())).unwrap();
+        let other_epic_id = db.create_epic(Epic::new("other".to_owned(), "".to_owned())).unwrap();
+        let story_id = db.create_story(Story::new("story".to_owned(), "".to_owned()), epic_id).unwrap();
+        db.create_story(Story::new("elsewhere".to_owned(), "".to_owned()), other_epic_id).unwrap();
+        let closed_story_id = db.create_story(Story::new("closed".to_owned(), "".to_owned()), epic_id).unwrap();
+        db.update_story_status(closed_story_id, Status::Closed).unwrap();
+        db.add_label(story_id, "backend").unwrap();
+
+        let query = format!("status = OPEN AND epic = {} AND label = backend", epic_id);
+        assert_eq!(run_to_string(parse(&["story", "list", "--query", &query]).command.unwrap(), &db).unwrap(), format!("{}\tstory\tOPEN\n", story_id));
+        assert_eq!(run_to_string(parse(&["story", "list", "--epic", &epic_id.to_string(), "--query", "status != open"]).command.unwrap(), &db).unwrap(), format!("{}\tclosed\tCLOSED\n", closed_story_id));
+
+        let error = Cli::try_parse_from(["jira", "story", "list", "--query", "status = done"]).unwrap_err().to_string();
+        assert!(error.contains("column 10: unknown status \"done\""));
+    }
+
+    #[test]
+    fn stories_should_track_people() {
+        let db = JiraDatabase { database: Box::new(MockDB::new()), user: None, workflow: Workflow::default() };
+        let mut out = vec![];
+        run(parse(&["epic", "create", "--name", "epic 1", "--owner", "carol"]).command.unwrap(), &db, None, &mut out).unwrap();
+        run(parse(&["story", "create", "--epic", "1", "--name", "mine", "--assignee", "alice"]).command.unwrap(), &db, Some("bob"), &mut out).unwrap();
+        run(parse(&["story", "create", "--epic", "1", "--name", "other"]).command.unwrap(), &db, Some("bob"), &mut out).unwrap();
+        run(parse(&["story", "assign", "3", "carol"]).command.unwrap(), &db, None, &mut out).unwrap();
+
+        let db_state = db.read_db().unwrap();
+        assert_eq!(db_state.epics[&1].owner, Some("carol".to_owned()));
+        assert_eq!(db_state.stories[&2].reporter, Some("bob".to_owned()));
+        assert_eq!(db_state.stories[&3].assignee, Some("carol".to_owned()));
+
+        let mut out = vec![];
+        run(parse(&["story", "list", "--mine"]).command.unwrap(), &db, Some("alice"), &mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "2\tmine\tOPEN\n");
+        assert_eq!(run_to_string(parse(&["story", "list", "--assignee", "carol"]).command.unwrap(), &db).unwrap(), "3\tother\tOPEN\n");
+        assert!(run_to_string(parse(&["story", "list", "--mine"]).command.unwrap(), &db).is_err());
+
+        run_to_string(parse(&["story", "assign", "3"]).command.unwrap(), &db).unwrap();
+        assert_eq!(db.read_db().unwrap().stories[&3].assignee, None);
+    }
+
+    #[test]
+    fn estimate_should_keep_omitted_values() {
+        let db = JiraDatabase { database: Box::new(MockDB::new()), user: None, workflow: Workflow::default() };
+        run_to_string(parse(&["epic", "create", "--name", "epic 1"]).command.unwrap(), &db).unwrap();
+        run_to_string(parse(&["story", "create", "--epic", "1", "--name", "s", "--priority", "high", "--points", "3"]).command.unwrap(), &db).unwrap();
+
+        run_to_string(parse(&["story", "estimate", "2", "--points", "5"]).command.unwrap(), &db).unwrap();
+        let story = db.read_db().unwrap().stories[&2].clone();
+        assert_eq!((story.priority, story.points), (Priority::High, Some(5)));
+
+        run_to_string(parse(&["story", "estimate", "2", "--priority", "LOWEST", "--clear-points"]).command.unwrap(), &db).unwrap();
+        let story = db.read_db().unwrap().stories[&2].clone();
+        assert_eq!((story.priority, story.points), (Priority::Lowest, None));
+        assert!(run_to_string(parse(&["story", "estimate", "999"]).command.unwrap(), &db).is_err());
+    }
+
+    #[test]
+    fn history_should_list_changes_of_one_item() {
+        let db = JiraDatabase { database: Box::new(MockDB::new()), user: Some("alice".to_owned()), workflow: Workflow::default() };
+        let epic_id = db.create_epic(Epic::new("epic 1".to_owned(), "".to_owned())).unwrap();
+        let story_id = db.create_story(Story::new("story 1".to_owned(), "".to_owned()), epic_id).unwrap();
+        db.update_story_status(story_id, Status::Closed).unwrap();
+
+        let history: serde_json::Value = serde_json::from_str(&run_to_string(parse(&["history", &story_id.to_string(), "--format", "json"]).command.unwrap(), &db).unwrap()).unwrap();
+        assert_eq!(history.as_array().unwrap().len(), 2);
+        assert_eq!(history[1]["user"], "alice");
+        assert_eq!(history[1]["field"], "status");
+        assert_eq!(history[1]["old"], "OPEN");
+        assert_eq!(history[1]["new"], "CLOSED");
+
+        let text = run_to_string(parse(&["history", &epic_id.to_string()]).command.unwrap(), &db).unwrap();
+        assert!(text.ends_with("\talice\tcreated\t\tepic 1\n"));
+    }
+
+    #[test]
+    fn list_should_print_json() {
+        let db = JiraDatabase { database: Box::new(MockDB::new()), user: None, workflow: Workflow::default() };
+        let epic_id = db.create_epic(Epic::new("epic 1".to_owned(), "".to_owned())).unwrap();
+        let story_id = db.create_story(Story::new("story 1".to_owned(), "".to_owned()), epic_id).unwrap();
+
+        let epics: serde_json::Value = serde_json::from_str(&run_to_string(parse(&["epic", "list", "--format", "json"]).command.unwrap(), &db).unwrap()).unwrap();
+        assert_eq!(epics[0]["id"], epic_id);
+        assert_eq!(epics[0]["stories"], serde_json::json!([story_id]));
+
+        let stories: serde_json::Value = serde_json::from_str(&run_to_string(parse(&["story", "list", "--format", "json"]).command.unwrap(), &db).unwrap()).unwrap();
+        assert_eq!(stories[0]["id"], story_id);
+        assert_eq!(stories[0]["epic_id"], epic_id);
+        assert_eq!(stories[0]["status"], "OPEN");
+    }
+
+    #[test]
+    fn export_and_import_csv_should_work() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("board.csv").to_str().unwrap().to_owned();
+        let db = JiraDatabase { database: Box::new(MockDB::new()), user: None, workflow: Workflow::default() };
+        let epic_id = db.create_epic(Epic::new("epic 1".to_owned(), "".to_owned())).unwrap();
+        db.create_story(Story::new("story 1".to_owned(), "".to_owned()), epic_id).unwrap();
+
+        run_to_string(parse(&["export", "csv", &file]).command.unwrap(), &db).unwrap();
+        let output = run_to_string(parse(&["import", "csv", &file]).command.unwrap(), &db).unwrap();
+
+        assert_eq!(output, "imported 1 epic(s) and 1 story(s)\n");
+        assert_eq!(db.read_db().unwrap().epics.len(), 2);
+    }
+
+    #[test]
+    fn import_jira_should_merge_into_database() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("jira.json").to_str().unwrap().to_owned();
+        std::fs::write(&file, r#"[
+            { "key": "J-1", "fields": { "summary": "epic", "issuetype": { "name": "Epic" }, "status": { "name": "Done" } } },
+            { "key": "J-2", "fields": { "summary": "story", "issuetype": { "name": "Story" }, "status": { "name": "To Do" }, "parent": { "key": "J-1" } } },
+            { "key": "J-3", "fields": { "summary": "sub", "issuetype": { "name": "Sub-task" }, "status": { "name": "To Do" } } }
+        ]"#).unwrap();
+        let db = JiraDatabase { database: Box::new(MockDB::new()), user: None, workflow: Workflow::default() };
+        db.create_epic(Epic::new("already there".to_owned(), "".to_owned())).unwrap();
+
+        let output = run_to_string(parse(&["import", "jira", &file]).command.unwrap(), &db).unwrap();
+
+        assert_eq!(output, "imported 1 epic(s) and 1 story(s)\nskipped J-3 (Sub-task): issue type is not mapped\n");
+        let db_state = db.read_db().unwrap();
+        assert_eq!(db_state.epics[&2].name, "epic");
+        assert_eq!(db_state.epics[&2].status, Status::Closed);
+        assert_eq!(db_state.epics[&2].stories, vec![3]);
+    }
+
+    #[test]
+    fn report_should_write_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("report.html").to_str().unwrap().to_owned();
+        let db = JiraDatabase { database: Box::new(MockDB::new()), user: None, workflow: Workflow::default() };
+        let epic_id = db.create_epic(Epic::new("epic 1".to_owned(), "".to_owned())).unwrap();
+
+        assert_eq!(run_to_string(parse(&["report", "1", &file, "--format", "html"]).command.unwrap(), &db).unwrap(), "");
+        assert!(std::fs::read_to_string(&file).unwrap().contains("<h1>epic 1 (epic 1)</h1>"));
+        assert!(run_to_string(parse(&["report", &epic_id.to_string()]).command.unwrap(), &db).unwrap().starts_with("# epic 1"));
+    }
+
+    #[test]
+    fn check_should_fail_until_repaired() {
+        let db = JiraDatabase { database: Box::new(MockDB::new()), user: None, workflow: Workflow::default() };
+        let epic_id = db.create_epic(Epic::new("".to_owned(), "".to_owned())).unwrap();
+        let mut db_state = db.read_db().unwrap();
+        db_state.epics.get_mut(&epic_id).unwrap().stories.push(999);
+        db.database.write_db(&db_state).unwrap();
+
+        assert!(run_to_string(Command::Check { repair: false }, &db).is_err());
+        let error = run_to_string(parse(&["epic", "create", "--name", "epic 2"]).command.unwrap(), &db).unwrap_err().to_string();
+        assert!(error.contains("check --repair") && error.contains("story 999"));
+        assert_eq!(run_to_string(parse(&["epic", "list"]).command.unwrap(), &db).unwrap(), "1\t\tOPEN\n"); //read-only commands only warn
+        assert!(run_to_string(Command::Check { repair: true }, &db).unwrap().contains("story 999"));
+        assert!(run_to_string(parse(&["epic", "create", "--name", "epic 2"]).command.unwrap(), &db).is_ok());
+        assert_eq!(run_to_string(Command::Check { repair: false }, &db).unwrap(), "");
+    }
+}
@@ -0,0 +1,271 @@
+use std::collections::{BTreeSet, HashMap};
+use std::io::{Read, Write};
+
+use anyhow::{anyhow, Result};
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+
+use crate::db::JiraDatabase;
+use crate::models::{normalize_label, DBState, Epic, Priority, Status, Story};
+
+// One line of the spreadsheet format. Epics and stories share the file; a story points at its epic through
+// epic_id, which is either the id of an epic row in the same file or the id of an epic already in the database.
+// The people and labels columns came later, so files without them still import.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct CsvRow {
+    #[serde(rename = "type")]
+    pub kind: String, //"epic" or "story"
+    pub id: u32,
+    pub epic_id: Option<u32>,
+    pub name: String,
+    pub description: String,
+    pub status: String,
+    #[serde(default)]
+    pub owner: Option<String>, //epics only
+    #[serde(default)]
+    pub assignee: Option<String>, //stories only
+    #[serde(default)]
+    pub reporter: Option<String>, //stories only
+    #[serde(default)]
+    pub priority: String, //stories only, blank means MEDIUM
+    #[serde(default)]
+    pub points: Option<u32>, //stories only
+    #[serde(default)]
+    pub labels: String, //comma separated
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ImportReport {
+    pub epics_created: usize,
+    pub stories_created: usize,
+    pub invalid_rows: Vec<(usize, String)>, //line number in the file and why the row was skipped
+}
+
+// Writes every epic, then their stories (in each epic's order). The trash is left out, and so are stories no epic links to
+// since import has nowhere to put them.
+pub fn export(db_state: &DBState, out: impl Write) -> Result<()> {
+    let mut writer = csv::Writer::from_writer(out);
+    let mut epic_ids: Vec<&u32> = db_state.epics.iter().filter(|(_, epic)| !epic.archived).map(|(id, _)| id).collect();
+    epic_ids.sort();
+
+    for epic_id in &epic_ids {
+        let epic = &db_state.epics[epic_id];
+        writer.serialize(CsvRow { kind: "epic".to_owned(), id: **epic_id, epic_id: None, name: epic.name.clone(), description: epic.description.clone(), status: epic.status.to_string(), owner: epic.owner.clone(), assignee: None, reporter: None, priority: "".to_owned(), points: None, labels: epic.labels.iter().join(", ") })?;
+    }
+    let mut exported = std::collections::HashSet::new();
+    for epic_id in &epic_ids {
+        for story_id in &db_state.epics[epic_id].stories {
+            if let Some(story) = db_state.stories.get(story_id).filter(|story| !story.archived).filter(|_| exported.insert(*story_id)) {
+                writer.serialize(CsvRow { kind: "story".to_owned(), id: *story_id, epic_id: Some(**epic_id), name: story.name.clone(), description: story.description.clone(), status: story.status.to_string(), owner: None, assignee: story.assignee.clone(), reporter: story.reporter.clone(), priority: story.priority.to_string(), points: story.points, labels: story.labels.iter().join(", ") })?;
+            }
+        }
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+fn parse_status(status: &str) -> Result<Status> {
+    if status.trim().is_empty() {
+        return Ok(Status::Open);
+    }
+    status.parse::<Status>().map_err(|error| anyhow!(error))
+}
+
+fn parse_priority(priority: &str) -> Result<Priority> {
+    if priority.trim().is_empty() {
+        return Ok(Priority::default());
+    }
+    priority.parse::<Priority>().map_err(|error| anyhow!(error))
+}
+
+fn parse_labels(labels: &str) -> Result<BTreeSet<String>> {
+    labels.split(',').filter(|label| !label.trim().is_empty()).map(|label| normalize_label(label).map_err(|error| anyhow!(error))).collect()
+}
+
+// Imports the rows with freshly allocated ids. Invalid rows are skipped and reported, everything else lands in one write.
+pub fn import(db: &JiraDatabase, input: impl Read) -> Result<ImportReport> {
+    let mut reader = csv::Reader::from_reader(input);
+    let mut report = ImportReport::default();
+    let mut epics = vec![];
+    let mut stories = vec![];
+
+    for (index, row) in reader.deserialize::<CsvRow>().enumerate() {
+        let line = index + 2; //the header is line 1
+        let row = match row {
+            Ok(row) => row,
+            Err(error) => {
+                report.invalid_rows.push((line, error.to_string()));
+                continue;
+            }
+        };
+        let fields = parse_status(&row.status).and_then(|status| Ok((status, parse_labels(&row.labels)?)));
+        match (row.kind.trim().to_lowercase().as_str(), fields) {
+            (_, Err(error)) => report.invalid_rows.push((line, error.to_string())),
+            ("epic", Ok(fields)) => epics.push((line, row, fields)),
+            ("story", Ok(fields)) => stories.push((line, row, fields)),
+            (kind, Ok(_)) => report.invalid_rows.push((line, format!("unknown type \"{}\" (expected epic or story)", kind))),
+        }
+    }
+
+    db.transaction(|tx| {
+        let mut new_epic_ids: HashMap<u32, u32> = HashMap::new(); //id in the file -> id allocated in the database
+        for (line, row, (status, labels)) in epics {
+            if new_epic_ids.contains_key(&row.id) {
+                report.invalid_rows.push((line, format!("duplicate epic id {}", row.id)));
+                continue;
+            }
+            let mut epic = Epic::new(row.name, row.description);
+            epic.status = status;
+            epic.owner = row.owner;
+            epic.labels = labels;
+            new_epic_ids.insert(row.id, tx.create_epic(epic)?);
+            report.epics_created += 1;
+        }
+
+        let existing_epics = tx.read_db()?.epics;
+        for (line, row, (status, labels)) in stories {
+            let priority = match parse_priority(&row.priority) {
+                Ok(priority) => priority,
+                Err(error) => {
+                    report.invalid_rows.push((line, error.to_string()));
+                    continue;
+                }
+            };
+            let epic_id = match row.epic_id {
+                Some(epic_id) if new_epic_ids.contains_key(&epic_id) => new_epic_ids[&epic_id],
+                Some(epic_id) if existing_epics.get(&epic_id).is_some_and(|epic| !epic.archived) => epic_id,
+                Some(epic_id) if existing_epics.contains_key(&epic_id) => {
+                    report.invalid_rows.push((line, format!("epic {} is in the trash", epic_id)));
+                    continue;
+                }
+                Some(epic_id) => {
+                    report.invalid_rows.push((line, format!("epic {} is neither in the file nor in the database", epic_id)));
+                    continue;
+                }
+                None => {
+                    report.invalid_rows.push((line, "story has no epic_id".to_owned()));
+                    continue;
+                }
+            };
+            let mut story = Story::new(row.name, row.description);
+            story.status = status;
+            story.assignee = row.assignee;
+            story.reporter = row.reporter;
+            story.priority = priority;
+            story.points = row.points;
+            story.labels = labels;
+            tx.create_story(story, epic_id)?;
+            report.stories_created += 1;
+        }
+        Ok(())
+    })?;
+
+    report.invalid_rows.sort();
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_utils::MockDB;
+    use crate::models::IntegrityIssue;
+    use crate::workflow::Workflow;
+
+    #[test]
+    fn export_then_import_should_preserve_epics_and_stories() {
+        let db = JiraDatabase { database: Box::new(MockDB::new()), user: None, workflow: Workflow::default() };
+        let epic_id = db.create_epic(Epic::new("epic, with comma".to_owned(), "multi\nline".to_owned())).unwrap();
+        let story_id = db.create_story(Story::new("story".to_owned(), "".to_owned()), epic_id).unwrap();
+        db.update_story_status(story_id, Status::InProgress).unwrap();
+        db.assign_story(story_id, Some("alice".to_owned())).unwrap();
+        db.estimate_story(story_id, Priority::Low, Some(2)).unwrap();
+
+        let mut file = vec![];
+        export(&db.read_db().unwrap(), &mut file).unwrap();
+
+        let other_db = JiraDatabase { database: Box::new(MockDB::new()), user: None, workflow: Workflow::default() };
+        other_db.create_epic(Epic::new("already there".to_owned(), "".to_owned())).unwrap();
+        let report = import(&other_db, file.as_slice()).unwrap();
+        assert_eq!(report, ImportReport { epics_created: 1, stories_created: 1, invalid_rows: vec![] });
+
+        let db_state = other_db.read_db().unwrap();
+        assert_eq!(db_state.last_item_id, 3); //ids continue from last_item_id instead of reusing the file's
+        assert_eq!(db_state.epics[&2].name, "epic, with comma");
+        assert_eq!(db_state.epics[&2].description, "multi\nline");
+        assert_eq!(db_state.epics[&2].stories, vec![3]);
+        assert_eq!(db_state.stories[&3].status, Status::InProgress);
+        assert_eq!(db_state.stories[&3].assignee, Some("alice".to_owned()));
+        assert_eq!((db_state.stories[&3].priority, db_state.stories[&3].points), (Priority::Low, Some(2)));
+    }
+
+    #[test]
+    fn export_import_export_should_round_trip() {
+        let db = JiraDatabase { database: Box::new(MockDB::new()), user: None, workflow: Workflow::default() };
+        let first_epic_id = db.create_epic(Epic::new("first".to_owned(), "".to_owned())).unwrap();
+        let second_epic_id = db.create_epic(Epic::new("second".to_owned(), "".to_owned())).unwrap();
+        let story_id = db.create_story(Story::new("story".to_owned(), "".to_owned()), first_epic_id).unwrap();
+        db.create_story(Story::new("other story".to_owned(), "".to_owned()), second_epic_id).unwrap();
+        db.add_label(first_epic_id, "backend").unwrap();
+        db.add_label(story_id, "backend").unwrap();
+        db.add_label(story_id, "tech-debt").unwrap();
+        db.database.put_story(99, &Story::new("orphan".to_owned(), "".to_owned())).unwrap(); //no epic links to it
+
+        let mut file = vec![];
+        export(&db.read_db().unwrap(), &mut file).unwrap();
+        let other_db = JiraDatabase { database: Box::new(MockDB::new()), user: None, workflow: Workflow::default() };
+        let report = import(&other_db, file.as_slice()).unwrap();
+        let mut round_tripped = vec![];
+        export(&other_db.read_db().unwrap(), &mut round_tripped).unwrap();
+
+        assert_eq!(report, ImportReport { epics_created: 2, stories_created: 2, invalid_rows: vec![] });
+        assert_eq!(String::from_utf8(round_tripped).unwrap(), String::from_utf8(file).unwrap());
+        let db_state = other_db.read_db().unwrap();
+        assert_eq!(db_state.labelled("backend"), BTreeSet::from([first_epic_id, story_id]));
+        assert!(!db_state.validate().contains(&IntegrityIssue::StaleLabelIndex));
+    }
+
+    #[test]
+    fn import_should_report_invalid_rows_and_apply_the_rest() {
+        let db = JiraDatabase { database: Box::new(MockDB::new()), user: None, workflow: Workflow::default() };
+        let existing_epic_id = db.create_epic(Epic::new("existing".to_owned(), "".to_owned())).unwrap();
+        let file = format!("type,id,epic_id,name,description,status
+epic,10,,new epic,,OPEN
+story,11,10,in new epic,,CLOSED
+story,12,{},in existing epic,,
+story,13,99,no such epic,,OPEN
+story,14,10,bad status,,DONE
+task,15,10,bad type,,OPEN
+story,not a number,10,bad id,,OPEN
+", existing_epic_id);
+
+        let report = import(&db, file.as_bytes()).unwrap();
+
+        assert_eq!(report.epics_created, 1);
+        assert_eq!(report.stories_created, 2);
+        assert_eq!(report.invalid_rows.iter().map(|(line, _)| *line).collect::<Vec<_>>(), vec![5, 6, 7, 8]);
+
+        let db_state = db.read_db().unwrap();
+        assert_eq!(db_state.epics[&existing_epic_id].stories.len(), 1);
+        assert_eq!(db_state.epics.len(), 2);
+        assert_eq!(db_state.stories.len(), 2);
+    }
+
+    #[test]
+    fn import_should_report_stories_of_epics_in_the_trash() {
+        let db = JiraDatabase { database: Box::new(MockDB::new()), user: None, workflow: Workflow::default() };
+        let live_epic_id = db.create_epic(Epic::new("live".to_owned(), "".to_owned())).unwrap();
+        let trashed_epic_id = db.create_epic(Epic::new("trashed".to_owned(), "".to_owned())).unwrap();
+        db.delete_epic(trashed_epic_id).unwrap();
+        let file = format!("type,id,epic_id,name,description,status
+story,1,{},in live epic,,OPEN
+story,2,{},in trashed epic,,OPEN
+", live_epic_id, trashed_epic_id);
+
+        let report = import(&db, file.as_bytes()).unwrap(); //the bad row doesn't abort the import
+
+        assert_eq!(report.stories_created, 1);
+        assert_eq!(report.invalid_rows, vec![(3, format!("epic {} is in the trash", trashed_epic_id))]);
+        assert!(db.read_db().unwrap().epics[&trashed_epic_id].stories.is_empty());
+    }
+}
@@ -1,88 +1,466 @@
-use anyhow::{Result, anyhow};
-use std::fs;
-use crate::models::{DBState, Epic, Story, Status};
+use anyhow::{Result, anyhow, Context};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{ErrorKind, Write};
+use std::path::Path;
+use serde::{de::IgnoredAny, Serialize};
+use serde_json::{Map, Value};
+use chrono::Utc;
+use itertools::Itertools;
+use crate::models::{normalize_label, Comment, DBState, Epic, FieldChange, HistoryEntry, IntegrityIssue, ItemStates, Priority, Story, Status};
+use crate::query::Query;
+use crate::workflow::Workflow;
+
+#[cfg(feature = "sqlite")]
+mod sqlite;
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteDatabase;
 
 pub struct JiraDatabase {
-    pub database: Box<dyn Database>
+    pub database: Box<dyn Database>,
+    pub user: Option<String>, //recorded in the history log as the author of every change made through this handle
+    pub workflow: Workflow, //the story status changes update_story_status accepts
 }
 
-impl JiraDatabase {
-    pub fn new(file_path: String) -> Self {
-        Self {
-            database: Box::new(JSONFileDatabase{
-                file_path
-            }) //creates new instance of JSONFileDatabase, which implements the Database traits 'read' and 'write', using file_path of the location of DBState
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Backend {
+    Json,
+    #[cfg(feature = "sqlite")]
+    Sqlite,
+}
+
+impl Backend {
+    pub fn from_path(file_path: &str) -> Self { //picks the backend from the file extension, anything that isn't sqlite stays JSON
+        match Path::new(file_path).extension().and_then(|ext| ext.to_str()) {
+            #[cfg(feature = "sqlite")]
+            Some("sqlite" | "sqlite3" | "db") => Backend::Sqlite,
+            _ => Backend::Json,
         }
-    }    
+    }
+}
+
+impl JiraDatabase {
+    pub fn new(file_path: String) -> Result<Self> {
+        let backend = Backend::from_path(&file_path);
+        Self::with_backend(file_path, backend)
+    }
+
+    pub fn with_backend(file_path: String, backend: Backend) -> Result<Self> {
+        let database: Box<dyn Database> = match backend {
+            Backend::Json => {
+                let database = JSONFileDatabase{
+                    file_path
+                }; //creates new instance of JSONFileDatabase, which implements the Database traits 'read' and 'write', using file_path of the location of DBState
+                database.initialize()?;
+                Box::new(database)
+            }
+            #[cfg(feature = "sqlite")]
+            Backend::Sqlite => {
+                if let Some(parent) = Path::new(&file_path).parent().filter(|dir| !dir.as_os_str().is_empty()) {
+                    fs::create_dir_all(parent).with_context(|| format!("could not create directory {}", parent.display()))?;
+                }
+                Box::new(SqliteDatabase::open(&file_path)?) //opening creates the tables, so there is nothing to seed
+            }
+        };
+        Ok(Self { database, user: None, workflow: Workflow::default() })
+    }
+
+    pub fn with_user(self, user: Option<String>) -> Self {
+        Self { user, ..self }
+    }
+
+    pub fn with_workflow(self, workflow: Workflow) -> Self {
+        Self { workflow, ..self }
+    }
+
     pub fn read_db(&self) -> Result<DBState> {
         self.database.read_db() //returns a copy/instance of DBState
     }
 
-    pub fn create_epic(&self, epic: Epic) -> Result<u32> {
-        let mut parsed = self.database.read_db()?;
-        let last_id = parsed.last_item_id;
-        let new_id = last_id + 1;
-        parsed.last_item_id = new_id;
-        parsed.epics.insert(new_id, epic); //takes epic argument, and new_id to create new epic in this instance of DBState, 'parsed'
-        self.database.write_db(&parsed)?; //take 'parsed', now with new epic included, to write over the DBState instance 'self' - i.e. the original DBState
-        Ok(new_id) //confirm write to DBState successful by return new_id of newly-uploaded epic
+    pub fn has_backup(&self) -> bool {
+        self.database.has_backup()
     }
 
-    pub fn create_story(&self, story: Story, epic_id: u32) -> Result<u32> {
-        let mut parsed = self.database.read_db()?; //create parsed instance of DBState
-        let last_id = parsed.last_item_id;
-        let new_id = last_id + 1; //create new id for story
+    pub fn recover_from_backup(&self) -> Result<()> {
+        self.database.recover_from_backup()
+    }
 
-        parsed.last_item_id = new_id;
-        parsed.stories.insert(new_id, story); //add Story struct into DBState using newly-generated new_id
+    pub fn validate(&self) -> Result<Vec<IntegrityIssue>> {
+        Ok(self.database.read_db()?.validate())
+    }
 
-        parsed.epics.get_mut(&epic_id).ok_or_else(|| anyhow!("Couldn't find Epic in database!"))?.stories.push(new_id); // add Story's new_id to Epic's stories field, which contains a Vector of u32 of Story ID's
+    pub fn repair(&self) -> Result<Vec<IntegrityIssue>> { //fixes every integrity issue in one write and returns what was fixed
+        self.transaction(|tx| {
+            let mut db_state = tx.read_db()?;
+            let fixed = db_state.repair();
+            tx.database.write_db(&db_state)?;
+            Ok(fixed)
+        })
+    }
 
-        self.database.write_db(&parsed)?; //write new ID, story struct and Story ID within Epic's Story field, to the parased instance of DBState
-        Ok(new_id) //return new_id value to confirm function that wrote new values to parsed instance of DBState
+    pub fn merge(&self, mut other: DBState) -> Result<HashMap<u32, u32>> { //adds everything in 'other' under new ids in one write, see DBState::merge
+        let now = Utc::now();
+        for epic in other.epics.values_mut() {
+            epic.created_at.get_or_insert(now);
+            epic.updated_at.get_or_insert(now);
+        }
+        for story in other.stories.values_mut() {
+            story.created_at.get_or_insert(now);
+            story.updated_at.get_or_insert(now);
+        }
+        self.transaction(|tx| {
+            let mut db_state = tx.read_db()?;
+            let new_ids = db_state.merge(other);
+            let created = new_ids.values().sorted().filter_map(|id| {
+                let name = db_state.epics.get(id).map(|epic| &epic.name).or_else(|| db_state.stories.get(id).map(|story| &story.name))?;
+                Some(tx.history_entry(*id, ("created", "".to_owned(), name.clone())))
+            }).collect::<Vec<_>>();
+            db_state.history.extend(created);
+            tx.database.write_db(&db_state)?;
+            Ok(new_ids)
+        })
+    }
 
+    // Puts every item, the comments and the saved filters back the way 'items' describes them (None removes one)
+    // in one write, used by undo and redo. Ids are kept as they are, so a redo after an undo lands on the same ids.
+    // Each item's changes are logged.
+    pub fn restore_items(&self, items: &ItemStates) -> Result<()> {
+        self.transaction(|tx| {
+            let mut db_state = tx.read_db()?;
+            let mut changes: Vec<(u32, FieldChange)> = vec![];
+            for (id, epic) in items.epics.iter().sorted_by_key(|(id, _)| **id) {
+                match (db_state.epics.remove(id), epic) {
+                    (Some(old), Some(new)) => changes.extend(old.changes(new).into_iter().map(|change| (*id, change))),
+                    (None, Some(new)) => changes.push((*id, ("restored", "".to_owned(), new.name.clone()))),
+                    (Some(old), None) => changes.push((*id, ("deleted", old.name, "".to_owned()))),
+                    (None, None) => {}
+                }
+                if let Some(epic) = epic {
+                    db_state.epics.insert(*id, epic.clone());
+                }
+                db_state.reindex_labels(*id);
+            }
+            for (id, story) in items.stories.iter().sorted_by_key(|(id, _)| **id) {
+                match (db_state.stories.remove(id), story) {
+                    (Some(old), Some(new)) => changes.extend(old.changes(new).into_iter().map(|change| (*id, change))),
+                    (None, Some(new)) => changes.push((*id, ("restored", "".to_owned(), new.name.clone()))),
+                    (Some(old), None) => changes.push((*id, ("deleted", old.name, "".to_owned()))),
+                    (None, None) => {}
+                }
+                if let Some(story) = story {
+                    db_state.stories.insert(*id, story.clone());
+                }
+                db_state.reindex_labels(*id);
+            }
+            if let Some(comments) = &items.comments {
+                for comment in db_state.comments.iter().filter(|comment| !comments.contains(comment)) {
+                    changes.push((comment.item_id, ("comment", comment.body.clone(), "".to_owned())));
+                }
+                for comment in comments.iter().filter(|comment| !db_state.comments.contains(comment)) {
+                    changes.push((comment.item_id, ("comment", "".to_owned(), comment.body.clone())));
+                }
+                db_state.comments = comments.clone();
+            }
+            for (name, query) in &items.saved_filters {
+                match query {
+                    Some(query) => db_state.saved_filters.insert(name.clone(), query.clone()),
+                    None => db_state.saved_filters.remove(name),
+                };
+            }
+            db_state.history.extend(changes.into_iter().map(|(id, change)| tx.history_entry(id, change)));
+            tx.database.write_db(&db_state)
+        })
     }
 
-    pub fn delete_epic(&self, epic_id: u32) -> Result<()> { //***When you delete an Epic, you also delete all linked Storys in DBState Hashmap of stories */
-        let mut parsed = self.database.read_db()?; //create mut instance of DBState struct within 'parsed' variable
-        
-        for story_id in &parsed.epics.get(&epic_id).ok_or_else(|| anyhow!("could not find epic in database!"))?.stories { // within the Epic struct of DBState, for each element within the Story field, which is a Vector of u32 unique ID's
-            parsed.stories.remove(story_id); //remove all stories using the Story IDs from the Epic struct to remove individual Storys from Stories Hashmap of DBState
+    // Runs 'apply' against a staged in-memory copy of DBState and writes the result back with a single write_db.
+    // If the closure returns an error nothing is written, so the batch lands entirely or not at all.
+    pub fn transaction<T>(&self, apply: impl FnOnce(&JiraDatabase) -> Result<T>) -> Result<T> {
+        let staged = JiraDatabase {
+            database: Box::new(StagedDatabase { state: RefCell::new(self.database.read_db()?) }),
+            user: self.user.clone(),
+            workflow: self.workflow.clone(),
+        };
+        let result = apply(&staged)?; //on error the staged copy is simply dropped
+        self.database.write_db(&staged.read_db()?)?;
+        Ok(result)
+    }
+
+    pub fn create_epic(&self, mut epic: Epic) -> Result<u32> {
+        epic.created_at = Some(Utc::now());
+        epic.updated_at = epic.created_at;
+        self.transaction(|tx| { //the epic and its history entry land in one write
+            let new_id = tx.database.add_epic(&epic)?; //bumps last_item_id and stores the epic under the new value
+            tx.record(new_id, vec![("created", "".to_owned(), epic.name)])?;
+            Ok(new_id) //confirm write to DBState successful by return new_id of newly-uploaded epic
+        })
+    }
+
+    pub fn create_story(&self, mut story: Story, epic_id: u32) -> Result<u32> {
+        story.created_at = Some(Utc::now());
+        story.updated_at = story.created_at;
+        self.transaction(|tx| {
+            tx.database.get_epic(epic_id)?.filter(|epic| !epic.archived).ok_or_else(|| anyhow!("Couldn't find Epic in database!"))?; //no new stories in an epic in the trash
+            let new_id = tx.database.add_story(epic_id, &story)?; //create new id for story, store the Story struct under it and add it to Epic's stories field
+            tx.record(new_id, vec![("created", "".to_owned(), story.name)])?;
+            Ok(new_id) //return new_id value to confirm function that wrote new values to the database
+        })
+    }
+
+    pub fn delete_epic(&self, epic_id: u32) -> Result<()> { //***When you delete an Epic, its linked Storys go to the trash with it, see restore_item and purge_item */
+        self.transaction(|tx| {
+            let mut db_state = tx.read_db()?;
+            let epic = db_state.epics.get_mut(&epic_id).filter(|epic| !epic.archived).ok_or_else(|| anyhow!("could not find epic in database!"))?;
+            epic.archived = true;
+            epic.updated_at = Some(Utc::now());
+            let (epic_name, story_ids) = (epic.name.clone(), epic.stories.clone());
+
+            let mut entries = vec![];
+            for story_id in story_ids {
+                if let Some(story) = db_state.stories.get_mut(&story_id).filter(|story| !story.archived) { //stories already in the trash keep their own entry
+                    story.archived = true;
+                    entries.push(tx.history_entry(story_id, ("deleted", format!("with epic {}", epic_id), "".to_owned())));
+                }
+            }
+            entries.push(tx.history_entry(epic_id, ("deleted", epic_name, "".to_owned())));
+            db_state.history.extend(entries);
+            tx.database.write_db(&db_state)
+        })
+    }
+
+    pub fn delete_story(&self, epic_id: u32, story_id: u32) -> Result<()> { //moves the story to the trash, it stays linked to its epic
+        self.transaction(|tx| {
+            let epic = tx.database.get_epic(epic_id)?.ok_or_else(|| anyhow!("count not find epic in database"))?;
+            if !epic.stories.contains(&story_id) { // from within Epic struct, the stories field has a Vector of u32 story ids
+                return Err(anyhow!("story id not found in epic's stories vector"));
+            }
+
+            let mut story = tx.database.get_story(story_id)?.filter(|story| !story.archived).ok_or_else(|| anyhow!("Could not find story in database!"))?;
+            story.archived = true;
+            story.updated_at = Some(Utc::now());
+            tx.database.put_story(story_id, &story)?;
+            tx.record(story_id, vec![("deleted", story.name, "".to_owned())])
+        })
+    }
+
+    // Takes an epic (with every story in it) or a single story back out of the trash
+    pub fn restore_item(&self, item_id: u32) -> Result<()> {
+        self.transaction(|tx| {
+            let mut db_state = tx.read_db()?;
+            let mut entries = vec![];
+            if let Some(epic) = db_state.epics.get_mut(&item_id).filter(|epic| epic.archived) {
+                epic.archived = false;
+                entries.push(tx.history_entry(item_id, ("restored", "".to_owned(), epic.name.clone())));
+                for story_id in epic.stories.clone() {
+                    if let Some(story) = db_state.stories.get_mut(&story_id).filter(|story| story.archived) {
+                        story.archived = false;
+                        entries.push(tx.history_entry(story_id, ("restored", "".to_owned(), format!("with epic {}", item_id))));
+                    }
+                }
+            } else if db_state.stories.get(&item_id).is_some_and(|story| story.archived) {
+                if let Some(epic_id) = db_state.epic_of(item_id).filter(|epic_id| db_state.epics[epic_id].archived) {
+                    return Err(anyhow!("story {} is in deleted epic {}, restore the epic instead!", item_id, epic_id));
+                }
+                let story = db_state.stories.get_mut(&item_id).unwrap();
+                story.archived = false;
+                entries.push(tx.history_entry(item_id, ("restored", "".to_owned(), story.name.clone())));
+            } else {
+                return Err(anyhow!("item {} is not in the trash!", item_id));
+            }
+            db_state.history.extend(entries);
+            tx.database.write_db(&db_state)
+        })
+    }
+
+    // Removes an item in the trash for good; purging an epic purges its stories. Only the history log remembers them.
+    pub fn purge_item(&self, item_id: u32) -> Result<()> {
+        self.transaction(|tx| {
+            let db_state = tx.read_db()?;
+            if db_state.epics.get(&item_id).is_some_and(|epic| epic.archived) {
+                let epic = tx.database.remove_epic(item_id)?.ok_or_else(|| anyhow!("could not find epic in database!"))?; //remove_epic takes the linked stories with it
+                let mut entries: Vec<HistoryEntry> = epic.stories.iter()
+                    .filter(|story_id| db_state.stories.contains_key(story_id))
+                    .map(|story_id| tx.history_entry(*story_id, ("purged", format!("with epic {}", item_id), "".to_owned())))
+                    .collect();
+                entries.push(tx.history_entry(item_id, ("purged", epic.name, "".to_owned())));
+                tx.database.append_history(&entries)
+            } else if db_state.stories.get(&item_id).is_some_and(|story| story.archived) {
+                let story = tx.database.remove_story(item_id)?; //removes the Story struct and unlinks it from its epic in one step
+                tx.record(item_id, vec![("purged", story.map(|story| story.name).unwrap_or_default(), "".to_owned())])
+            } else {
+                Err(anyhow!("item {} is not in the trash!", item_id))
+            }
+        })
+    }
+
+    // Tags an epic or story; adding a label it already has is not a change
+    pub fn add_label(&self, item_id: u32, label: &str) -> Result<()> {
+        let label = normalize_label(label).map_err(|error| anyhow!(error))?;
+        if self.database.get_epic(item_id)?.is_some() {
+            return self.change_epic(item_id, |epic| { epic.labels.insert(label); });
+        }
+        self.change_story(item_id, |story| { story.labels.insert(label); })
+            .with_context(|| format!("could not find epic or story {} in database!", item_id))
+    }
+
+    pub fn remove_label(&self, item_id: u32, label: &str) -> Result<()> {
+        let label = normalize_label(label).map_err(|error| anyhow!(error))?;
+        if self.database.get_epic(item_id)?.is_some() {
+            return self.change_epic(item_id, |epic| { epic.labels.remove(&label); });
+        }
+        self.change_story(item_id, |story| { story.labels.remove(&label); })
+            .with_context(|| format!("could not find epic or story {} in database!", item_id))
+    }
+
+    // Adds to the thread of an epic or story, signed by the current user
+    pub fn add_comment(&self, item_id: u32, body: &str) -> Result<()> {
+        let author = self.user.clone().ok_or_else(|| anyhow!("no current user to sign the comment, start with --user or set JIRA_USER"))?;
+        let body = body.trim();
+        if body.is_empty() {
+            return Err(anyhow!("comment is empty!"));
         }
+        let at = Utc::now();
+        self.transaction(|tx| { //the comment and the item's updated_at land in one write
+            if let Some(mut epic) = tx.database.get_epic(item_id)? {
+                if epic.archived {
+                    return Err(anyhow!("epic {} is in the trash, restore it first!", item_id));
+                }
+                epic.updated_at = Some(at);
+                tx.database.put_epic(item_id, &epic)?;
+            } else if let Some(mut story) = tx.database.get_story(item_id)? {
+                if story.archived {
+                    return Err(anyhow!("story {} is in the trash, restore it first!", item_id));
+                }
+                story.updated_at = Some(at);
+                tx.database.put_story(item_id, &story)?;
+            } else {
+                return Err(anyhow!("could not find epic or story {} in database!", item_id));
+            }
+            tx.database.append_comment(&Comment { item_id, at, author, body: body.to_owned() })
+        })
+    }
 
-        parsed.epics.remove(&epic_id); //remove epic from parsed instance of DBState with epic_id from provided the function arguments' parameters
+    // Saves a named filter, replacing any filter with the same name; it has to parse so a saved filter always opens
+    pub fn save_filter(&self, name: &str, query: &str) -> Result<()> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(anyhow!("filter name is empty!"));
+        }
+        Query::parse(query).map_err(|error| anyhow!("invalid filter, {}", error))?;
+        self.database.put_saved_filter(name, query.trim())
+    }
 
-        self.database.write_db(&parsed)?;
+    pub fn delete_filter(&self, name: &str) -> Result<()> {
+        if !self.database.remove_saved_filter(name)? {
+            return Err(anyhow!("could not find saved filter \"{}\"!", name));
+        }
         Ok(())
     }
 
-    pub fn delete_story(&self, epic_id: u32, story_id: u32) -> Result<()> {
-        let mut parsed = self.database.read_db()?;
+    pub fn update_epic(&self, epic_id: u32, name: String, description: String) -> Result<()> { //rename and/or re-describe the epic, its stories and status are kept
+        self.change_epic(epic_id, |epic| {
+            epic.name = name;
+            epic.description = description;
+        })
+    }
 
-        let epic = parsed.epics.get_mut(&epic_id).ok_or_else(|| anyhow!("count not find epic in database"))?;
-        let story_index = epic.stories.iter().position(|id| id == &story_id).ok_or_else(|| anyhow!("story id not found in epic's stories vector"))?;// from within Epic struct, the stories field has a Vector of u32, returning index of Story with provided story_id.
+    pub fn update_story(&self, story_id: u32, name: String, description: String) -> Result<()> {
+        self.change_story(story_id, |story| {
+            story.name = name;
+            story.description = description;
+        })
+    }
 
+    pub fn assign_story(&self, story_id: u32, assignee: Option<String>) -> Result<()> { //None unassigns the story
+        self.change_story(story_id, |story| story.assignee = assignee)
+    }
 
-        epic.stories.remove(story_index); // within Epic struct (chose from epics hashmap using provided epic_id) from parsed DBState instance, remove story_id based on story_id provided in function argument's parameters
+    pub fn estimate_story(&self, story_id: u32, priority: Priority, points: Option<u32>) -> Result<()> {
+        self.change_story(story_id, |story| {
+            story.priority = priority;
+            story.points = points;
+        })
+    }
 
-        parsed.stories.remove(&story_id);//within parsed DBState instance, remove Story structs from stories HashMap using provided story_id
+    pub fn set_epic_owner(&self, epic_id: u32, owner: Option<String>) -> Result<()> {
+        self.change_epic(epic_id, |epic| epic.owner = owner)
+    }
 
-        self.database.write_db(&parsed)?;
-        Ok(())
+    pub fn move_story(&self, story_id: u32, from_epic_id: u32, to_epic_id: u32) -> Result<()> { //re-parents the story, both epics change in the same write
+        if from_epic_id == to_epic_id {
+            return Err(anyhow!("story is already in epic {}!", to_epic_id));
+        }
+        self.transaction(|tx| {
+            let mut from_epic = tx.database.get_epic(from_epic_id)?.ok_or_else(|| anyhow!("could not find epic {} in database!", from_epic_id))?;
+            let mut to_epic = tx.database.get_epic(to_epic_id)?.filter(|epic| !epic.archived).ok_or_else(|| anyhow!("could not find epic {} in database!", to_epic_id))?;
+            let story_index = from_epic.stories.iter().position(|id| id == &story_id).ok_or_else(|| anyhow!("story id not found in epic's stories vector"))?;
+
+            from_epic.stories.remove(story_index);
+            to_epic.stories.push(story_id);
+            tx.database.put_epic(from_epic_id, &from_epic)?;
+            tx.database.put_epic(to_epic_id, &to_epic)?;
+            if let Some(mut story) = tx.database.get_story(story_id)? {
+                story.updated_at = Some(Utc::now());
+                tx.database.put_story(story_id, &story)?;
+            }
+            tx.record(story_id, vec![("epic", from_epic_id.to_string(), to_epic_id.to_string())])
+        })
     }
 
     pub fn update_epic_status(&self, epic_id:u32, status: Status) -> Result<()> {
-        let mut parsed = self.database.read_db()?;
-        parsed.epics.get_mut(&epic_id).ok_or_else( || anyhow!("Could not find epic in database!"))?.status = status;
-        self.database.write_db(&parsed)?;
-        Ok(())
+        self.change_epic(epic_id, |epic| epic.status = status)
     }
 
-    pub fn update_story_status(&self, story_id: u32, status: Status) -> Result<()> {
-        let mut parsed = self.database.read_db()?;
-        parsed.stories.get_mut(&story_id).ok_or_else( || anyhow!("Could not find story in database!"))?.status = status;
-        self.database.write_db(&parsed)?;
-        Ok(())
+    pub fn update_story_status(&self, story_id: u32, status: Status) -> Result<()> { //only along the transitions of the workflow
+        let story = self.database.get_story(story_id)?.ok_or_else(|| anyhow!("Could not find story in database!"))?;
+        self.workflow.check_transition(&story.status, &status)?;
+        self.change_story(story_id, |story| story.status = status)
+    }
+
+    // Applies 'change' to the stored epic; if any field actually changed, bumps updated_at and logs each change
+    fn change_epic(&self, epic_id: u32, change: impl FnOnce(&mut Epic)) -> Result<()> {
+        let old = self.database.get_epic(epic_id)?.ok_or_else(|| anyhow!("Could not find epic in database!"))?;
+        if old.archived {
+            return Err(anyhow!("epic {} is in the trash, restore it first!", epic_id));
+        }
+        let mut epic = old.clone();
+        change(&mut epic);
+        let changes = old.changes(&epic);
+        if changes.is_empty() {
+            return Ok(());
+        }
+        epic.updated_at = Some(Utc::now());
+        self.transaction(|tx| { //the change and its history entries are written together
+            tx.database.put_epic(epic_id, &epic)?;
+            tx.record(epic_id, changes)
+        })
+    }
+
+    fn change_story(&self, story_id: u32, change: impl FnOnce(&mut Story)) -> Result<()> {
+        let old = self.database.get_story(story_id)?.ok_or_else(|| anyhow!("Could not find story in database!"))?;
+        if old.archived {
+            return Err(anyhow!("story {} is in the trash, restore it first!", story_id));
+        }
+        let mut story = old.clone();
+        change(&mut story);
+        let changes = old.changes(&story);
+        if changes.is_empty() {
+            return Ok(());
+        }
+        story.updated_at = Some(Utc::now());
+        self.transaction(|tx| {
+            tx.database.put_story(story_id, &story)?;
+            tx.record(story_id, changes)
+        })
+    }
+
+    fn history_entry(&self, item_id: u32, (field, old, new): FieldChange) -> HistoryEntry {
+        HistoryEntry { item_id, at: Utc::now(), user: self.user.clone(), field: field.to_owned(), old, new }
+    }
+
+    fn record(&self, item_id: u32, changes: Vec<FieldChange>) -> Result<()> {
+        let entries: Vec<HistoryEntry> = changes.into_iter().map(|change| self.history_entry(item_id, change)).collect();
+        self.database.append_history(&entries)
     }
 }
 
@@ -92,28 +470,323 @@ pub trait Database {
 
     fn write_db(&self, db_state: &DBState) -> Result<()>;
 
+    // Fine-grained operations. The defaults fall back to a read-modify-write of the whole DBState,
+    // backends that can touch a single row (see SqliteDatabase) override them.
+
+    fn add_epic(&self, epic: &Epic) -> Result<u32> { //allocates a new id and stores the epic under it in one write
+        let mut db_state = self.read_db()?;
+        db_state.last_item_id += 1;
+        db_state.epics.insert(db_state.last_item_id, epic.clone());
+        db_state.reindex_labels(db_state.last_item_id);
+        self.write_db(&db_state)?;
+        Ok(db_state.last_item_id)
+    }
+
+    fn add_story(&self, epic_id: u32, story: &Story) -> Result<u32> { //allocates a new id, stores the story and links it to the epic in one write
+        let mut db_state = self.read_db()?;
+        let new_id = db_state.last_item_id + 1;
+        db_state.epics.get_mut(&epic_id).ok_or_else(|| anyhow!("could not find epic {} in database!", epic_id))?.stories.push(new_id);
+        db_state.last_item_id = new_id;
+        db_state.stories.insert(new_id, story.clone());
+        db_state.reindex_labels(new_id);
+        self.write_db(&db_state)?;
+        Ok(new_id)
+    }
+
+    fn get_epic(&self, epic_id: u32) -> Result<Option<Epic>> {
+        Ok(self.read_db()?.epics.remove(&epic_id))
+    }
+
+    fn get_story(&self, story_id: u32) -> Result<Option<Story>> {
+        Ok(self.read_db()?.stories.remove(&story_id))
+    }
+
+    fn put_epic(&self, epic_id: u32, epic: &Epic) -> Result<()> { //inserts or replaces the epic, including its list of story ids
+        let mut db_state = self.read_db()?;
+        db_state.epics.insert(epic_id, epic.clone());
+        db_state.reindex_labels(epic_id);
+        self.write_db(&db_state)
+    }
+
+    fn put_story(&self, story_id: u32, story: &Story) -> Result<()> { //inserts or replaces the story, links are left alone
+        let mut db_state = self.read_db()?;
+        db_state.stories.insert(story_id, story.clone());
+        db_state.reindex_labels(story_id);
+        self.write_db(&db_state)
+    }
+
+    fn remove_epic(&self, epic_id: u32) -> Result<Option<Epic>> { //removes the epic together with every story linked to it
+        let mut db_state = self.read_db()?;
+        let epic = db_state.epics.remove(&epic_id);
+        if let Some(epic) = &epic {
+            for story_id in &epic.stories {
+                db_state.stories.remove(story_id);
+                db_state.reindex_labels(*story_id);
+            }
+            db_state.reindex_labels(epic_id);
+            self.write_db(&db_state)?;
+        }
+        Ok(epic)
+    }
+
+    fn remove_story(&self, story_id: u32) -> Result<Option<Story>> { //removes the story and unlinks it from every epic
+        let mut db_state = self.read_db()?;
+        let story = db_state.stories.remove(&story_id);
+        db_state.reindex_labels(story_id);
+        for epic in db_state.epics.values_mut() {
+            epic.stories.retain(|id| *id != story_id);
+        }
+        self.write_db(&db_state)?;
+        Ok(story)
+    }
+
+    fn append_history(&self, entries: &[HistoryEntry]) -> Result<()> { //adds to the end of the log, existing entries are never touched
+        if entries.is_empty() {
+            return Ok(());
+        }
+        let mut db_state = self.read_db()?;
+        db_state.history.extend_from_slice(entries);
+        self.write_db(&db_state)
+    }
+
+    fn append_comment(&self, comment: &Comment) -> Result<()> {
+        let mut db_state = self.read_db()?;
+        db_state.comments.push(comment.clone());
+        self.write_db(&db_state)
+    }
+
+    fn put_saved_filter(&self, name: &str, query: &str) -> Result<()> { //inserts or replaces the filter
+        let mut db_state = self.read_db()?;
+        db_state.saved_filters.insert(name.to_owned(), query.to_owned());
+        self.write_db(&db_state)
+    }
+
+    fn remove_saved_filter(&self, name: &str) -> Result<bool> { //false when there was no such filter
+        let mut db_state = self.read_db()?;
+        let removed = db_state.saved_filters.remove(name).is_some();
+        if removed {
+            self.write_db(&db_state)?;
+        }
+        Ok(removed)
+    }
+
+    fn has_backup(&self) -> bool { //backends that don't keep a previous generation of the state have nothing to recover from
+        false
+    }
+
+    fn recover_from_backup(&self) -> Result<()> {
+        Err(anyhow!("this database does not keep backups!"))
+    }
 }
 
 struct JSONFileDatabase {
     pub file_path: String,
 }
 
+impl JSONFileDatabase {
+    fn backup_path(&self) -> String {
+        format!("{}.bak", self.file_path) //previous generation of the db file, kept next to it
+    }
+
+    // Seeds an empty DBState when the db file is missing or empty, creating its parent directory if needed.
+    // Anything else (including a corrupt file) is left alone so read_db can report it.
+    fn initialize(&self) -> Result<()> {
+        let is_blank = match fs::read_to_string(&self.file_path) {
+            Ok(content) => content.trim().is_empty(),
+            Err(error) if error.kind() == ErrorKind::NotFound => true,
+            Err(error) => return Err(error).with_context(|| format!("could not read database file {}", self.file_path)),
+        };
+        if !is_blank || self.has_backup() { //a blank file next to a backup is a failed write, not a fresh install
+            return Ok(());
+        }
+
+        if let Some(parent) = Path::new(&self.file_path).parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(parent).with_context(|| format!("could not create directory {}", parent.display()))?;
+        }
+        self.write_db(&DBState { last_item_id: 0, ..Default::default() })
+    }
+}
+
+// In-memory Database that collects the changes made inside JiraDatabase::transaction
+struct StagedDatabase {
+    state: RefCell<DBState>,
+}
+
+impl Database for StagedDatabase {
+    fn read_db(&self) -> Result<DBState> {
+        Ok(self.state.borrow().clone())
+    }
+
+    fn write_db(&self, db_state: &DBState) -> Result<()> {
+        *self.state.borrow_mut() = db_state.clone();
+        Ok(())
+    }
+}
+
+// Writes to a temp file in the same directory, fsyncs it and then renames it over 'path', so a crash or a full disk
+// in the middle of a write can never leave a truncated file behind (rename within one directory is atomic)
+fn write_atomically(path: &str, contents: &[u8]) -> Result<()> {
+    let parent = Path::new(path).parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or_else(|| Path::new("."));
+    let mut temp_file = tempfile::NamedTempFile::new_in(parent).with_context(|| format!("could not create temp file next to {}", path))?; //unique name, so concurrent writers can't clobber each other's temp file
+    temp_file.write_all(contents).with_context(|| format!("could not write temp file {}", temp_file.path().display()))?;
+    temp_file.as_file().sync_all()?; //make sure the bytes are on disk before the rename makes them visible
+    temp_file.persist(path).with_context(|| format!("could not replace {}", path))?;
+
+    #[cfg(unix)] //persist the rename itself by syncing the parent directory entry
+    File::open(parent)?.sync_all()?;
+    Ok(())
+}
+
+// Version of the document written by JSONFileDatabase. Bump it and append a step to MIGRATIONS whenever the
+// persisted shape of DBState changes, even by a field with a serde default: an older build then refuses the document
+// instead of silently dropping the field on its next write.
+pub const SCHEMA_VERSION: u32 = 8;
+
+type Migration = fn(&mut Map<String, Value>) -> Result<()>;
+
+// MIGRATIONS[n] upgrades a document from schema_version n to n + 1; documents written before versioning are version 0
+const MIGRATIONS: &[Migration] = &[
+    migrate_v0_to_v1,
+    migrate_v1_to_v2,
+    migrate_v2_to_v3,
+    migrate_v3_to_v4,
+    migrate_v4_to_v5,
+    migrate_v5_to_v6,
+    migrate_v6_to_v7,
+    migrate_v7_to_v8,
+];
+
+fn migrate_v0_to_v1(_document: &mut Map<String, Value>) -> Result<()> {
+    Ok(()) //v1 only introduced the schema_version stamp itself, epics and stories are unchanged
+}
+
+fn migrate_v1_to_v2(document: &mut Map<String, Value>) -> Result<()> { //epics got an owner, stories an assignee and a reporter
+    add_item_field(document, "epics", "owner", Value::Null)?;
+    add_item_field(document, "stories", "assignee", Value::Null)?;
+    add_item_field(document, "stories", "reporter", Value::Null)
+}
+
+fn migrate_v2_to_v3(document: &mut Map<String, Value>) -> Result<()> { //stories got a priority and an estimate
+    add_item_field(document, "stories", "priority", serde_json::to_value(Priority::default())?)?;
+    add_item_field(document, "stories", "points", Value::Null)
+}
+
+fn migrate_v3_to_v4(document: &mut Map<String, Value>) -> Result<()> { //timestamps on epics and stories, and the history log
+    for kind in ["epics", "stories"] {
+        add_item_field(document, kind, "created_at", Value::Null)?; //unknown for items created before v4
+        add_item_field(document, kind, "updated_at", Value::Null)?;
+    }
+    document.entry("history").or_insert_with(|| Value::Array(vec![]));
+    Ok(())
+}
+
+fn migrate_v4_to_v5(document: &mut Map<String, Value>) -> Result<()> { //the trash: epics and stories can be archived
+    add_item_field(document, "epics", "archived", Value::Bool(false))?;
+    add_item_field(document, "stories", "archived", Value::Bool(false))
+}
+
+fn migrate_v5_to_v6(document: &mut Map<String, Value>) -> Result<()> { //comment threads
+    document.entry("comments").or_insert_with(|| Value::Array(vec![]));
+    Ok(())
+}
+
+fn migrate_v6_to_v7(document: &mut Map<String, Value>) -> Result<()> { //labels, and the index over them (empty, as no item has a label yet)
+    add_item_field(document, "epics", "labels", Value::Array(vec![]))?;
+    add_item_field(document, "stories", "labels", Value::Array(vec![]))?;
+    document.entry("label_index").or_insert_with(|| Value::Object(Map::new()));
+    Ok(())
+}
+
+fn migrate_v7_to_v8(document: &mut Map<String, Value>) -> Result<()> { //saved filters
+    document.entry("saved_filters").or_insert_with(|| Value::Object(Map::new()));
+    Ok(())
+}
+
+// Gives every entry of the "epics" or "stories" object 'field' with 'value', unless it already has one
+fn add_item_field(document: &mut Map<String, Value>, kind: &str, field: &str, value: Value) -> Result<()> {
+    let items = match document.get_mut(kind) {
+        Some(items) => items.as_object_mut().ok_or_else(|| anyhow!("expected {} to be an object", kind))?,
+        None => return Ok(()), //left for deserialization to report
+    };
+    for (id, item) in items.iter_mut() {
+        let item = item.as_object_mut().ok_or_else(|| anyhow!("expected item {} to be an object", id))?;
+        item.entry(field).or_insert_with(|| value.clone());
+    }
+    Ok(())
+}
+
+// Runs every migration step between the document's schema_version and SCHEMA_VERSION, then deserializes it
+fn upgrade_document(mut document: Value) -> Result<DBState> {
+    let object = document.as_object_mut().ok_or_else(|| anyhow!("expected a JSON object at the top level"))?;
+    let version = match object.get("schema_version") {
+        None => 0,
+        Some(version) => version.as_u64().and_then(|version| u32::try_from(version).ok()).ok_or_else(|| anyhow!("invalid schema_version {}", version))?,
+    };
+    if version > SCHEMA_VERSION { //refuse instead of dropping fields we don't know about on the next write
+        return Err(anyhow!("schema_version {} was written by a newer version of this tool (this build supports up to {})", version, SCHEMA_VERSION));
+    }
+
+    for (from_version, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        migration(object).with_context(|| format!("failed to migrate from schema_version {} to {}", from_version, from_version + 1))?;
+    }
+    object.insert("schema_version".to_owned(), SCHEMA_VERSION.into());
+
+    Ok(serde_json::from_value(document)?)
+}
+
+fn is_valid_document(content: &[u8]) -> bool {
+    serde_json::from_slice(content).map_err(anyhow::Error::from).and_then(upgrade_document).is_ok()
+}
+
+#[derive(Serialize)]
+struct VersionedDBState<'a> { //what actually lands on disk: DBState's fields plus the schema_version stamp
+    schema_version: u32,
+    #[serde(flatten)]
+    state: &'a DBState,
+}
+
 impl Database for JSONFileDatabase {
 
     fn read_db(&self) -> Result<DBState> {
-      let content = fs::read_to_string(&self.file_path)?; // read the content's of self.file_path 
-      let parsed: DBState = serde_json::from_str(&content)?; //deserialize content variable using serde_json
+      let content = fs::read_to_string(&self.file_path).with_context(|| format!("could not read database file {}", self.file_path))?; // read the content's of self.file_path 
+      let document: Value = serde_json::from_str(&content).with_context(|| { //deserialize content variable using serde_json
+          if self.has_backup() {
+              format!("database file {} is corrupt (a backup is available at {})", self.file_path, self.backup_path())
+          } else {
+              format!("database file {} is corrupt", self.file_path)
+          }
+      })?;
+      let parsed = upgrade_document(document).with_context(|| format!("could not load database file {}", self.file_path))?;
       Ok(parsed)
     }
 
     fn write_db(&self, db_state: &DBState) -> Result<()> {
-        fs::write(&self.file_path, serde_json::to_vec(&db_state)?)?; // serialize db_state to json and store it in self.file_path
-        Ok(())
+        let contents = serde_json::to_vec(&VersionedDBState { schema_version: SCHEMA_VERSION, state: db_state })?; // serialize db_state to json before touching any file
+
+        if let Ok(previous) = fs::read(&self.file_path) {
+            if serde_json::from_slice::<IgnoredAny>(&previous).is_ok() { //never let a corrupt primary file overwrite a good backup; a syntax check catches torn writes without migrating the file
+                write_atomically(&self.backup_path(), &previous)?;
+            }
+        }
+        write_atomically(&self.file_path, &contents) // store the new generation in self.file_path
+    }
+
+    fn has_backup(&self) -> bool {
+        Path::new(&self.backup_path()).exists()
+    }
+
+    fn recover_from_backup(&self) -> Result<()> {
+        let backup = fs::read(self.backup_path()).with_context(|| format!("could not read backup file {}", self.backup_path()))?;
+        if !is_valid_document(&backup) {
+            return Err(anyhow!("backup file {} is corrupt as well", self.backup_path()));
+        }
+        write_atomically(&self.file_path, &backup) //the backup stays in place, so recovering twice is harmless
     }
 }
 
+#[cfg(test)]
 pub mod test_utils {
-    use std::{cell::RefCell, collections::HashMap};
+    use std::cell::RefCell;
 
     use super::*;
     pub struct MockDB {
@@ -125,7 +798,7 @@ pub mod test_utils {
                                // remember to instantiate new MockDB struct as a RefCell (i.e. single owner, but field inside struct are mutable/writeable, even if they are referenced by someone else),
                                // then copy code/instructions to instantiate a new, clean DBState struct called MockDB
             Self {
-                last_written_state: RefCell::new(DBState{last_item_id: 0, epics: HashMap::new(), stories: HashMap::new() })
+                last_written_state: RefCell::new(DBState{last_item_id: 0, ..Default::default() })
             }
         }
     }
@@ -151,220 +824,715 @@ pub mod test_utils {
 
 #[cfg(test)]
 mod tests {
+    use std::collections::{BTreeMap, BTreeSet};
+
     use super::*;
     use super::test_utils::MockDB;
 
+    // every JiraDatabase test runs against each backend compiled into this build
+    fn test_databases() -> Vec<JiraDatabase> {
+        vec![
+            JiraDatabase { database: Box::new(MockDB::new()), user: None, workflow: Workflow::default() },
+            #[cfg(feature = "sqlite")]
+            JiraDatabase { database: Box::new(SqliteDatabase::open_in_memory().unwrap()), user: None, workflow: Workflow::default() },
+        ]
+    }
+
     #[test]
     fn create_epic_should_work() {
-        let db = JiraDatabase{
-            database: Box::new(MockDB::new()),
-        };
-        let epic = Epic::new("".to_owned(), "".to_owned());
-        //TODO: fix this error by deriving the appropr
-        let result = db.create_epic(epic.clone());
+        for db in test_databases() {
+            let epic = Epic::new("".to_owned(), "".to_owned());
+            //TODO: fix this error by deriving the appropr
+            let result = db.create_epic(epic.clone());
 
-        assert_eq!(result.is_ok(), true);
+            assert_eq!(result.is_ok(), true);
 
-        let id = result.unwrap();
-        let db_state = db.read_db().unwrap();
+            let id = result.unwrap();
+            let db_state = db.read_db().unwrap();
 
-        let expected_id = 1;
+            let expected_id = 1;
 
-        assert_eq!(id, expected_id);
-        assert_eq!(db_state.last_item_id, expected_id);
-        assert_eq!(db_state.epics.get(&id), Some(&epic));
+            assert_eq!(id, expected_id);
+            assert_eq!(db_state.last_item_id, expected_id);
+            let stored = db_state.epics.get(&id).unwrap();
+            assert!(stored.created_at.is_some()); //stamped by create_epic, everything else is stored as given
+            assert_eq!(stored, &Epic { created_at: stored.created_at, updated_at: stored.created_at, ..epic });
+        }
     }
 
     #[test]
     fn create_story_should_error_if_invalid_epic_id() {
-        let db = JiraDatabase{
-            database: Box::new(MockDB::new())
-        };
-        let story = Story::new("".to_owned(), "".to_owned());
+        for db in test_databases() {
+            let story = Story::new("".to_owned(), "".to_owned());
         
-        let non_existent_epic_id = 999;
-        let result = db.create_story(story, non_existent_epic_id);
-        assert_eq!(result.is_err(), true);
-
+            let non_existent_epic_id = 999;
+            let result = db.create_story(story, non_existent_epic_id);
+            assert_eq!(result.is_err(), true);
+        }
     }
 
     #[test]
     fn create_story_should_work() {
-        let db = JiraDatabase{
-            database: Box::new(MockDB::new())
-        };
-        let epic = Epic::new("".to_owned(), "".to_owned());
-        let story = Story::new("".to_owned(), "".to_owned());
+        for db in test_databases() {
+            let epic = Epic::new("".to_owned(), "".to_owned());
+            let story = Story::new("".to_owned(), "".to_owned());
 
-        let result = db.create_epic(epic);
-        assert_eq!(result.is_ok(), true);
+            let result = db.create_epic(epic);
+            assert_eq!(result.is_ok(), true);
 
-        let epic_id = result.unwrap();
-        //TODO: fix this error by deriving the appropriate traits for the Story struct
-        let result = db.create_story(story.clone(), epic_id.clone());
-        assert_eq!(result.is_ok(), true);
+            let epic_id = result.unwrap();
+            //TODO: fix this error by deriving the appropriate traits for the Story struct
+            let result = db.create_story(story.clone(), epic_id.clone());
+            assert_eq!(result.is_ok(), true);
 
-        let id = result.unwrap();
-        let db_state = db.read_db().unwrap();
+            let id = result.unwrap();
+            let db_state = db.read_db().unwrap();
 
-        let expected_id = 2;
+            let expected_id = 2;
 
-        assert_eq!(id, expected_id);
-        assert_eq!(db_state.last_item_id, expected_id);
-        assert_eq!(db_state.epics.get(&epic_id).unwrap().stories.contains(&id), true);
-        assert_eq!(db_state.stories.get(&id), Some(&story));
+            assert_eq!(id, expected_id);
+            assert_eq!(db_state.last_item_id, expected_id);
+            assert_eq!(db_state.epics.get(&epic_id).unwrap().stories.contains(&id), true);
+            let stored = db_state.stories.get(&id).unwrap();
+            assert!(stored.created_at.is_some());
+            assert_eq!(stored, &Story { created_at: stored.created_at, updated_at: stored.created_at, ..story });
+        }
     }
 
     #[test]
     fn delete_epic_should_error_if_invalid_epic_id() {
-        let db = JiraDatabase{
-            database: Box::new(MockDB::new()),
-        };
-        let non_existent_epic_id = 999;
+        for db in test_databases() {
+            let non_existent_epic_id = 999;
 
-        let result = db.delete_epic(non_existent_epic_id);
-        assert_eq!(result.is_err(), true);
+            let result = db.delete_epic(non_existent_epic_id);
+            assert_eq!(result.is_err(), true);
+        }
     }
 
     #[test]
     fn delete_epic_should_work() {
-        let db = JiraDatabase{
-            database: Box::new(MockDB::new()),
-        };
-        let epic = Epic::new("".to_owned(), "".to_owned());
-        let story = Story::new("".to_owned(), "".to_owned());
+        for db in test_databases() {
+            let epic = Epic::new("".to_owned(), "".to_owned());
+            let story = Story::new("".to_owned(), "".to_owned());
 
-        let result = db.create_epic(epic);
-        assert_eq!(result.is_ok(), true);
+            let result = db.create_epic(epic);
+            assert_eq!(result.is_ok(), true);
         
-        let epic_id = result.unwrap();
-        let result = db.create_story(story, epic_id);
-        assert_eq!(result.is_ok(), true);
+            let epic_id = result.unwrap();
+            let result = db.create_story(story, epic_id);
+            assert_eq!(result.is_ok(), true);
+
+            let story_id = result.unwrap();
+            let result = db.delete_epic(epic_id);
+            assert_eq!(result.is_ok(), true);
+
+            let db_state = db.read_db().unwrap();
+            let expected_last_id = 2;
+
+            assert_eq!(db_state.last_item_id, expected_last_id);
+            assert!(db_state.epics.get(&epic_id).unwrap().archived); //in the trash, not gone
+            assert!(db_state.stories.get(&story_id).unwrap().archived);
+            assert_eq!(db_state.epics.get(&epic_id).unwrap().stories, vec![story_id]);
+            assert!(db.delete_epic(epic_id).is_err()); //already deleted
+        }
+    }
 
-        let story_id = result.unwrap();
-        let result = db.delete_epic(epic_id);
-        assert_eq!(result.is_ok(), true);
+    #[test]
+    fn delete_story_should_error_if_invalid_epic_id() {
+        for db in test_databases() {
+            let epic = Epic::new("".to_owned(), "".to_owned());
+            let story = Story::new("".to_owned(), "".to_owned());
+            let result = db.create_epic(epic);
+            assert_eq!(result.is_ok(), true);
+
+            let epic_id = result.unwrap();
+            let result = db.create_story(story, epic_id);
+            assert_eq!(result.is_ok(), true);
+
+            let story_id = result.unwrap();
+            let non_existent_epic_id = 999;
+            let result = db.delete_story(non_existent_epic_id, story_id);
+            assert_eq!(result.is_err(), true);
+        }
+    }
 
-        let db_state = db.read_db().unwrap();
-        let expected_last_id = 2;
+    #[test]
+    fn delete_story_should_error_if_story_not_found_in_epic() {
+        for db in test_databases() {
+            let epic = Epic::new("".to_owned(), "".to_owned());
+            let story = Story::new("".to_owned(), "".to_owned());
+            let result = db.create_epic(epic);
+            assert_eq!(result.is_ok(), true);
+
+            let epic_id = result.unwrap();
+            let result = db.create_story(story, epic_id);
+            assert_eq!(result.is_ok(), true);
+
+            let non_existent_story_id = 999;
+            let result = db.delete_story(epic_id, non_existent_story_id);
+            assert_eq!(result.is_err(), true);
+        }
+    }
 
-        assert_eq!(db_state.last_item_id, expected_last_id);
-        assert_eq!(db_state.epics.get(&epic_id), None);
-        assert_eq!(db_state.stories.get(&story_id), None);
+    #[test]
+    fn delete_story_should_work() {
+        for db in test_databases() {
+            let epic = Epic::new("".to_owned(), "".to_owned());
+            let story = Story::new("".to_owned(), "".to_owned());
+            let result = db.create_epic(epic);
+            assert_eq!(result.is_ok(), true);
+
+            let epic_id = result.unwrap();
+            let result = db.create_story(story, epic_id);
+            assert_eq!(result.is_ok(), true);
+
+            let story_id = result.unwrap();
+            let result = db.delete_story(epic_id, story_id);
+            assert_eq!(result.is_ok(), true);
+
+            let db_state = db.read_db().unwrap();
+            let expected_last_id = 2;
+
+            assert_eq!(db_state.last_item_id, expected_last_id);
+            assert_eq!(db_state.epics.get(&epic_id).unwrap().stories.contains(&story_id), true); //stays linked so it can be restored
+            assert!(db_state.stories.get(&story_id).unwrap().archived);
+            assert!(db_state.epic_stories(epic_id).is_empty());
+        }
     }
 
     #[test]
-    fn delete_story_should_error_if_invalid_epic_id() {
-        let db = JiraDatabase{
-            database: Box::new(MockDB::new())
-        };
-        let epic = Epic::new("".to_owned(), "".to_owned());
-        let story = Story::new("".to_owned(), "".to_owned());
-        let result = db.create_epic(epic);
-        assert_eq!(result.is_ok(), true);
+    fn edits_should_error_on_items_in_the_trash() {
+        for db in test_databases() {
+            let epic_id = db.create_epic(Epic::new("epic".to_owned(), "".to_owned())).unwrap();
+            let story_id = db.create_story(Story::new("".to_owned(), "".to_owned()), epic_id).unwrap();
+            db.delete_epic(epic_id).unwrap();
+            let before = db.read_db().unwrap();
+
+            assert!(db.update_epic_status(epic_id, Status::Closed).is_err());
+            assert!(db.set_epic_owner(epic_id, Some("alice".to_owned())).is_err());
+            assert!(db.assign_story(story_id, Some("alice".to_owned())).is_err());
+            assert!(db.add_label(story_id, "backend").is_err());
+            assert_eq!(db.read_db().unwrap(), before);
+        }
+    }
 
-        let epic_id = result.unwrap();
-        let result = db.create_story(story, epic_id);
-        assert_eq!(result.is_ok(), true);
+    #[test]
+    fn restore_item_should_bring_back_an_epic_with_its_stories() {
+        for db in test_databases() {
+            let epic_id = db.create_epic(Epic::new("epic".to_owned(), "".to_owned())).unwrap();
+            let story_id = db.create_story(Story::new("".to_owned(), "".to_owned()), epic_id).unwrap();
+            db.delete_epic(epic_id).unwrap();
+
+            assert!(db.restore_item(story_id).is_err()); //the story went with its epic
+            db.restore_item(epic_id).unwrap();
+            let db_state = db.read_db().unwrap();
+            assert!(!db_state.epics[&epic_id].archived);
+            assert!(!db_state.stories[&story_id].archived);
+            assert_eq!(db_state.history.last().unwrap().field, "restored");
+
+            db.delete_story(epic_id, story_id).unwrap();
+            db.restore_item(story_id).unwrap();
+            assert!(!db.read_db().unwrap().stories[&story_id].archived);
+            assert!(db.restore_item(story_id).is_err()); //not in the trash
+            assert!(db.restore_item(999).is_err());
+        }
+    }
 
-        let story_id = result.unwrap();
-        let non_existent_epic_id = 999;
-        let result = db.delete_story(non_existent_epic_id, story_id);
-        assert_eq!(result.is_err(), true);
+    #[test]
+    fn purge_item_should_only_remove_items_in_the_trash() {
+        for db in test_databases() {
+            let epic_id = db.create_epic(Epic::new("epic".to_owned(), "".to_owned())).unwrap();
+            let story_id = db.create_story(Story::new("".to_owned(), "".to_owned()), epic_id).unwrap();
+            let other_story_id = db.create_story(Story::new("".to_owned(), "".to_owned()), epic_id).unwrap();
+            assert!(db.purge_item(story_id).is_err());
+
+            db.delete_story(epic_id, story_id).unwrap();
+            db.purge_item(story_id).unwrap();
+            let db_state = db.read_db().unwrap();
+            assert_eq!(db_state.stories.get(&story_id), None);
+            assert_eq!(db_state.epics[&epic_id].stories, vec![other_story_id]);
+
+            db.delete_epic(epic_id).unwrap();
+            db.purge_item(epic_id).unwrap();
+            let db_state = db.read_db().unwrap();
+            assert!(db_state.epics.is_empty() && db_state.stories.is_empty());
+            assert_eq!(db_state.history.iter().filter(|entry| entry.field == "purged").count(), 3);
+        }
     }
 
     #[test]
-    fn delete_story_should_error_if_story_not_found_in_epic() {
-        let db = JiraDatabase{
-            database: Box::new(MockDB::new())
-        };
-        let epic = Epic::new("".to_owned(), "".to_owned());
-        let story = Story::new("".to_owned(), "".to_owned());
-        let result = db.create_epic(epic);
-        assert_eq!(result.is_ok(), true);
+    fn update_epic_status_should_error_if_invalid_epic_id() {
+        for db in test_databases() {
+            let non_existent_epic_id = 999;
+            let result = db.update_epic_status(non_existent_epic_id, Status::Closed);
+            assert_eq!(result.is_err(), true);
+        }
+    }
 
-        let epic_id = result.unwrap();
-        let result = db.create_story(story, epic_id);
-        assert_eq!(result.is_ok(), true);
+    #[test]
+    fn update_epic_status_should_work() {
+        for db in test_databases() {
+            let epic = Epic::new("".to_owned(), "".to_owned());
+            let result = db.create_epic(epic);
+            assert_eq!(result.is_ok(), true);
+
+            let epic_id = result.unwrap();
+            let result = db.update_epic_status(epic_id, Status::Closed);
+            assert_eq!(result.is_ok(), true);
 
-        let non_existent_story_id = 999;
-        let result = db.delete_story(epic_id, non_existent_story_id);
-        assert_eq!(result.is_err(), true);
+            let db_state = db.read_db().unwrap();
+            assert_eq!(db_state.epics.get(&epic_id).unwrap().status, Status::Closed);
+        }
     }
 
     #[test]
-    fn delete_story_should_work() {
-        let db = JiraDatabase{
-            database: Box::new(MockDB::new())
-        };
-        let epic = Epic::new("".to_owned(), "".to_owned());
-        let story = Story::new("".to_owned(), "".to_owned());
-        let result = db.create_epic(epic);
-        assert_eq!(result.is_ok(), true);
+    fn update_story_status_should_error_if_invalid_story_id() {
+        for db in test_databases() {
+            let epic = Epic::new("".to_owned(), "".to_owned());
+            let story = Story::new("".to_owned(), "".to_owned());
+            let result = db.create_epic(epic);
+            let epic_id = result.unwrap();
+            let result = db.create_story(story, epic_id);
+            let story_id = result.unwrap();
+            let result = db.update_story_status(story_id, Status::Closed);
+            assert_eq!(result.is_ok(), true);
+
+            let db_state = db.read_db().unwrap();
+            assert_eq!(db_state.stories.get(&story_id).unwrap().status, Status::Closed);
+        }
+    }
 
-        let epic_id = result.unwrap();
-        let result = db.create_story(story, epic_id);
-        assert_eq!(result.is_ok(), true);
+    #[test]
+    fn update_story_status_should_follow_the_workflow() {
+        for mut db in test_databases() {
+            db.workflow = Workflow::parse(r#"{"statuses": ["OPEN", "IN PROGRESS", "CLOSED"], "transitions": {"OPEN": ["IN PROGRESS"], "IN PROGRESS": ["CLOSED"]}}"#).unwrap();
+            let epic_id = db.create_epic(Epic::new("".to_owned(), "".to_owned())).unwrap();
+            let story_id = db.create_story(Story::new("".to_owned(), "".to_owned()), epic_id).unwrap();
+
+            assert!(db.update_story_status(story_id, Status::Closed).is_err());
+            db.update_story_status(story_id, Status::InProgress).unwrap();
+            db.update_story_status(story_id, Status::Closed).unwrap();
+            assert!(db.update_story_status(story_id, Status::Open).is_err());
+            db.update_story_status(story_id, Status::Closed).unwrap(); //not a change
+            assert_eq!(db.read_db().unwrap().stories[&story_id].status, Status::Closed);
+            db.update_epic_status(epic_id, Status::Resolved).unwrap(); //epics are free to move
+        }
+    }
 
-        let story_id = result.unwrap();
-        let result = db.delete_story(epic_id, story_id);
-        assert_eq!(result.is_ok(), true);
+    #[test]
+    fn add_comment_should_work() {
+        for mut db in test_databases() {
+            let epic_id = db.create_epic(Epic::new("".to_owned(), "".to_owned())).unwrap();
+            let story_id = db.create_story(Story::new("".to_owned(), "".to_owned()), epic_id).unwrap();
+            assert!(db.add_comment(story_id, "hi").is_err()); //no author
+
+            db.user = Some("alice".to_owned());
+            db.add_comment(story_id, "  first  ").unwrap();
+            db.add_comment(epic_id, "on the epic").unwrap();
+            db.add_comment(story_id, "second").unwrap();
+            assert!(db.add_comment(story_id, " ").is_err());
+            assert!(db.add_comment(999, "nowhere").is_err());
+
+            let db_state = db.read_db().unwrap();
+            let bodies: Vec<&str> = db_state.comments_on(story_id).iter().map(|comment| comment.body.as_str()).collect();
+            assert_eq!(bodies, vec!["first", "second"]);
+            assert_eq!(db_state.comments_on(epic_id)[0].author, "alice");
+            assert_eq!(db_state.stories[&story_id].updated_at, Some(db_state.comments_on(story_id)[1].at)); //commenting counts as activity
+
+            db.delete_epic(epic_id).unwrap();
+            assert!(db.add_comment(epic_id, "in the trash").is_err());
+            assert!(db.add_comment(story_id, "in the trash").is_err());
+            assert_eq!(db.read_db().unwrap().comments.len(), 3);
+        }
+    }
 
-        let db_state = db.read_db().unwrap();
-        let expected_last_id = 2;
+    #[test]
+    fn update_epic_should_error_if_invalid_epic_id() {
+        for db in test_databases() {
+            let non_existent_epic_id = 999;
+            let result = db.update_epic(non_existent_epic_id, "".to_owned(), "".to_owned());
+            assert!(result.is_err());
+        }
+    }
 
-        assert_eq!(db_state.last_item_id, expected_last_id);
-        assert_eq!(db_state.epics.get(&epic_id).unwrap().stories.contains(&story_id), false);
-        assert_eq!(db_state.stories.get(&story_id), None);
+    #[test]
+    fn update_epic_should_work() {
+        for db in test_databases() {
+            let epic_id = db.create_epic(Epic::new("old".to_owned(), "old".to_owned())).unwrap();
+            let story_id = db.create_story(Story::new("".to_owned(), "".to_owned()), epic_id).unwrap();
+            db.update_epic_status(epic_id, Status::Resolved).unwrap();
+
+            let result = db.update_epic(epic_id, "new name".to_owned(), "new description".to_owned());
+            assert!(result.is_ok());
+
+            let db_state = db.read_db().unwrap();
+            let epic = db_state.epics.get(&epic_id).unwrap();
+            assert_eq!(epic.name, "new name".to_owned());
+            assert_eq!(epic.description, "new description".to_owned());
+            assert_eq!(epic.status, Status::Resolved);
+            assert_eq!(epic.stories, vec![story_id]); //editing keeps the epic's stories
+        }
     }
 
     #[test]
-    fn update_epic_status_should_error_if_invalid_epic_id() {
-        let db = JiraDatabase{
-            database: Box::new(MockDB::new()),
-        };
-        let non_existent_epic_id = 999;
-        let result = db.update_epic_status(non_existent_epic_id, Status::Closed);
-        assert_eq!(result.is_err(), true);
+    fn update_story_should_error_if_invalid_story_id() {
+        for db in test_databases() {
+            let non_existent_story_id = 999;
+            let result = db.update_story(non_existent_story_id, "".to_owned(), "".to_owned());
+            assert!(result.is_err());
+        }
     }
 
     #[test]
-    fn update_epic_status_should_work() {
-        let db = JiraDatabase {
-            database: Box::new(MockDB::new()),
-        };
-        let epic = Epic::new("".to_owned(), "".to_owned());
-        let result = db.create_epic(epic);
-        assert_eq!(result.is_ok(), true);
+    fn update_story_should_work() {
+        for db in test_databases() {
+            let epic_id = db.create_epic(Epic::new("".to_owned(), "".to_owned())).unwrap();
+            let story_id = db.create_story(Story::new("old".to_owned(), "old".to_owned()), epic_id).unwrap();
+
+            let result = db.update_story(story_id, "new name".to_owned(), "new description".to_owned());
+            assert!(result.is_ok());
+
+            let db_state = db.read_db().unwrap();
+            let story = db_state.stories.get(&story_id).unwrap();
+            assert_eq!(story.name, "new name".to_owned());
+            assert_eq!(story.description, "new description".to_owned());
+            assert_eq!(story.status, Status::Open);
+        }
+    }
 
-        let epic_id = result.unwrap();
-        let result = db.update_epic_status(epic_id, Status::Closed);
-        assert_eq!(result.is_ok(), true);
+    #[test]
+    fn assign_story_and_set_epic_owner_should_work() {
+        for db in test_databases() {
+            let epic_id = db.create_epic(Epic::new("".to_owned(), "".to_owned())).unwrap();
+            let story_id = db.create_story(Story::new("".to_owned(), "".to_owned()), epic_id).unwrap();
+
+            db.assign_story(story_id, Some("alice".to_owned())).unwrap();
+            db.set_epic_owner(epic_id, Some("bob".to_owned())).unwrap();
+            assert_eq!(db.read_db().unwrap().stories[&story_id].assignee, Some("alice".to_owned()));
+            assert_eq!(db.read_db().unwrap().epics[&epic_id].owner, Some("bob".to_owned()));
+            assert_eq!(db.read_db().unwrap().epics[&epic_id].stories, vec![story_id]);
+
+            db.assign_story(story_id, None).unwrap();
+            assert_eq!(db.read_db().unwrap().stories[&story_id].assignee, None);
+            assert!(db.assign_story(999, None).is_err());
+            assert!(db.set_epic_owner(999, None).is_err());
+        }
+    }
 
-        let db_state = db.read_db().unwrap();
-        assert_eq!(db_state.epics.get(&epic_id).unwrap().status, Status::Closed);
+    #[test]
+    fn estimate_story_should_work() {
+        for db in test_databases() {
+            let epic_id = db.create_epic(Epic::new("".to_owned(), "".to_owned())).unwrap();
+            let story_id = db.create_story(Story::new("".to_owned(), "".to_owned()), epic_id).unwrap();
+
+            db.estimate_story(story_id, Priority::High, Some(8)).unwrap();
+            let story = db.read_db().unwrap().stories[&story_id].clone();
+            assert_eq!((story.priority, story.points), (Priority::High, Some(8)));
+            assert!(db.estimate_story(999, Priority::Low, None).is_err());
+        }
     }
 
     #[test]
-    fn update_story_status_should_error_if_invalid_story_id() {
-        let db = JiraDatabase {
-            database: Box::new(MockDB::new()),
-        };
-        let epic = Epic::new("".to_owned(), "".to_owned());
-        let story = Story::new("".to_owned(), "".to_owned());
-        let result = db.create_epic(epic);
-        let epic_id = result.unwrap();
-        let result = db.create_story(story, epic_id);
-        let story_id = result.unwrap();
-        let result = db.update_story_status(story_id, Status::Closed);
-        assert_eq!(result.is_ok(), true);
+    fn mutations_should_maintain_timestamps_and_history() {
+        for mut db in test_databases() {
+            db.user = Some("alice".to_owned());
+            let epic_id = db.create_epic(Epic::new("epic".to_owned(), "".to_owned())).unwrap();
+            let other_epic_id = db.create_epic(Epic::new("other".to_owned(), "".to_owned())).unwrap();
+            let story_id = db.create_story(Story::new("story".to_owned(), "".to_owned()), epic_id).unwrap();
+            let created = db.read_db().unwrap().stories[&story_id].clone();
+            assert!(created.created_at.is_some());
+            assert_eq!(created.created_at, created.updated_at);
+
+            db.update_story_status(story_id, Status::InProgress).unwrap();
+            db.update_story_status(story_id, Status::InProgress).unwrap(); //not a change, nothing logged
+            db.update_story(story_id, "renamed".to_owned(), "".to_owned()).unwrap();
+            db.move_story(story_id, epic_id, other_epic_id).unwrap();
+            db.delete_story(other_epic_id, story_id).unwrap();
+
+            let db_state = db.read_db().unwrap();
+            let story_history: Vec<(&str, &str, &str)> = db_state.history.iter()
+                .filter(|entry| entry.item_id == story_id)
+                .map(|entry| (entry.field.as_str(), entry.old.as_str(), entry.new.as_str()))
+                .collect();
+            assert_eq!(story_history, vec![
+                ("created", "", "story"),
+                ("status", "OPEN", "IN PROGRESS"),
+                ("name", "story", "renamed"),
+                ("epic", "1", "2"),
+                ("deleted", "renamed", ""),
+            ]);
+            assert!(db_state.history.iter().all(|entry| entry.user.as_deref() == Some("alice")));
+            assert_eq!(db_state.history.iter().filter(|entry| entry.item_id == epic_id).count(), 1);
+
+            db.update_epic_status(epic_id, Status::Closed).unwrap();
+            let epic = db.read_db().unwrap().epics[&epic_id].clone();
+            assert!(epic.updated_at > epic.created_at);
+        }
+    }
+
+    #[test]
+    fn restore_items_should_undo_and_redo_a_delete() {
+        for db in test_databases() {
+            let epic_id = db.create_epic(Epic::new("epic".to_owned(), "".to_owned())).unwrap();
+            let story_id = db.create_story(Story::new("story".to_owned(), "".to_owned()), epic_id).unwrap();
+            let before = db.read_db().unwrap();
+            db.delete_epic(epic_id).unwrap();
+            let change_set = db.read_db().unwrap().changes_since(&before);
+
+            db.restore_items(&change_set.before).unwrap();
+            let restored = db.read_db().unwrap();
+            assert_eq!(restored.epics, before.epics);
+            assert_eq!(restored.stories, before.stories);
+            assert_eq!(restored.history.last().unwrap().field, "archived");
+
+            db.restore_items(&change_set.after).unwrap();
+            let db_state = db.read_db().unwrap();
+            assert!(db_state.epics[&epic_id].archived && db_state.stories[&story_id].archived);
+
+            let before = db.read_db().unwrap();
+            db.purge_item(epic_id).unwrap();
+            let change_set = db.read_db().unwrap().changes_since(&before);
+            db.restore_items(&change_set.before).unwrap(); //even a purge can be taken back within the session
+            assert_eq!(db.read_db().unwrap().epics, before.epics);
+            assert_eq!(db.read_db().unwrap().history.last().unwrap().field, "restored");
+            db.restore_items(&change_set.after).unwrap();
+            assert!(db.read_db().unwrap().epics.is_empty());
+            assert_eq!(db.read_db().unwrap().last_item_id, story_id); //ids are never handed out twice
+        }
+    }
+
+    #[test]
+    fn add_and_remove_label_should_keep_the_index_up_to_date() {
+        for db in test_databases() {
+            let epic_id = db.create_epic(Epic::new("".to_owned(), "".to_owned())).unwrap();
+            let story_id = db.create_story(Story::new("".to_owned(), "".to_owned()), epic_id).unwrap();
+
+            db.add_label(epic_id, "Backend").unwrap();
+            db.add_label(story_id, " backend ").unwrap();
+            db.add_label(story_id, "ui").unwrap();
+            assert!(db.add_label(story_id, "two words").is_err());
+            assert!(db.add_label(999, "ui").is_err());
+
+            let db_state = db.read_db().unwrap();
+            assert_eq!(db_state.stories[&story_id].labels, BTreeSet::from(["backend".to_owned(), "ui".to_owned()]));
+            assert_eq!(db_state.labelled("backend"), BTreeSet::from([epic_id, story_id]));
+            assert_eq!(db_state.history.last().unwrap().field, "labels");
+
+            db.remove_label(story_id, "BACKEND").unwrap();
+            db.remove_label(story_id, "ui").unwrap();
+            let db_state = db.read_db().unwrap();
+            assert_eq!(db_state.labelled("backend"), BTreeSet::from([epic_id]));
+            assert!(!db_state.label_index.contains_key("ui"));
+
+            db.delete_epic(epic_id).unwrap();
+            db.purge_item(epic_id).unwrap();
+            assert!(db.read_db().unwrap().label_index.is_empty());
+            assert_eq!(db.validate().unwrap(), vec![]);
+        }
+    }
+
+    #[test]
+    fn save_and_delete_filter_should_work() {
+        for db in test_databases() {
+            db.save_filter(" open ", " status = open ").unwrap();
+            db.save_filter("open", "status = open AND label = backend").unwrap(); //same name replaces it
+            assert!(db.save_filter("broken", "status = done").is_err());
+            assert!(db.save_filter(" ", "status = open").is_err());
+            assert_eq!(db.read_db().unwrap().saved_filters, BTreeMap::from([("open".to_owned(), "status = open AND label = backend".to_owned())]));
+
+            db.delete_filter("open").unwrap();
+            assert!(db.read_db().unwrap().saved_filters.is_empty());
+            assert!(db.delete_filter("open").is_err());
+        }
+    }
+
+    #[test]
+    fn move_story_should_error_if_invalid_epic_id() {
+        for db in test_databases() {
+            let epic_id = db.create_epic(Epic::new("".to_owned(), "".to_owned())).unwrap();
+            let story_id = db.create_story(Story::new("".to_owned(), "".to_owned()), epic_id).unwrap();
+            let non_existent_epic_id = 999;
+
+            assert!(db.move_story(story_id, epic_id, non_existent_epic_id).is_err());
+            assert!(db.move_story(story_id, non_existent_epic_id, epic_id).is_err());
+            assert!(db.move_story(story_id, epic_id, epic_id).is_err());
+            assert_eq!(db.read_db().unwrap().epics.get(&epic_id).unwrap().stories, vec![story_id]);
+        }
+    }
+
+    #[test]
+    fn move_story_should_error_if_story_not_found_in_epic() {
+        for db in test_databases() {
+            let from_epic_id = db.create_epic(Epic::new("".to_owned(), "".to_owned())).unwrap();
+            let to_epic_id = db.create_epic(Epic::new("".to_owned(), "".to_owned())).unwrap();
+            let story_id = db.create_story(Story::new("".to_owned(), "".to_owned()), to_epic_id).unwrap();
+
+            assert!(db.move_story(story_id, from_epic_id, to_epic_id).is_err());
+        }
+    }
+
+    #[test]
+    fn move_story_should_work() {
+        for db in test_databases() {
+            let from_epic_id = db.create_epic(Epic::new("".to_owned(), "".to_owned())).unwrap();
+            let to_epic_id = db.create_epic(Epic::new("".to_owned(), "".to_owned())).unwrap();
+            let story_id = db.create_story(Story::new("".to_owned(), "".to_owned()), from_epic_id).unwrap();
+            let other_story_id = db.create_story(Story::new("".to_owned(), "".to_owned()), to_epic_id).unwrap();
+
+            let result = db.move_story(story_id, from_epic_id, to_epic_id);
+            assert!(result.is_ok());
+
+            let db_state = db.read_db().unwrap();
+            assert!(db_state.epics.get(&from_epic_id).unwrap().stories.is_empty());
+            assert_eq!(db_state.epics.get(&to_epic_id).unwrap().stories, vec![other_story_id, story_id]);
+            assert!(db_state.stories.contains_key(&story_id));
+        }
+    }
+
+    #[test]
+    fn repair_should_fix_integrity_issues() {
+        for db in test_databases() {
+            let epic_id = db.create_epic(Epic::new("".to_owned(), "".to_owned())).unwrap();
+            let story_id = db.create_story(Story::new("".to_owned(), "".to_owned()), epic_id).unwrap();
+            let mut db_state = db.read_db().unwrap();
+            db_state.epics.get_mut(&epic_id).unwrap().stories.push(999);
+            db_state.stories.remove(&story_id);
+            db.database.write_db(&db_state).unwrap();
+
+            assert_eq!(db.validate().unwrap().len(), 2); //story 999 is missing and last_item_id is too low
+
+            let fixed = db.repair().unwrap();
+            assert_eq!(fixed.len(), 2);
+            assert_eq!(db.validate().unwrap(), vec![]);
+            assert!(db.read_db().unwrap().epics.get(&epic_id).unwrap().stories.is_empty());
+        }
+    }
+
+    #[test]
+    fn database_operations_should_match_read_db() {
+        for db in test_databases() {
+            let database = &db.database;
+            let epic = Epic::new("epic".to_owned(), "".to_owned());
+            let story = Story::new("story".to_owned(), "".to_owned());
+            let epic_id = database.add_epic(&epic).unwrap();
+            let story_id = database.add_story(epic_id, &story).unwrap();
+            assert_eq!((epic_id, story_id), (1, 2));
+
+            let db_state = db.read_db().unwrap();
+            assert_eq!(db_state.last_item_id, 2);
+            assert_eq!(database.get_epic(epic_id).unwrap().as_ref(), db_state.epics.get(&epic_id));
+            assert_eq!(database.get_epic(epic_id).unwrap().unwrap().stories, vec![story_id]);
+            assert_eq!(database.get_story(story_id).unwrap(), Some(story.clone()));
+            assert_eq!(database.get_story(999).unwrap(), None);
+
+            assert_eq!(database.remove_story(story_id).unwrap(), Some(story));
+            assert!(database.get_epic(epic_id).unwrap().unwrap().stories.is_empty());
+
+            let story_id = database.add_story(epic_id, &Story::new("".to_owned(), "".to_owned())).unwrap();
+            database.put_story(story_id, &Story::new("changed".to_owned(), "".to_owned())).unwrap(); //replacing a story keeps its link
+            assert_eq!(database.get_epic(epic_id).unwrap().unwrap().stories, vec![story_id]);
+            assert!(database.remove_epic(epic_id).unwrap().is_some());
+            assert_eq!(database.get_story(story_id).unwrap(), None); //removing an epic takes its stories along
+            assert!(database.remove_epic(epic_id).unwrap().is_none());
+        }
+    }
+
+    #[test]
+    fn add_story_should_error_if_invalid_epic_id() {
+        for db in test_databases() {
+            assert!(db.database.add_story(999, &Story::new("".to_owned(), "".to_owned())).is_err());
+            assert_eq!(db.read_db().unwrap().last_item_id, 0); //a failed add doesn't burn an id
+        }
+    }
+
+    // counts the writes that reach the wrapped backend
+    struct CountingDatabase {
+        inner: JiraDatabase,
+        writes: std::rc::Rc<std::cell::Cell<usize>>,
+    }
+
+    impl Database for CountingDatabase {
+        fn read_db(&self) -> Result<DBState> {
+            self.inner.read_db()
+        }
 
-        let db_state = db.read_db().unwrap();
-        assert_eq!(db_state.stories.get(&story_id).unwrap().status, Status::Closed);
+        fn write_db(&self, db_state: &DBState) -> Result<()> {
+            self.writes.set(self.writes.get() + 1);
+            self.inner.database.write_db(db_state)
+        }
+    }
+
+    #[test]
+    fn each_operation_should_write_once() {
+        for inner in test_databases() {
+            let writes = std::rc::Rc::new(std::cell::Cell::new(0));
+            let db = JiraDatabase { database: Box::new(CountingDatabase { inner, writes: writes.clone() }), user: None, workflow: Workflow::default() };
+
+            let epic_id = db.create_epic(Epic::new("".to_owned(), "".to_owned())).unwrap();
+            assert_eq!(writes.replace(0), 1);
+            let story_id = db.create_story(Story::new("".to_owned(), "".to_owned()), epic_id).unwrap();
+            assert_eq!(writes.replace(0), 1);
+            db.update_story_status(story_id, Status::Closed).unwrap();
+            assert_eq!(writes.replace(0), 1);
+            db.delete_story(epic_id, story_id).unwrap();
+            assert_eq!(writes.replace(0), 1);
+            db.delete_epic(epic_id).unwrap();
+            assert_eq!(writes.replace(0), 1);
+            db.restore_item(epic_id).unwrap();
+            assert_eq!(writes.replace(0), 1);
+            db.delete_epic(epic_id).unwrap();
+            writes.set(0);
+            db.purge_item(story_id).unwrap();
+            assert_eq!(writes.replace(0), 1);
+            db.purge_item(epic_id).unwrap();
+            assert_eq!(writes.replace(0), 1);
+        }
+    }
+
+    #[test]
+    fn transaction_should_commit_all_changes_with_one_write() {
+        for inner in test_databases() {
+            let writes = std::rc::Rc::new(std::cell::Cell::new(0));
+            let db = JiraDatabase { database: Box::new(CountingDatabase { inner, writes: writes.clone() }), user: None, workflow: Workflow::default() };
+
+            let (epic_id, story_ids) = db.transaction(|tx| {
+                let epic_id = tx.create_epic(Epic::new("epic".to_owned(), "".to_owned()))?;
+                let mut story_ids = vec![];
+                for _ in 0..10 {
+                    let story_id = tx.create_story(Story::new("".to_owned(), "".to_owned()), epic_id)?;
+                    tx.update_story_status(story_id, Status::InProgress)?;
+                    story_ids.push(story_id);
+                }
+                Ok((epic_id, story_ids))
+            }).unwrap();
+
+            assert_eq!(writes.get(), 1);
+            let db_state = db.read_db().unwrap();
+            assert_eq!(db_state.last_item_id, 11);
+            assert_eq!(db_state.epics[&epic_id].stories, story_ids);
+            assert!(story_ids.iter().all(|id| db_state.stories[id].status == Status::InProgress));
+        }
+    }
+
+    #[test]
+    fn transaction_should_roll_back_on_error() {
+        for db in test_databases() {
+            let epic_id = db.create_epic(Epic::new("".to_owned(), "".to_owned())).unwrap();
+            let before = db.read_db().unwrap();
+
+            let result = db.transaction(|tx| {
+                tx.create_story(Story::new("".to_owned(), "".to_owned()), epic_id)?;
+                tx.delete_epic(epic_id)?;
+                tx.create_story(Story::new("".to_owned(), "".to_owned()), epic_id) //epic is in the trash by now, so this fails
+            });
+
+            assert!(result.is_err());
+            assert_eq!(db.read_db().unwrap(), before);
+        }
     }
 
     mod database {
-        use std::collections::HashMap;
+        use std::collections::{BTreeMap, BTreeSet, HashMap};
         use std::fs::{remove_file};
         use std::io::Write;
 
@@ -420,13 +1588,26 @@ mod tests {
             let story = Story {
                 name: "epic 1".to_owned(),
                 description: "epic 1".to_owned(),
-                status: Status::Open
+                status: Status::Open,
+                assignee: Some("alice".to_owned()),
+                reporter: None,
+                priority: Priority::High,
+                points: Some(3),
+                created_at: Some(chrono::Utc::now()),
+                updated_at: None,
+                archived: true,
+                labels: BTreeSet::from(["backend".to_owned()])
             };
             let epic = Epic {
                 name: "epic 1".to_owned(),
                 description: "epic 1".to_owned(),
                 status: Status::Open,
-                stories: vec![2]
+                stories: vec![2],
+                owner: None,
+                created_at: None,
+                updated_at: None,
+                archived: false,
+                labels: BTreeSet::new()
             };
 
             let mut stories = HashMap::new();
@@ -438,17 +1619,239 @@ mod tests {
             let state = DBState{
                 last_item_id: 2,
                 epics,
-                stories
+                stories,
+                label_index: BTreeMap::from([("backend".to_owned(), BTreeSet::from([2]))]),
+                saved_filters: BTreeMap::from([("open".to_owned(), "status = OPEN".to_owned())]),
+                ..Default::default()
             };
 
             let write_result = db.write_db(&state);
             let read_result = db.read_db().unwrap();
 
-            remove_file(file_path).unwrap();
+            remove_file(&file_path).unwrap();
+            remove_file(format!("{}.bak", file_path)).unwrap();
 
             assert_eq!(write_result.is_ok(), true);
             //TODO: Fix this error by deriving the appropriate traits for DBState
             assert_eq!(read_result, state);
         }
+
+        fn json_db_in(dir: &tempfile::TempDir) -> JSONFileDatabase {
+            JSONFileDatabase {
+                file_path: dir.path().join("db.json").to_str().unwrap().to_owned()
+            }
+        }
+
+        fn state_with_last_id(last_item_id: u32) -> DBState {
+            DBState { last_item_id, ..Default::default() }
+        }
+
+        #[test]
+        fn write_db_should_keep_previous_generation_as_backup() {
+            let dir = tempfile::tempdir().unwrap();
+            let db = json_db_in(&dir);
+
+            db.write_db(&state_with_last_id(1)).unwrap();
+            assert!(!db.has_backup()); //nothing to back up on the very first write
+
+            db.write_db(&state_with_last_id(2)).unwrap();
+            assert!(db.has_backup());
+
+            let backup: DBState = serde_json::from_str(&std::fs::read_to_string(db.backup_path()).unwrap()).unwrap();
+            assert_eq!(backup, state_with_last_id(1));
+            assert_eq!(db.read_db().unwrap(), state_with_last_id(2));
+            assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 2); //no temp file left behind next to db.json and its backup
+        }
+
+        #[test]
+        fn write_db_should_survive_concurrent_writers() {
+            let dir = tempfile::tempdir().unwrap();
+            let file_path = json_db_in(&dir).file_path;
+            let writers: Vec<_> = (1..=4).map(|writer| {
+                let db = JSONFileDatabase { file_path: file_path.clone() };
+                std::thread::spawn(move || {
+                    for _ in 0..20 {
+                        db.write_db(&state_with_last_id(writer)).unwrap();
+                    }
+                })
+            }).collect();
+            for writer in writers {
+                writer.join().unwrap();
+            }
+
+            let db = JSONFileDatabase { file_path };
+            assert!((1..=4).contains(&db.read_db().unwrap().last_item_id)); //one writer wins, nobody gets a torn file
+        }
+
+        #[test]
+        fn read_db_should_name_corrupt_file_and_backup() {
+            let dir = tempfile::tempdir().unwrap();
+            let db = json_db_in(&dir);
+            db.write_db(&state_with_last_id(1)).unwrap();
+            db.write_db(&state_with_last_id(2)).unwrap();
+
+            std::fs::write(&db.file_path, r#"{ "last_item_id": 2, "epi"#).unwrap(); //simulate a write cut short by a crash
+
+            let error = format!("{:#}", db.read_db().unwrap_err());
+            assert!(error.contains(&db.file_path));
+            assert!(error.contains(&db.backup_path()));
+        }
+
+        #[test]
+        fn recover_from_backup_should_restore_previous_generation() {
+            let dir = tempfile::tempdir().unwrap();
+            let db = json_db_in(&dir);
+            db.write_db(&state_with_last_id(1)).unwrap();
+            db.write_db(&state_with_last_id(2)).unwrap();
+            std::fs::write(&db.file_path, "").unwrap();
+
+            db.recover_from_backup().unwrap();
+            assert_eq!(db.read_db().unwrap(), state_with_last_id(1));
+
+            db.write_db(&state_with_last_id(3)).unwrap(); //the recovered state becomes the new backup
+            assert_eq!(db.read_db().unwrap(), state_with_last_id(3));
+        }
+
+        #[test]
+        fn write_db_should_not_back_up_corrupt_file() {
+            let dir = tempfile::tempdir().unwrap();
+            let db = json_db_in(&dir);
+            db.write_db(&state_with_last_id(1)).unwrap();
+            db.write_db(&state_with_last_id(2)).unwrap();
+            std::fs::write(&db.file_path, "{").unwrap();
+
+            db.write_db(&state_with_last_id(3)).unwrap();
+            db.recover_from_backup().unwrap();
+            assert_eq!(db.read_db().unwrap(), state_with_last_id(1));
+        }
+
+        #[test]
+        fn new_should_seed_missing_file_and_parent_directory() {
+            let dir = tempfile::tempdir().unwrap();
+            let file_path = dir.path().join("nested").join("db.json").to_str().unwrap().to_owned();
+
+            let db = JiraDatabase::new(file_path.clone()).unwrap();
+
+            assert!(std::path::Path::new(&file_path).exists());
+            assert_eq!(db.read_db().unwrap(), state_with_last_id(0));
+        }
+
+        #[test]
+        fn new_should_seed_empty_file() {
+            let dir = tempfile::tempdir().unwrap();
+            let file_path = dir.path().join("db.json").to_str().unwrap().to_owned();
+            std::fs::write(&file_path, "").unwrap();
+
+            let db = JiraDatabase::new(file_path).unwrap();
+            assert_eq!(db.read_db().unwrap(), state_with_last_id(0));
+        }
+
+        #[test]
+        fn new_should_not_overwrite_existing_or_corrupt_file() {
+            let dir = tempfile::tempdir().unwrap();
+            let file_path = dir.path().join("db.json").to_str().unwrap().to_owned();
+
+            std::fs::write(&file_path, r#"{ "last_item_id": 7, "epics": {}, "stories": {} }"#).unwrap();
+            let db = JiraDatabase::new(file_path.clone()).unwrap();
+            assert_eq!(db.read_db().unwrap(), state_with_last_id(7));
+
+            std::fs::write(&file_path, r#"{ "last_item_id": 7, "#).unwrap();
+            let db = JiraDatabase::new(file_path.clone()).unwrap();
+            let error = format!("{:#}", db.read_db().unwrap_err());
+            assert!(error.contains(&file_path));
+        }
+
+        #[test]
+        fn read_db_should_migrate_unversioned_document() {
+            let dir = tempfile::tempdir().unwrap();
+            let db = json_db_in(&dir);
+            std::fs::write(&db.file_path, r#"{ "last_item_id": 4, "epics": {}, "stories": {} }"#).unwrap();
+
+            assert_eq!(db.read_db().unwrap(), state_with_last_id(4));
+
+            db.write_db(&state_with_last_id(5)).unwrap();
+            let document: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&db.file_path).unwrap()).unwrap();
+            assert_eq!(document["schema_version"], SCHEMA_VERSION);
+        }
+
+        #[test]
+        fn read_db_should_migrate_older_documents() {
+            // (schema_version, document written at that version, what the migrations should have filled in)
+            let cases = vec![
+                (1, serde_json::json!({ "schema_version": 1, "last_item_id": 2,
+                    "epics": { "1": { "name": "e", "description": "", "status": "Open", "stories": [2] } },
+                    "stories": { "2": { "name": "s", "description": "", "status": "Closed" } } }),
+                    vec![("/epics/1/owner", Value::Null), ("/stories/2/assignee", Value::Null), ("/stories/2/reporter", Value::Null)]),
+                (2, serde_json::json!({ "schema_version": 2, "last_item_id": 2,
+                    "epics": { "1": { "name": "e", "description": "", "status": "Open", "stories": [2], "owner": null } },
+                    "stories": { "2": { "name": "s", "description": "", "status": "Closed", "assignee": "bob", "reporter": null } } }),
+                    vec![("/stories/2/priority", serde_json::json!("Medium")), ("/stories/2/points", Value::Null), ("/stories/2/assignee", serde_json::json!("bob"))]),
+                (3, serde_json::json!({ "schema_version": 3, "last_item_id": 2,
+                    "epics": { "1": { "name": "e", "description": "", "status": "Open", "stories": [2], "owner": null } },
+                    "stories": { "2": { "name": "s", "description": "", "status": "Closed", "assignee": null, "reporter": null, "priority": "High", "points": 3 } } }),
+                    vec![("/epics/1/created_at", Value::Null), ("/stories/2/updated_at", Value::Null), ("/history", serde_json::json!([])), ("/stories/2/points", serde_json::json!(3))]),
+                (4, serde_json::json!({ "schema_version": 4, "last_item_id": 2,
+                    "epics": { "1": { "name": "e", "description": "", "status": "Open", "stories": [2], "owner": null, "created_at": null, "updated_at": null } },
+                    "stories": { "2": { "name": "s", "description": "", "status": "Closed", "assignee": null, "reporter": null, "priority": "High", "points": null, "created_at": null, "updated_at": null } },
+                    "history": [] }),
+                    vec![("/epics/1/archived", Value::Bool(false)), ("/stories/2/archived", Value::Bool(false))]),
+                (5, serde_json::json!({ "schema_version": 5, "last_item_id": 1,
+                    "epics": { "1": { "name": "e", "description": "", "status": "Open", "stories": [], "owner": null, "created_at": null, "updated_at": null, "archived": true } },
+                    "stories": {}, "history": [] }),
+                    vec![("/comments", serde_json::json!([])), ("/epics/1/archived", Value::Bool(true))]),
+                (6, serde_json::json!({ "schema_version": 6, "last_item_id": 2,
+                    "epics": { "1": { "name": "e", "description": "", "status": "Open", "stories": [2], "owner": null, "created_at": null, "updated_at": null, "archived": false } },
+                    "stories": { "2": { "name": "s", "description": "", "status": "Closed", "assignee": null, "reporter": null, "priority": "High", "points": null, "created_at": null, "updated_at": null, "archived": false } },
+                    "history": [], "comments": [] }),
+                    vec![("/epics/1/labels", serde_json::json!([])), ("/stories/2/labels", serde_json::json!([])), ("/label_index", serde_json::json!({}))]),
+                (7, serde_json::json!({ "schema_version": 7, "last_item_id": 1,
+                    "epics": { "1": { "name": "e", "description": "", "status": "Open", "stories": [], "owner": null, "created_at": null, "updated_at": null, "archived": false, "labels": ["ui"] } },
+                    "stories": {}, "history": [], "comments": [], "label_index": { "ui": [1] } }),
+                    vec![("/saved_filters", serde_json::json!({})), ("/epics/1/labels", serde_json::json!(["ui"]))]),
+            ];
+
+            for (version, document, expected) in cases {
+                let mut migrated = document.clone();
+                for migration in &MIGRATIONS[version..] {
+                    migration(migrated.as_object_mut().unwrap()).unwrap();
+                }
+                for (pointer, value) in expected {
+                    assert_eq!(migrated.pointer(pointer), Some(&value), "v{} {}", version, pointer);
+                }
+
+                let dir = tempfile::tempdir().unwrap();
+                let db = json_db_in(&dir);
+                std::fs::write(&db.file_path, document.to_string()).unwrap();
+                assert!(db.read_db().is_ok(), "v{}", version);
+            }
+        }
+
+        #[test]
+        fn migrations_should_cover_every_schema_version() {
+            assert_eq!(MIGRATIONS.len(), SCHEMA_VERSION as usize);
+        }
+
+        #[test]
+        fn read_db_should_refuse_newer_schema_version() {
+            let dir = tempfile::tempdir().unwrap();
+            let db = json_db_in(&dir);
+            let document = format!(r#"{{ "schema_version": {}, "last_item_id": 4, "epics": {{}}, "stories": {{}} }}"#, SCHEMA_VERSION + 1);
+            std::fs::write(&db.file_path, &document).unwrap();
+
+            let error = format!("{:#}", db.read_db().unwrap_err());
+            assert!(error.contains("newer version"));
+            assert!(error.contains(&db.file_path));
+
+            let jira_db = JiraDatabase { database: Box::new(db), user: None, workflow: Workflow::default() };
+            assert!(jira_db.create_epic(Epic::new("".to_owned(), "".to_owned())).is_err()); //nothing gets written over the newer document
+        }
+
+        #[test]
+        fn recover_from_backup_should_fail_without_backup() {
+            let dir = tempfile::tempdir().unwrap();
+            let db = json_db_in(&dir);
+            assert!(!db.has_backup());
+            assert!(db.recover_from_backup().is_err());
+        }
     }
 }
\ No newline at end of file
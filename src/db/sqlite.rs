@@ -0,0 +1,398 @@
+use std::collections::BTreeSet;
+
+use anyhow::{Result, anyhow, Context};
+use rusqlite::{Connection, OptionalExtension, Row, params};
+use serde::{de::DeserializeOwned, Serialize};
+
+use super::Database;
+use crate::models::{Comment, DBState, Epic, HistoryEntry, Priority, Story, Status};
+
+// SQLITE_MIGRATIONS[n] upgrades a database from user_version n to n + 1, same idea as MIGRATIONS for db.json.
+// Links deliberately carry no foreign keys: DBState can hold dangling story ids and this backend must round-trip them.
+const SQLITE_MIGRATIONS: &[&str] = &[
+    "CREATE TABLE meta (key TEXT PRIMARY KEY, value INTEGER NOT NULL);
+     INSERT INTO meta (key, value) VALUES ('last_item_id', 0);
+     CREATE TABLE epics (id INTEGER PRIMARY KEY, name TEXT NOT NULL, description TEXT NOT NULL, status TEXT NOT NULL);
+     CREATE TABLE stories (id INTEGER PRIMARY KEY, name TEXT NOT NULL, description TEXT NOT NULL, status TEXT NOT NULL);
+     CREATE TABLE epic_stories (epic_id INTEGER NOT NULL, story_id INTEGER NOT NULL, position INTEGER NOT NULL, PRIMARY KEY (epic_id, story_id));
+     CREATE INDEX epic_stories_by_story ON epic_stories (story_id);",
+    "ALTER TABLE epics ADD COLUMN owner TEXT;
+     ALTER TABLE stories ADD COLUMN assignee TEXT;
+     ALTER TABLE stories ADD COLUMN reporter TEXT;",
+    "ALTER TABLE stories ADD COLUMN priority TEXT NOT NULL DEFAULT 'Medium';
+     ALTER TABLE stories ADD COLUMN points INTEGER;",
+    "ALTER TABLE epics ADD COLUMN created_at TEXT;
+     ALTER TABLE epics ADD COLUMN updated_at TEXT;
+     ALTER TABLE stories ADD COLUMN created_at TEXT;
+     ALTER TABLE stories ADD COLUMN updated_at TEXT;
+     CREATE TABLE history (seq INTEGER PRIMARY KEY AUTOINCREMENT, item_id INTEGER NOT NULL, at TEXT NOT NULL, user TEXT,
+         field TEXT NOT NULL, old TEXT NOT NULL, new TEXT NOT NULL);
+     CREATE INDEX history_by_item ON history (item_id);",
+    "ALTER TABLE epics ADD COLUMN archived INTEGER NOT NULL DEFAULT 0;
+     ALTER TABLE stories ADD COLUMN archived INTEGER NOT NULL DEFAULT 0;",
+    "CREATE TABLE comments (seq INTEGER PRIMARY KEY AUTOINCREMENT, item_id INTEGER NOT NULL, at TEXT NOT NULL, author TEXT NOT NULL, body TEXT NOT NULL);
+     CREATE INDEX comments_by_item ON comments (item_id);",
+    "CREATE TABLE labels (item_id INTEGER NOT NULL, label TEXT NOT NULL, PRIMARY KEY (item_id, label));
+     CREATE INDEX labels_by_label ON labels (label);",
+    "CREATE TABLE saved_filters (name TEXT PRIMARY KEY, query TEXT NOT NULL);",
+];
+
+pub struct SqliteDatabase {
+    connection: Connection,
+}
+
+impl SqliteDatabase {
+    pub fn open(file_path: &str) -> Result<Self> {
+        let connection = Connection::open(file_path).with_context(|| format!("could not open sqlite database {}", file_path))?;
+        Self::from_connection(connection).with_context(|| format!("could not load sqlite database {}", file_path))
+    }
+
+    #[cfg(test)]
+    pub fn open_in_memory() -> Result<Self> {
+        Self::from_connection(Connection::open_in_memory()?)
+    }
+
+    fn from_connection(connection: Connection) -> Result<Self> {
+        let version: usize = connection.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        if version > SQLITE_MIGRATIONS.len() { //refuse instead of writing a layout we don't understand
+            return Err(anyhow!("user_version {} was written by a newer version of this tool (this build supports up to {})", version, SQLITE_MIGRATIONS.len()));
+        }
+
+        for (from_version, migration) in SQLITE_MIGRATIONS.iter().enumerate().skip(version) {
+            let transaction = connection.unchecked_transaction()?;
+            transaction.execute_batch(migration).with_context(|| format!("failed to migrate from user_version {} to {}", from_version, from_version + 1))?;
+            transaction.pragma_update(None, "user_version", from_version + 1)?;
+            transaction.commit()?;
+        }
+        Ok(Self { connection })
+    }
+}
+
+// Statuses and priorities are stored by their serde name ("InProgress"), the same spelling db.json uses
+fn variant_to_sql(value: &impl Serialize) -> Result<String> {
+    match serde_json::to_value(value)? {
+        serde_json::Value::String(name) => Ok(name),
+        other => Err(anyhow!("unexpected enum representation {}", other)),
+    }
+}
+
+fn variant_from_sql<T: DeserializeOwned>(name: String) -> Result<T> {
+    serde_json::from_value(serde_json::Value::String(name.clone())).with_context(|| format!("unknown value {} in sqlite database", name))
+}
+
+fn variant_from_row<T: DeserializeOwned>(row: &Row, column: &str) -> rusqlite::Result<T> {
+    let index = row.as_ref().column_index(column)?;
+    variant_from_sql(row.get(index)?).map_err(|error| rusqlite::Error::FromSqlConversionFailure(index, rusqlite::types::Type::Text, error.into()))
+}
+
+// Every column of a row, so reads and writes can't drift apart as fields are added
+const EPIC_COLUMNS: &str = "id, name, description, status, owner, created_at, updated_at, archived";
+const STORY_COLUMNS: &str = "id, name, description, status, assignee, reporter, priority, points, created_at, updated_at, archived";
+
+fn epic_from_row(row: &Row) -> rusqlite::Result<(u32, Epic)> { //without its stories, those live in epic_stories
+    let mut epic = Epic::new(row.get("name")?, row.get("description")?);
+    epic.status = variant_from_row::<Status>(row, "status")?;
+    epic.owner = row.get("owner")?;
+    epic.created_at = row.get("created_at")?;
+    epic.updated_at = row.get("updated_at")?;
+    epic.archived = row.get("archived")?;
+    Ok((row.get("id")?, epic))
+}
+
+fn story_from_row(row: &Row) -> rusqlite::Result<(u32, Story)> {
+    let mut story = Story::new(row.get("name")?, row.get("description")?);
+    story.status = variant_from_row::<Status>(row, "status")?;
+    story.assignee = row.get("assignee")?;
+    story.reporter = row.get("reporter")?;
+    story.priority = variant_from_row::<Priority>(row, "priority")?;
+    story.points = row.get("points")?;
+    story.created_at = row.get("created_at")?;
+    story.updated_at = row.get("updated_at")?;
+    story.archived = row.get("archived")?;
+    Ok((row.get("id")?, story))
+}
+
+fn labels_of(connection: &Connection, item_id: u32) -> Result<BTreeSet<String>> {
+    let mut statement = connection.prepare("SELECT label FROM labels WHERE item_id = ?1")?;
+    let labels = statement.query_map(params![item_id], |row| row.get(0))?.collect::<rusqlite::Result<_>>()?;
+    Ok(labels)
+}
+
+fn replace_labels(connection: &Connection, item_id: u32, labels: &BTreeSet<String>) -> Result<()> { //the labels table doubles as DBState::label_index
+    connection.execute("DELETE FROM labels WHERE item_id = ?1", params![item_id])?;
+    let mut statement = connection.prepare("INSERT INTO labels (item_id, label) VALUES (?1, ?2)")?;
+    for label in labels {
+        statement.execute(params![item_id, label])?;
+    }
+    Ok(())
+}
+
+fn insert_epic(connection: &Connection, epic_id: u32, epic: &Epic) -> Result<()> {
+    connection.execute(&format!("INSERT OR REPLACE INTO epics ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)", EPIC_COLUMNS),
+        params![epic_id, epic.name, epic.description, variant_to_sql(&epic.status)?, epic.owner, epic.created_at, epic.updated_at, epic.archived])?;
+    replace_labels(connection, epic_id, &epic.labels)
+}
+
+fn insert_story(connection: &Connection, story_id: u32, story: &Story) -> Result<()> {
+    connection.execute(&format!("INSERT OR REPLACE INTO stories ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)", STORY_COLUMNS),
+        params![story_id, story.name, story.description, variant_to_sql(&story.status)?, story.assignee, story.reporter, variant_to_sql(&story.priority)?, story.points,
+            story.created_at, story.updated_at, story.archived])?;
+    replace_labels(connection, story_id, &story.labels)
+}
+
+fn insert_history(connection: &Connection, entries: &[HistoryEntry]) -> Result<()> {
+    let mut statement = connection.prepare("INSERT INTO history (item_id, at, user, field, old, new) VALUES (?1, ?2, ?3, ?4, ?5, ?6)")?;
+    for entry in entries {
+        statement.execute(params![entry.item_id, entry.at, entry.user, entry.field, entry.old, entry.new])?;
+    }
+    Ok(())
+}
+
+fn insert_comments(connection: &Connection, comments: &[Comment]) -> Result<()> {
+    let mut statement = connection.prepare("INSERT INTO comments (item_id, at, author, body) VALUES (?1, ?2, ?3, ?4)")?;
+    for comment in comments {
+        statement.execute(params![comment.item_id, comment.at, comment.author, comment.body])?;
+    }
+    Ok(())
+}
+
+impl Database for SqliteDatabase {
+
+    fn read_db(&self) -> Result<DBState> {
+        let last_item_id: u32 = self.connection.query_row("SELECT value FROM meta WHERE key = 'last_item_id'", [], |row| row.get(0)).optional()?.unwrap_or(0);
+        let mut db_state = DBState { last_item_id, ..Default::default() };
+
+        let mut statement = self.connection.prepare(&format!("SELECT {} FROM epics", EPIC_COLUMNS))?;
+        db_state.epics = statement.query_map([], epic_from_row)?.collect::<rusqlite::Result<_>>()?;
+
+        let mut statement = self.connection.prepare(&format!("SELECT {} FROM stories", STORY_COLUMNS))?;
+        db_state.stories = statement.query_map([], story_from_row)?.collect::<rusqlite::Result<_>>()?;
+
+        let mut statement = self.connection.prepare("SELECT item_id, at, user, field, old, new FROM history ORDER BY seq")?;
+        db_state.history = statement.query_map([], |row| Ok(HistoryEntry {
+            item_id: row.get(0)?, at: row.get(1)?, user: row.get(2)?, field: row.get(3)?, old: row.get(4)?, new: row.get(5)?,
+        }))?.collect::<rusqlite::Result<_>>()?;
+
+        let mut statement = self.connection.prepare("SELECT item_id, at, author, body FROM comments ORDER BY seq")?;
+        db_state.comments = statement.query_map([], |row| Ok(Comment {
+            item_id: row.get(0)?, at: row.get(1)?, author: row.get(2)?, body: row.get(3)?,
+        }))?.collect::<rusqlite::Result<_>>()?;
+
+        let mut statement = self.connection.prepare("SELECT item_id, label FROM labels")?;
+        let rows = statement.query_map([], |row| Ok((row.get::<_, u32>(0)?, row.get::<_, String>(1)?)))?;
+        for row in rows {
+            let (item_id, label) = row?;
+            if let Some(labels) = db_state.epics.get_mut(&item_id).map(|epic| &mut epic.labels).or_else(|| db_state.stories.get_mut(&item_id).map(|story| &mut story.labels)) {
+                labels.insert(label.clone());
+                db_state.label_index.entry(label).or_default().insert(item_id);
+            }
+        }
+
+        let mut statement = self.connection.prepare("SELECT name, query FROM saved_filters")?;
+        db_state.saved_filters = statement.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?.collect::<rusqlite::Result<_>>()?;
+
+        let mut statement = self.connection.prepare("SELECT epic_id, story_id FROM epic_stories ORDER BY epic_id, position")?;
+        let rows = statement.query_map([], |row| Ok((row.get::<_, u32>(0)?, row.get::<_, u32>(1)?)))?;
+        for row in rows {
+            let (epic_id, story_id) = row?;
+            if let Some(epic) = db_state.epics.get_mut(&epic_id) {
+                epic.stories.push(story_id);
+            }
+        }
+
+        Ok(db_state)
+    }
+
+    fn write_db(&self, db_state: &DBState) -> Result<()> {
+        let transaction = self.connection.unchecked_transaction()?; //all or nothing, same guarantee as the atomic rename in JSONFileDatabase
+        transaction.execute_batch("DELETE FROM epic_stories; DELETE FROM stories; DELETE FROM epics; DELETE FROM history; DELETE FROM comments; DELETE FROM labels; DELETE FROM saved_filters;")?;
+        transaction.execute("UPDATE meta SET value = ?1 WHERE key = 'last_item_id'", params![db_state.last_item_id])?;
+
+        for (id, epic) in &db_state.epics {
+            insert_epic(&transaction, *id, epic)?;
+            for (position, story_id) in epic.stories.iter().enumerate() {
+                transaction.execute("INSERT INTO epic_stories (epic_id, story_id, position) VALUES (?1, ?2, ?3)", params![id, story_id, position])?;
+            }
+        }
+        for (id, story) in &db_state.stories {
+            insert_story(&transaction, *id, story)?;
+        }
+        insert_history(&transaction, &db_state.history)?;
+        insert_comments(&transaction, &db_state.comments)?;
+        for (name, query) in &db_state.saved_filters {
+            transaction.execute("INSERT INTO saved_filters (name, query) VALUES (?1, ?2)", params![name, query])?;
+        }
+
+        transaction.commit()?;
+        Ok(())
+    }
+
+    fn get_epic(&self, epic_id: u32) -> Result<Option<Epic>> {
+        let row = self.connection.query_row(&format!("SELECT {} FROM epics WHERE id = ?1", EPIC_COLUMNS), params![epic_id], epic_from_row).optional()?;
+        let Some((_, mut epic)) = row else {
+            return Ok(None);
+        };
+
+        let mut statement = self.connection.prepare("SELECT story_id FROM epic_stories WHERE epic_id = ?1 ORDER BY position")?;
+        epic.stories = statement.query_map(params![epic_id], |row| row.get(0))?.collect::<rusqlite::Result<_>>()?;
+        epic.labels = labels_of(&self.connection, epic_id)?;
+        Ok(Some(epic))
+    }
+
+    fn get_story(&self, story_id: u32) -> Result<Option<Story>> {
+        let row = self.connection.query_row(&format!("SELECT {} FROM stories WHERE id = ?1", STORY_COLUMNS), params![story_id], story_from_row).optional()?;
+        let Some((_, mut story)) = row else {
+            return Ok(None);
+        };
+        story.labels = labels_of(&self.connection, story_id)?;
+        Ok(Some(story))
+    }
+
+    fn put_epic(&self, epic_id: u32, epic: &Epic) -> Result<()> {
+        let transaction = self.connection.unchecked_transaction()?;
+        insert_epic(&transaction, epic_id, epic)?;
+        transaction.execute("DELETE FROM epic_stories WHERE epic_id = ?1", params![epic_id])?; //the epic's story list is replaced as a whole
+        for (position, story_id) in epic.stories.iter().enumerate() {
+            transaction.execute("INSERT INTO epic_stories (epic_id, story_id, position) VALUES (?1, ?2, ?3)", params![epic_id, story_id, position])?;
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+
+    fn put_story(&self, story_id: u32, story: &Story) -> Result<()> {
+        let transaction = self.connection.unchecked_transaction()?;
+        insert_story(&transaction, story_id, story)?;
+        transaction.commit()?;
+        Ok(())
+    }
+
+    fn remove_epic(&self, epic_id: u32) -> Result<Option<Epic>> {
+        let epic = self.get_epic(epic_id)?;
+        if epic.is_some() {
+            let transaction = self.connection.unchecked_transaction()?;
+            transaction.execute("DELETE FROM labels WHERE item_id = ?1 OR item_id IN (SELECT story_id FROM epic_stories WHERE epic_id = ?1)", params![epic_id])?;
+            transaction.execute("DELETE FROM stories WHERE id IN (SELECT story_id FROM epic_stories WHERE epic_id = ?1)", params![epic_id])?;
+            transaction.execute("DELETE FROM epic_stories WHERE epic_id = ?1", params![epic_id])?;
+            transaction.execute("DELETE FROM epics WHERE id = ?1", params![epic_id])?;
+            transaction.commit()?;
+        }
+        Ok(epic)
+    }
+
+    fn remove_story(&self, story_id: u32) -> Result<Option<Story>> {
+        let story = self.get_story(story_id)?;
+        let transaction = self.connection.unchecked_transaction()?;
+        transaction.execute("DELETE FROM epic_stories WHERE story_id = ?1", params![story_id])?;
+        transaction.execute("DELETE FROM labels WHERE item_id = ?1", params![story_id])?;
+        transaction.execute("DELETE FROM stories WHERE id = ?1", params![story_id])?;
+        transaction.commit()?;
+        Ok(story)
+    }
+
+    fn append_history(&self, entries: &[HistoryEntry]) -> Result<()> {
+        let transaction = self.connection.unchecked_transaction()?;
+        insert_history(&transaction, entries)?;
+        transaction.commit()?;
+        Ok(())
+    }
+
+    fn append_comment(&self, comment: &Comment) -> Result<()> {
+        insert_comments(&self.connection, std::slice::from_ref(comment))
+    }
+
+    fn put_saved_filter(&self, name: &str, query: &str) -> Result<()> {
+        self.connection.execute("INSERT OR REPLACE INTO saved_filters (name, query) VALUES (?1, ?2)", params![name, query])?;
+        Ok(())
+    }
+
+    fn remove_saved_filter(&self, name: &str) -> Result<bool> {
+        Ok(self.connection.execute("DELETE FROM saved_filters WHERE name = ?1", params![name])? > 0)
+    }
+
+    fn add_epic(&self, epic: &Epic) -> Result<u32> {
+        let transaction = self.connection.unchecked_transaction()?;
+        let epic_id: u32 = transaction.query_row("UPDATE meta SET value = value + 1 WHERE key = 'last_item_id' RETURNING value", [], |row| row.get(0))?;
+        insert_epic(&transaction, epic_id, epic)?;
+        for (position, story_id) in epic.stories.iter().enumerate() {
+            transaction.execute("INSERT INTO epic_stories (epic_id, story_id, position) VALUES (?1, ?2, ?3)", params![epic_id, story_id, position])?;
+        }
+        transaction.commit()?;
+        Ok(epic_id)
+    }
+
+    fn add_story(&self, epic_id: u32, story: &Story) -> Result<u32> {
+        let transaction = self.connection.unchecked_transaction()?;
+        let epic_exists: bool = transaction.query_row("SELECT EXISTS (SELECT 1 FROM epics WHERE id = ?1)", params![epic_id], |row| row.get(0))?;
+        if !epic_exists {
+            return Err(anyhow!("could not find epic {} in database!", epic_id)); //dropping the transaction rolls it back
+        }
+        let story_id: u32 = transaction.query_row("UPDATE meta SET value = value + 1 WHERE key = 'last_item_id' RETURNING value", [], |row| row.get(0))?;
+        insert_story(&transaction, story_id, story)?;
+        transaction.execute("INSERT INTO epic_stories (epic_id, story_id, position) \
+            VALUES (?1, ?2, (SELECT COALESCE(MAX(position) + 1, 0) FROM epic_stories WHERE epic_id = ?1))", params![epic_id, story_id])?;
+        transaction.commit()?;
+        Ok(story_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn open_should_create_schema_and_reopen_existing_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let file_path = dir.path().join("db.sqlite").to_str().unwrap().to_owned();
+
+        let db = SqliteDatabase::open(&file_path).unwrap();
+        let mut db_state = db.read_db().unwrap();
+        assert_eq!(db_state.last_item_id, 0);
+
+        let mut epic = Epic::new("epic 1".to_owned(), "epic 1".to_owned());
+        epic.stories = vec![3, 2];
+        epic.status = Status::InProgress;
+        epic.owner = Some("alice".to_owned());
+        db_state.epics.insert(1, epic);
+        let mut story = Story::new("story 2".to_owned(), "".to_owned());
+        story.assignee = Some("bob".to_owned());
+        story.priority = Priority::Highest;
+        story.points = Some(13);
+        story.labels.insert("backend".to_owned());
+        db_state.stories.insert(2, story);
+        db_state.stories.insert(3, Story::new("story 3".to_owned(), "".to_owned()));
+        db_state.last_item_id = 3;
+        db_state.reindex_labels(2);
+        db_state.history.push(HistoryEntry { item_id: 2, at: chrono::Utc::now(), user: None, field: "created".to_owned(), old: "".to_owned(), new: "story 2".to_owned() });
+        db_state.comments.push(Comment { item_id: 2, at: chrono::Utc::now(), author: "alice".to_owned(), body: "multi\nline".to_owned() });
+        db_state.saved_filters.insert("mine".to_owned(), "assignee = bob".to_owned());
+        db.write_db(&db_state).unwrap();
+        drop(db);
+
+        let db = SqliteDatabase::open(&file_path).unwrap();
+        assert_eq!(db.read_db().unwrap(), db_state); //story order inside the epic survives the round trip
+    }
+
+    #[test]
+    fn jira_database_should_pick_sqlite_from_file_extension() {
+        let dir = tempfile::tempdir().unwrap();
+        let file_path = dir.path().join("data").join("db.sqlite").to_str().unwrap().to_owned();
+
+        let db = crate::db::JiraDatabase::new(file_path.clone()).unwrap();
+        let epic_id = db.create_epic(Epic::new("epic".to_owned(), "".to_owned())).unwrap();
+
+        let reopened = SqliteDatabase::open(&file_path).unwrap();
+        assert_eq!(reopened.read_db().unwrap().epics[&epic_id].name, "epic");
+    }
+
+    #[test]
+    fn open_should_refuse_newer_user_version() {
+        let dir = tempfile::tempdir().unwrap();
+        let file_path = dir.path().join("db.sqlite").to_str().unwrap().to_owned();
+        let connection = Connection::open(&file_path).unwrap();
+        connection.pragma_update(None, "user_version", SQLITE_MIGRATIONS.len() + 1).unwrap();
+        drop(connection);
+
+        let error = format!("{:#}", SqliteDatabase::open(&file_path).err().unwrap());
+        assert!(error.contains("newer version"));
+    }
+}
@@ -0,0 +1,308 @@
+use std::collections::HashMap;
+use std::io::Read;
+
+use anyhow::{anyhow, Context, Result};
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::models::{DBState, Epic, Status, Story};
+
+// How a Jira export maps onto epics and stories. Every field has a default matching a stock Jira Cloud
+// project, so a mapping file only needs the keys that differ, e.g. { "story_types": ["Story", "Task", "Bug"] }
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct JiraMapping {
+    pub epic_types: Vec<String>,
+    pub story_types: Vec<String>,
+    pub statuses: HashMap<String, String>, //Jira status name -> OPEN / IN PROGRESS / RESOLVED / CLOSED, names compare case-insensitively
+    pub default_status: String, //used when neither the status name nor its category is known
+    pub epic_link_fields: Vec<String>, //JSON field names holding the epic's key, tried in order
+    pub csv_columns: CsvColumns,
+    pub unassigned_epic_name: String, //stories without an epic link are collected in an epic with this name
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct CsvColumns {
+    pub id: String,
+    pub key: String,
+    pub summary: String,
+    pub description: String,
+    pub issue_type: String,
+    pub status: String,
+    pub epic_link: Vec<String>, //first non-empty column wins; holds an epic's key or numeric id
+}
+
+impl Default for JiraMapping {
+    fn default() -> Self {
+        let statuses = [
+            ("To Do", "OPEN"), ("Open", "OPEN"), ("Backlog", "OPEN"), ("Selected for Development", "OPEN"), ("Reopened", "OPEN"),
+            ("In Progress", "IN PROGRESS"), ("In Review", "IN PROGRESS"),
+            ("Resolved", "RESOLVED"),
+            ("Done", "CLOSED"), ("Closed", "CLOSED"),
+        ];
+        Self {
+            epic_types: vec!["Epic".to_owned()],
+            story_types: vec!["Story".to_owned(), "Task".to_owned()],
+            statuses: statuses.iter().map(|(jira, ours)| (jira.to_string(), ours.to_string())).collect(),
+            default_status: "OPEN".to_owned(),
+            epic_link_fields: vec!["customfield_10014".to_owned(), "parent".to_owned()],
+            csv_columns: CsvColumns::default(),
+            unassigned_epic_name: "Imported without epic".to_owned(),
+        }
+    }
+}
+
+impl Default for CsvColumns {
+    fn default() -> Self {
+        Self {
+            id: "Issue id".to_owned(),
+            key: "Issue key".to_owned(),
+            summary: "Summary".to_owned(),
+            description: "Description".to_owned(),
+            issue_type: "Issue Type".to_owned(),
+            status: "Status".to_owned(),
+            epic_link: vec!["Custom field (Epic Link)".to_owned(), "Parent".to_owned(), "Parent id".to_owned()],
+        }
+    }
+}
+
+impl JiraMapping {
+    pub fn from_file(file_path: &str) -> Result<Self> {
+        let content = std::fs::read_to_string(file_path).with_context(|| format!("could not read mapping file {}", file_path))?;
+        serde_json::from_str(&content).with_context(|| format!("mapping file {} is invalid", file_path))
+    }
+
+    fn status_for(&self, issue: &JiraIssue) -> Result<Status> {
+        let mapped = self.statuses.iter().find(|(jira, _)| jira.eq_ignore_ascii_case(&issue.status)).map(|(_, ours)| ours.as_str());
+        let by_category = match issue.status_category.as_deref() { //Jira's own grouping, only present in JSON exports
+            Some("new") => Some("OPEN"),
+            Some("indeterminate") => Some("IN PROGRESS"),
+            Some("done") => Some("CLOSED"),
+            _ => None,
+        };
+        let status = mapped.or(by_category).unwrap_or(&self.default_status);
+        status.parse::<Status>().map_err(|error| anyhow!("status mapping for \"{}\": {}", issue.status, error))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JiraFormat {
+    Json,
+    Csv,
+}
+
+impl JiraFormat {
+    pub fn from_path(file_path: &str) -> Self {
+        if file_path.to_lowercase().ends_with(".csv") { JiraFormat::Csv } else { JiraFormat::Json }
+    }
+}
+
+// What the importer produced: a self-contained DBState (ids start at 1, see DBState::merge) and the issues it left out
+#[derive(Debug)]
+pub struct JiraImport {
+    pub state: DBState,
+    pub skipped: Vec<String>,
+}
+
+// One issue in either export format, before it is mapped
+#[derive(Debug, Default)]
+struct JiraIssue {
+    id: Option<String>,
+    key: String,
+    summary: String,
+    description: String,
+    issue_type: String,
+    status: String,
+    status_category: Option<String>,
+    epic_link: Option<String>,
+}
+
+pub fn import(input: impl Read, format: JiraFormat, mapping: &JiraMapping) -> Result<JiraImport> {
+    let issues = match format {
+        JiraFormat::Json => read_json(input, mapping)?,
+        JiraFormat::Csv => read_csv(input, mapping)?,
+    };
+    build_state(issues, mapping)
+}
+
+// Accepts a REST search response ({ "issues": [...] }) or a bare array of issues
+fn read_json(input: impl Read, mapping: &JiraMapping) -> Result<Vec<JiraIssue>> {
+    let document: Value = serde_json::from_reader(input).context("Jira export is not valid JSON")?;
+    let issues = document.get("issues").unwrap_or(&document).as_array().ok_or_else(|| anyhow!("expected an \"issues\" array in the Jira export"))?;
+
+    issues.iter().map(|issue| {
+        let fields = issue.get("fields").ok_or_else(|| anyhow!("issue {} has no fields", issue["key"]))?;
+        let epic_link = mapping.epic_link_fields.iter().find_map(|field| match fields.get(field) {
+            Some(Value::String(key)) => Some(key.clone()),
+            Some(Value::Object(parent)) => parent.get("key").and_then(Value::as_str).map(str::to_owned), //"parent" is an object
+            _ => None,
+        });
+        Ok(JiraIssue {
+            id: issue.get("id").and_then(Value::as_str).map(str::to_owned),
+            key: issue.get("key").and_then(Value::as_str).ok_or_else(|| anyhow!("issue without a key"))?.to_owned(),
+            summary: fields["summary"].as_str().unwrap_or_default().to_owned(),
+            description: plain_text(&fields["description"]),
+            issue_type: fields["issuetype"]["name"].as_str().unwrap_or_default().to_owned(),
+            status: fields["status"]["name"].as_str().unwrap_or_default().to_owned(),
+            status_category: fields["status"]["statusCategory"]["key"].as_str().map(str::to_owned),
+            epic_link,
+        })
+    }).collect()
+}
+
+// Descriptions are plain strings in API v2 and Atlassian Document Format trees in v3, keep just the text of the latter
+fn plain_text(description: &Value) -> String {
+    match description {
+        Value::String(text) => text.clone(),
+        Value::Object(node) => {
+            let text = node.get("text").and_then(Value::as_str).unwrap_or_default().to_owned();
+            let children = node.get("content").and_then(Value::as_array).map(|content| content.iter().map(plain_text).collect::<Vec<_>>());
+            match (node.get("type").and_then(Value::as_str), children) {
+                (Some("doc"), Some(children)) => children.join("\n"), //one line per top-level block
+                (_, Some(children)) => text + &children.concat(),
+                (_, None) => text,
+            }
+        }
+        _ => String::new(),
+    }
+}
+
+fn read_csv(input: impl Read, mapping: &JiraMapping) -> Result<Vec<JiraIssue>> {
+    let mut reader = csv::Reader::from_reader(input);
+    let headers = reader.headers()?.clone();
+    let columns = &mapping.csv_columns;
+    let column = |name: &str| headers.iter().position(|header| header == name); //Jira repeats some headers, the first one wins
+    let required = |name: &str| column(name).ok_or_else(|| anyhow!("Jira CSV export has no \"{}\" column", name));
+
+    let (key, summary, issue_type) = (required(&columns.key)?, required(&columns.summary)?, required(&columns.issue_type)?);
+    let (id, description, status) = (column(&columns.id), column(&columns.description), column(&columns.status));
+    let epic_links: Vec<usize> = columns.epic_link.iter().filter_map(|name| column(name)).collect();
+
+    let mut issues = vec![];
+    for record in reader.records() {
+        let record = record?;
+        let get = |index: Option<usize>| index.and_then(|index| record.get(index)).unwrap_or_default().to_owned();
+        issues.push(JiraIssue {
+            id: Some(get(id)).filter(|id| !id.is_empty()),
+            key: get(Some(key)),
+            summary: get(Some(summary)),
+            description: get(description),
+            issue_type: get(Some(issue_type)),
+            status: get(status),
+            status_category: None,
+            epic_link: epic_links.iter().map(|index| get(Some(*index))).find(|link| !link.is_empty()),
+        });
+    }
+    Ok(issues)
+}
+
+fn build_state(issues: Vec<JiraIssue>, mapping: &JiraMapping) -> Result<JiraImport> {
+    let is_one_of = |types: &[String], issue_type: &str| types.iter().any(|name| name.eq_ignore_ascii_case(issue_type));
+    let mut state = DBState { last_item_id: 0, ..Default::default() };
+    let mut skipped = vec![];
+    let mut epic_ids: HashMap<String, u32> = HashMap::new(); //Jira key and numeric id -> our epic id
+
+    let (epics, rest): (Vec<JiraIssue>, Vec<JiraIssue>) = issues.into_iter().partition(|issue| is_one_of(&mapping.epic_types, &issue.issue_type));
+    for issue in epics {
+        let mut epic = Epic::new(issue.summary.clone(), issue.description.clone());
+        epic.status = mapping.status_for(&issue)?;
+        state.last_item_id += 1;
+        state.epics.insert(state.last_item_id, epic);
+        epic_ids.insert(issue.key.clone(), state.last_item_id);
+        if let Some(id) = issue.id {
+            epic_ids.insert(id, state.last_item_id);
+        }
+    }
+
+    let mut unassigned_epic_id = None;
+    for issue in rest {
+        if !is_one_of(&mapping.story_types, &issue.issue_type) {
+            skipped.push(format!("{} ({}): issue type is not mapped", issue.key, issue.issue_type));
+            continue;
+        }
+        let mut story = Story::new(issue.summary.clone(), issue.description.clone());
+        story.status = mapping.status_for(&issue)?;
+        state.last_item_id += 1;
+        let story_id = state.last_item_id;
+        state.stories.insert(story_id, story);
+
+        let epic_id = match issue.epic_link.as_ref().and_then(|link| epic_ids.get(link)) {
+            Some(epic_id) => *epic_id,
+            None => *unassigned_epic_id.get_or_insert_with(|| { //created on first use so a clean export doesn't get an empty epic
+                state.last_item_id += 1;
+                state.epics.insert(state.last_item_id, Epic::new(mapping.unassigned_epic_name.clone(), "Stories imported from Jira without an epic link".to_owned()));
+                state.last_item_id
+            }),
+        };
+        state.epics.get_mut(&epic_id).unwrap().stories.push(story_id);
+    }
+
+    Ok(JiraImport { state, skipped })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn import_should_map_json_export() {
+        let export = r#"{ "issues": [
+            { "id": "10001", "key": "PROJ-1", "fields": { "summary": "Checkout", "description": "Epic text",
+              "issuetype": { "name": "Epic" }, "status": { "name": "In Progress" } } },
+            { "id": "10002", "key": "PROJ-2", "fields": { "summary": "Pay by card",
+              "description": { "type": "doc", "content": [{ "type": "paragraph", "content": [{ "type": "text", "text": "Visa and " }, { "type": "text", "text": "Mastercard" }] }] },
+              "issuetype": { "name": "Story" }, "status": { "name": "Ready for QA", "statusCategory": { "key": "done" } }, "parent": { "key": "PROJ-1" } } },
+            { "id": "10003", "key": "PROJ-3", "fields": { "summary": "Refactor", "issuetype": { "name": "task" },
+              "status": { "name": "To Do" }, "customfield_10014": "PROJ-1" } },
+            { "id": "10004", "key": "PROJ-4", "fields": { "summary": "Crash", "issuetype": { "name": "Bug" }, "status": { "name": "Done" } } }
+        ] }"#;
+
+        let import = import(export.as_bytes(), JiraFormat::Json, &JiraMapping::default()).unwrap();
+        let state = import.state;
+
+        assert_eq!(state.epics.len(), 1);
+        assert_eq!(state.epics[&1].name, "Checkout");
+        assert_eq!(state.epics[&1].status, Status::InProgress);
+        assert_eq!(state.epics[&1].stories, vec![2, 3]);
+        assert_eq!(state.stories[&2].description, "Visa and Mastercard");
+        assert_eq!(state.stories[&2].status, Status::Closed); //unknown name, mapped through its status category
+        assert_eq!(state.stories[&3].status, Status::Open);
+        assert_eq!(import.skipped, vec!["PROJ-4 (Bug): issue type is not mapped".to_owned()]);
+        assert_eq!(state.validate(), vec![]);
+    }
+
+    #[test]
+    fn import_should_map_csv_export_with_custom_mapping() {
+        let export = "Summary,Issue key,Issue id,Issue Type,Status,Description,Custom field (Epic Link),Sprint,Sprint
+Platform,OPS-1,200,Epic,Open,,,,
+Upgrade db,OPS-2,201,Story,Waiting,\"two, lines\",OPS-1,S1,S2
+Rotate keys,OPS-3,202,Bug,Done,,,,
+Loose end,OPS-4,203,Story,Done,,,,
+";
+        let mapping: JiraMapping = serde_json::from_str(r#"{
+            "story_types": ["Story", "Bug"],
+            "statuses": { "waiting": "IN PROGRESS", "done": "RESOLVED", "open": "OPEN" }
+        }"#).unwrap();
+
+        let import = import(export.as_bytes(), JiraFormat::Csv, &mapping).unwrap();
+        let state = import.state;
+
+        assert!(import.skipped.is_empty());
+        assert_eq!(state.epics[&1].stories, vec![2]);
+        assert_eq!(state.stories[&2].description, "two, lines");
+        assert_eq!(state.stories[&2].status, Status::InProgress);
+        assert_eq!(state.epics[&4].name, "Imported without epic"); //created when the first unlinked story shows up
+        assert_eq!(state.epics[&4].stories, vec![3, 5]);
+        assert_eq!(state.stories[&5].status, Status::Resolved);
+        assert_eq!(state.validate(), vec![]);
+    }
+
+    #[test]
+    fn import_should_reject_bad_status_mapping() {
+        let export = r#"[{ "key": "PROJ-1", "fields": { "summary": "", "issuetype": { "name": "Epic" }, "status": { "name": "Open" } } }]"#;
+        let mapping: JiraMapping = serde_json::from_str(r#"{ "statuses": { "Open": "NEW" } }"#).unwrap();
+
+        assert!(import(export.as_bytes(), JiraFormat::Json, &mapping).is_err());
+    }
+}
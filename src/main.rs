@@ -5,17 +5,68 @@ mod db;
 use db::*;
 
 mod ui;
+use ui::Prompts;
 mod io_utils;
 use io_utils::*;
 
 mod navigator;
 use navigator::*;
 
+mod cli;
+mod output;
+mod csv_io;
+mod jira_import;
+mod report;
+mod query;
+mod search;
+mod workflow;
+use clap::Parser;
+
 
 fn main() {
+    let cli = cli::Cli::parse();
+    let current_user = cli.current_user();
+
     //TODO: create database and manager
-    let db = Rc::new(JiraDatabase::new("./data/db.json".to_owned()));
-    let mut navigator = Navigator::new(Rc::clone(&db));
+    let workflow = match workflow::Workflow::load(&cli.workflow) { //no workflow file allows every status change
+        Ok(workflow) => workflow,
+        Err(error) => exit_with_error(error),
+    };
+    let db = match JiraDatabase::new(cli.db) { //a missing or empty db file gets seeded with an empty board
+        Ok(db) => db.with_user(current_user.clone()).with_workflow(workflow),
+        Err(error) => exit_with_error(error),
+    };
+
+    match cli.command {
+        Some(command) => { //non-interactive: run one command for scripts and git hooks, never prompt
+            if let Err(error) = cli::run(command, &db, current_user.as_deref(), &mut std::io::stdout()) {
+                exit_with_error(error);
+            }
+        }
+        None => run_interactive(Rc::new(db), current_user),
+    }
+}
+
+fn run_interactive(db: Rc<JiraDatabase>, current_user: Option<String>) {
+    if let Err(error) = db.read_db() { //a crash in the middle of a write could have left the db file corrupt, offer the backup before starting
+        println!("Error loading database: {:#}", error);
+        if !(db.has_backup() && (Prompts::new().recover_from_backup)()) {
+            exit_with_error(error); //a corrupt db file is a hard error, every page would fail to render anyway
+        }
+        if let Err(error) = db.recover_from_backup() {
+            exit_with_error(error);
+        }
+    }
+    match db.validate() { //a bad db.json can list stories that don't exist, which would make pages fail later on
+        Ok(issues) if !issues.is_empty() && (Prompts::new().repair_database)(&issues) => {
+            if let Err(error) = db.repair() {
+                exit_with_error(error);
+            }
+        }
+        Ok(_) => {}
+        Err(error) => exit_with_error(error),
+    }
+    let mut navigator = Navigator::new(Rc::clone(&db), current_user);
 
     loop {
         clearscreen::clear().unwrap();
@@ -32,7 +83,7 @@ fn main() {
         let user_input = get_user_input();
 
           //4. pass input to page's input handler    
-        match page.handle_input(user_input.trim()) { //handle_input is a method for Navigator struct; therefore compiler infers page relates to pages field of Navigator struct, which has Page trait objects in a vector
+        match navigator.handle_input(user_input.trim()) { //global keys like undo are checked first, then the input goes to the current page's handle_input
             Err(error) => {
                 println!("Error getting user input: {}\nPress any key to continue...", error);
                 wait_for_key_press();
@@ -51,4 +102,9 @@ fn main() {
         break;//recall that 'break' is a keyword to exit early from a loop, in this case, the loop to render the screen
         }
     }
-}
\ No newline at end of file
+}
+
+fn exit_with_error(error: anyhow::Error) -> ! {
+    eprintln!("Error: {:#}", error);
+    std::process::exit(1);
+}
@@ -1,17 +1,42 @@
-use std::{collections::HashMap, fmt::Display};
+use std::{collections::{BTreeMap, BTreeSet, HashMap}, fmt::Display, str::FromStr};
+use chrono::{DateTime, Utc};
 use serde::{Serialize, Deserialize};
+use itertools::Itertools;
 
 #[derive(Debug, PartialEq, Eq)]
 pub enum Action {
     NavigateToEpicDetail {epic_id: u32},
     NavigateToStoryDetail { epic_id: u32, story_id: u32 },
     NavigateToPreviousPage,
+    NavigateToMyStories,
+    NavigateToHistory { item_id: u32 },
+    NavigateToTrash,
+    Search, //asks for the query, see Prompts::search
+    QueryStories, //asks for a filter, see query::Query
+    NavigateToSavedFilters,
+    OpenSavedFilter { name: String },
+    SaveFilter { query: String }, //asks for the name, see Prompts::save_filter
+    DeleteSavedFilter, //asks which one, see Prompts::delete_saved_filter
     CreateEpic,
     UpdateEpicStatus {epic_id: u32},
+    EditEpic {epic_id: u32},
+    SetEpicOwner {epic_id: u32},
     DeleteEpic {epic_id: u32},
     CreateStory {epic_id: u32},
     UpdateStoryStatus { story_id: u32 },
+    EditStory { story_id: u32 },
+    AssignStory { story_id: u32 },
+    EstimateStory { story_id: u32 },
     DeleteStory {epic_id: u32, story_id: u32},
+    AddComment { item_id: u32 },
+    AddLabel { item_id: u32 },
+    RemoveLabel { item_id: u32 },
+    FilterByLabel { epic_id: Option<u32> }, //None filters the epics on HomePage
+    MoveStory {epic_id: u32, story_id: u32},
+    RestoreItem { item_id: u32 },
+    PurgeItem, //asks which item, see Prompts::purge_item
+    Undo,
+    Redo,
     Exit,
 }
 
@@ -23,6 +48,10 @@ pub enum Status {
     Resolved,
     Closed,
 }
+impl Status {
+    pub const ALL: [Status; 4] = [Status::Open, Status::InProgress, Status::Resolved, Status::Closed]; //in workflow order
+}
+
 //Step 1, Note 1: implement the Display trait for the Status Enum, to allow Enum to String mapping
 impl Display for Status {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result { //&self means the function takes a references to the Status enum
@@ -42,6 +71,57 @@ impl Display for Status {
         }
     }
 }
+// Parses what people type on the command line: the display form ("IN PROGRESS") or a relaxed one ("in-progress", "InProgress")
+impl FromStr for Status {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let normalized: String = input.chars().filter(|c| c.is_alphanumeric()).collect::<String>().to_lowercase();
+        match normalized.as_str() {
+            "open" => Ok(Self::Open),
+            "inprogress" => Ok(Self::InProgress),
+            "resolved" => Ok(Self::Resolved),
+            "closed" => Ok(Self::Closed),
+            _ => Err(format!("unknown status \"{}\" (expected OPEN, IN PROGRESS, RESOLVED or CLOSED)", input)),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Copy, Default)]
+pub enum Priority { //declared from lowest to highest so the derived Ord sorts by urgency
+    Lowest,
+    Low,
+    #[default]
+    Medium,
+    High,
+    Highest,
+}
+
+impl Priority {
+    pub const ALL: [Priority; 5] = [Priority::Lowest, Priority::Low, Priority::Medium, Priority::High, Priority::Highest];
+}
+
+impl Display for Priority {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Lowest => write!(f, "LOWEST"),
+            Self::Low => write!(f, "LOW"),
+            Self::Medium => write!(f, "MEDIUM"),
+            Self::High => write!(f, "HIGH"),
+            Self::Highest => write!(f, "HIGHEST"),
+        }
+    }
+}
+
+impl FromStr for Priority {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        Priority::ALL.into_iter().find(|priority| priority.to_string().eq_ignore_ascii_case(input.trim()))
+            .ok_or_else(|| format!("unknown priority \"{}\" (expected LOWEST, LOW, MEDIUM, HIGH or HIGHEST)", input))
+    }
+}
+
 #[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
 pub struct Epic {
     // TODO: add fields (make sure the fields are public)
@@ -49,6 +129,16 @@ pub struct Epic {
     pub description: String,
     pub status: Status,
     pub stories: Vec<u32>,
+    #[serde(default)] //absent in files written before epics had owners
+    pub owner: Option<String>,
+    #[serde(default)] //set by JiraDatabase, None for entities written before timestamps existed
+    pub created_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub updated_at: Option<DateTime<Utc>>,
+    #[serde(default)] //in the trash, see JiraDatabase::delete_epic; archiving an epic archives its stories too
+    pub archived: bool,
+    #[serde(default)] //normalized with normalize_label, DBState::label_index mirrors them
+    pub labels: BTreeSet<String>,
 }
 
 impl Epic {
@@ -59,8 +149,25 @@ impl Epic {
             description,
             status: Status::Open,
             stories: vec![],
+            owner: None,
+            created_at: None,
+            updated_at: None,
+            archived: false,
+            labels: BTreeSet::new(),
         }
     }
+
+    // The fields that differ from 'new', as shown in the history log; the story list is tracked on the stories themselves
+    pub fn changes(&self, new: &Epic) -> Vec<FieldChange> {
+        let mut changes = vec![];
+        push_change(&mut changes, "name", &self.name, &new.name);
+        push_change(&mut changes, "description", &self.description, &new.description);
+        push_change(&mut changes, "status", &self.status.to_string(), &new.status.to_string());
+        push_change(&mut changes, "owner", self.owner.as_deref().unwrap_or(""), new.owner.as_deref().unwrap_or(""));
+        push_change(&mut changes, "labels", &self.labels.iter().join(", "), &new.labels.iter().join(", "));
+        push_change(&mut changes, "archived", &self.archived.to_string(), &new.archived.to_string());
+        changes
+    }
 }
 
 #[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
@@ -69,6 +176,22 @@ pub struct Story {
     pub name: String,
     pub description: String,
     pub status: Status,
+    #[serde(default)] //absent in files written before stories had people on them
+    pub assignee: Option<String>,
+    #[serde(default)]
+    pub reporter: Option<String>,
+    #[serde(default)] //absent in files written before stories were estimated
+    pub priority: Priority,
+    #[serde(default)]
+    pub points: Option<u32>, //None means not estimated yet, which is different from 0
+    #[serde(default)]
+    pub created_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub updated_at: Option<DateTime<Utc>>,
+    #[serde(default)] //in the trash, hidden from every listing until restored or purged
+    pub archived: bool,
+    #[serde(default)]
+    pub labels: BTreeSet<String>,
 }
 
 impl Story {
@@ -77,17 +200,538 @@ impl Story {
         Self {
             name,
             description,
-            status: Status::Open,        
+            status: Status::Open,
+            assignee: None,
+            reporter: None,
+            priority: Priority::Medium,
+            points: None,
+            created_at: None,
+            updated_at: None,
+            archived: false,
+            labels: BTreeSet::new(),
         }    
     }
+
+    pub fn changes(&self, new: &Story) -> Vec<FieldChange> {
+        let points = |story: &Story| story.points.map(|points| points.to_string()).unwrap_or_default();
+        let mut changes = vec![];
+        push_change(&mut changes, "name", &self.name, &new.name);
+        push_change(&mut changes, "description", &self.description, &new.description);
+        push_change(&mut changes, "status", &self.status.to_string(), &new.status.to_string());
+        push_change(&mut changes, "assignee", self.assignee.as_deref().unwrap_or(""), new.assignee.as_deref().unwrap_or(""));
+        push_change(&mut changes, "reporter", self.reporter.as_deref().unwrap_or(""), new.reporter.as_deref().unwrap_or(""));
+        push_change(&mut changes, "priority", &self.priority.to_string(), &new.priority.to_string());
+        push_change(&mut changes, "points", &points(self), &points(new));
+        push_change(&mut changes, "labels", &self.labels.iter().join(", "), &new.labels.iter().join(", "));
+        push_change(&mut changes, "archived", &self.archived.to_string(), &new.archived.to_string());
+        changes
+    }
+}
+
+// Labels are compared case-insensitively and shown as typed in lower case, one word each (e.g. "backend", "tech-debt")
+pub fn normalize_label(input: &str) -> Result<String, String> {
+    let label = input.trim().to_lowercase();
+    if label.is_empty() || label.contains(|c: char| c.is_whitespace() || c == ',') {
+        return Err(format!("invalid label \"{}\" (one word, no commas)", input.trim()));
+    }
+    Ok(label)
+}
+
+pub type FieldChange = (&'static str, String, String); //field name, old and new value in display form, "" for unset
+
+fn push_change(changes: &mut Vec<FieldChange>, field: &'static str, old: &str, new: &str) {
+    if old != new {
+        changes.push((field, old.to_owned(), new.to_owned()));
+    }
 }
 
+// One line of the append-only audit log in DBState::history
 #[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
+pub struct HistoryEntry {
+    pub item_id: u32, //an epic or a story, they share one id space
+    pub at: DateTime<Utc>,
+    pub user: Option<String>, //None when no current user was configured
+    pub field: String, //the changed field, or "created" / "deleted" / "restored" / "purged" for the item as a whole
+    pub old: String,
+    pub new: String,
+}
+
+// One message in the discussion of an epic or story, see DBState::comments_on
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
+pub struct Comment {
+    pub item_id: u32,
+    pub at: DateTime<Utc>,
+    pub author: String,
+    pub body: String,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Default)]
 pub struct DBState {
     // This struct represents the entire db state which includes the last_item_id, epics, and stories
     // TODO: add fields (make sure the fields are public)
     pub last_item_id: u32,
     pub epics: HashMap<u32, Epic>,
     pub stories: HashMap<u32, Story>,
+    #[serde(default)] //oldest first, entries are never edited or removed (not even when their item is deleted)
+    pub history: Vec<HistoryEntry>,
+    #[serde(default)] //oldest first, like history they outlive a purged item
+    pub comments: Vec<Comment>,
+    #[serde(default)] //label -> every epic and story carrying it; kept up to date by reindex_labels, see validate
+    pub label_index: BTreeMap<String, BTreeSet<u32>>,
+    #[serde(default)] //name -> filter in the query language, see query::Query
+    pub saved_filters: BTreeMap<String, String>,
 } //added 'use std::fm::Display to allow fields within DBState to be Cloned (Clone needs Display trait)
-   // Also derived Clone trait to Epic, Story and DBState data types
\ No newline at end of file
+   // Also derived Clone trait to Epic, Story and DBState data types
+
+// Epics, stories and saved filters by key as they were at some point; None means it did not exist
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct ItemStates {
+    pub epics: HashMap<u32, Option<Epic>>,
+    pub stories: HashMap<u32, Option<Story>>,
+    pub comments: Option<Vec<Comment>>, //the whole comment log, only when it changed
+    pub saved_filters: BTreeMap<String, Option<String>>,
+}
+
+// What one action did to the board: every item it touched, before and after. Applying 'before' undoes it.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct ChangeSet {
+    pub before: ItemStates,
+    pub after: ItemStates,
+}
+
+impl ChangeSet {
+    pub fn is_empty(&self) -> bool {
+        self.before.epics.is_empty() && self.before.stories.is_empty() && self.before.comments.is_none() && self.before.saved_filters.is_empty()
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum IntegrityIssue {
+    MissingStory { epic_id: u32, story_id: u32 }, //listed in Epic.stories but not in DBState.stories
+    DuplicateStoryLink { story_id: u32, epic_ids: Vec<u32> }, //listed more than once, in one epic or across several
+    OrphanStory { story_id: u32 }, //in DBState.stories but not listed by any epic
+    LastItemIdTooLow { last_item_id: u32, max_id: u32 }, //the next create would reuse an existing id
+    StaleLabelIndex, //DBState::label_index doesn't match the labels on the items, e.g. after a hand edit
+}
+
+impl Display for IntegrityIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingStory { epic_id, story_id } => {
+                write!(f, "epic {} lists story {}, which does not exist", epic_id, story_id)
+            }
+            Self::DuplicateStoryLink { story_id, epic_ids } => {
+                write!(f, "story {} is listed more than once (epics {})", story_id, epic_ids.iter().map(|id| id.to_string()).collect::<Vec<_>>().join(", "))
+            }
+            Self::OrphanStory { story_id } => {
+                write!(f, "story {} does not belong to any epic", story_id)
+            }
+            Self::LastItemIdTooLow { last_item_id, max_id } => {
+                write!(f, "last_item_id is {} but id {} is already in use", last_item_id, max_id)
+            }
+            Self::StaleLabelIndex => {
+                write!(f, "the label index does not match the labels on epics and stories")
+            }
+        }
+    }
+}
+
+// Title of the epic that repair() creates to adopt orphan stories
+pub const RECOVERED_EPIC_NAME: &str = "Recovered stories";
+
+impl DBState {
+    // Reports every inconsistency between epics, stories and last_item_id without changing anything
+    pub fn validate(&self) -> Vec<IntegrityIssue> {
+        let mut issues = vec![];
+        let mut links: HashMap<u32, Vec<u32>> = HashMap::new(); //story id -> every epic that lists it, once per listing
+
+        let mut epic_ids: Vec<&u32> = self.epics.keys().collect();
+        epic_ids.sort();
+        for epic_id in epic_ids {
+            for story_id in &self.epics[epic_id].stories {
+                if self.stories.contains_key(story_id) {
+                    links.entry(*story_id).or_default().push(*epic_id);
+                } else {
+                    issues.push(IntegrityIssue::MissingStory { epic_id: *epic_id, story_id: *story_id });
+                }
+            }
+        }
+
+        let mut story_ids: Vec<&u32> = self.stories.keys().collect();
+        story_ids.sort();
+        for story_id in story_ids {
+            match links.remove(story_id) {
+                None => issues.push(IntegrityIssue::OrphanStory { story_id: *story_id }),
+                Some(epic_ids) if epic_ids.len() > 1 => issues.push(IntegrityIssue::DuplicateStoryLink { story_id: *story_id, epic_ids }),
+                Some(_) => {}
+            }
+        }
+
+        let max_id = self.epics.keys().chain(self.stories.keys()).copied().max().unwrap_or(0);
+        if self.last_item_id < max_id {
+            issues.push(IntegrityIssue::LastItemIdTooLow { last_item_id: self.last_item_id, max_id });
+        }
+        if self.label_index != self.built_label_index() {
+            issues.push(IntegrityIssue::StaleLabelIndex);
+        }
+        issues
+    }
+
+    // Fixes everything validate() reports and returns what was fixed: missing stories are unlinked,
+    // duplicate links keep the first listing, last_item_id is raised and orphan stories move into a new epic
+    pub fn repair(&mut self) -> Vec<IntegrityIssue> {
+        let issues = self.validate();
+        let mut orphans = vec![];
+
+        for issue in &issues {
+            match issue {
+                IntegrityIssue::MissingStory { epic_id, story_id } => {
+                    if let Some(epic) = self.epics.get_mut(epic_id) {
+                        epic.stories.retain(|id| id != story_id);
+                    }
+                }
+                IntegrityIssue::DuplicateStoryLink { story_id, epic_ids } => {
+                    let keep_epic_id = epic_ids[0];
+                    for epic_id in epic_ids.iter().collect::<std::collections::HashSet<_>>() {
+                        if let Some(epic) = self.epics.get_mut(epic_id) {
+                            let mut seen = *epic_id != keep_epic_id; //in every other epic even the first listing goes
+                            epic.stories.retain(|id| id != story_id || !std::mem::replace(&mut seen, true));
+                        }
+                    }
+                }
+                IntegrityIssue::OrphanStory { story_id } => orphans.push(*story_id),
+                IntegrityIssue::LastItemIdTooLow { max_id, .. } => self.last_item_id = *max_id,
+                IntegrityIssue::StaleLabelIndex => self.label_index = self.built_label_index(),
+            }
+        }
+
+        if !orphans.is_empty() {
+            let mut epic = Epic::new(RECOVERED_EPIC_NAME.to_owned(), "Stories that did not belong to any epic".to_owned());
+            epic.stories = orphans;
+            self.last_item_id += 1;
+            self.epics.insert(self.last_item_id, epic);
+        }
+        issues
+    }
+
+    // The label index as it should be, from a full scan of the items
+    fn built_label_index(&self) -> BTreeMap<String, BTreeSet<u32>> {
+        let mut label_index: BTreeMap<String, BTreeSet<u32>> = BTreeMap::new();
+        let epic_labels = self.epics.iter().map(|(id, epic)| (id, &epic.labels));
+        let story_labels = self.stories.iter().map(|(id, story)| (id, &story.labels));
+        for (id, labels) in epic_labels.chain(story_labels) {
+            for label in labels {
+                label_index.entry(label.clone()).or_default().insert(*id);
+            }
+        }
+        label_index
+    }
+
+    // Brings the index up to date for one item after its labels changed or it was added or removed
+    pub fn reindex_labels(&mut self, item_id: u32) {
+        for ids in self.label_index.values_mut() {
+            ids.remove(&item_id);
+        }
+        let labels = self.labels_of(item_id).cloned().unwrap_or_default();
+        for label in labels {
+            self.label_index.entry(label).or_default().insert(item_id);
+        }
+        self.label_index.retain(|_, ids| !ids.is_empty());
+    }
+
+    // The labels on an epic or story, None if there is no item with that id
+    pub fn labels_of(&self, item_id: u32) -> Option<&BTreeSet<String>> {
+        self.epics.get(&item_id).map(|epic| &epic.labels).or_else(|| self.stories.get(&item_id).map(|story| &story.labels))
+    }
+
+    // Ids of the epics and stories carrying the label, straight from the index
+    pub fn labelled(&self, label: &str) -> BTreeSet<u32> {
+        self.label_index.get(label).cloned().unwrap_or_default()
+    }
+
+    // Story points of the epic's stories summed per status, in Status::ALL order; unestimated and missing stories count as 0
+    pub fn points_by_status(&self, epic_id: u32) -> Vec<(Status, u32)> {
+        let stories = self.epic_stories(epic_id);
+        Status::ALL.iter().map(|status| {
+            (status.clone(), stories.iter().filter(|(_, story)| &story.status == status).filter_map(|(_, story)| story.points).sum())
+        }).collect()
+    }
+
+    // The epic's stories in its order, leaving out dangling ids and stories in the trash
+    pub fn epic_stories(&self, epic_id: u32) -> Vec<(u32, &Story)> {
+        let Some(epic) = self.epics.get(&epic_id) else {
+            return vec![];
+        };
+        epic.stories.iter()
+            .filter_map(|id| self.stories.get(id).map(|story| (*id, story)))
+            .filter(|(_, story)| !story.archived)
+            .collect()
+    }
+
+    // What the trash page lists: archived epics, and archived stories whose epic is still live (the rest go with their epic), both by id
+    pub fn trash(&self) -> (Vec<u32>, Vec<u32>) {
+        let epic_ids = self.epics.iter().filter(|(_, epic)| epic.archived).map(|(id, _)| *id).sorted().collect();
+        let story_ids = self.epics.values().filter(|epic| !epic.archived)
+            .flat_map(|epic| epic.stories.iter())
+            .filter(|id| self.stories.get(id).is_some_and(|story| story.archived))
+            .copied().sorted().dedup().collect();
+        (epic_ids, story_ids)
+    }
+
+    // The epic that lists the story, the first one if a broken file links it twice
+    pub fn epic_of(&self, story_id: u32) -> Option<u32> {
+        self.epics.iter().filter(|(_, epic)| epic.stories.contains(&story_id)).map(|(id, _)| *id).min()
+    }
+
+    // Every epic, story, comment and saved filter that differs between 'before' and self
+    // (the history log is append-only and not part of it)
+    pub fn changes_since(&self, before: &DBState) -> ChangeSet {
+        let mut change_set = ChangeSet::default();
+        for id in before.epics.keys().chain(self.epics.keys()) {
+            let (old, new) = (before.epics.get(id), self.epics.get(id));
+            if old != new {
+                change_set.before.epics.insert(*id, old.cloned());
+                change_set.after.epics.insert(*id, new.cloned());
+            }
+        }
+        for id in before.stories.keys().chain(self.stories.keys()) {
+            let (old, new) = (before.stories.get(id), self.stories.get(id));
+            if old != new {
+                change_set.before.stories.insert(*id, old.cloned());
+                change_set.after.stories.insert(*id, new.cloned());
+            }
+        }
+        if self.comments != before.comments {
+            change_set.before.comments = Some(before.comments.clone());
+            change_set.after.comments = Some(self.comments.clone());
+        }
+        for name in before.saved_filters.keys().chain(self.saved_filters.keys()) {
+            let (old, new) = (before.saved_filters.get(name), self.saved_filters.get(name));
+            if old != new {
+                change_set.before.saved_filters.insert(name.clone(), old.cloned());
+                change_set.after.saved_filters.insert(name.clone(), new.cloned());
+            }
+        }
+        change_set
+    }
+
+    // Adds every epic and story of 'other' under fresh ids taken from last_item_id, keeping their links.
+    // Returns the id mapping (id in 'other' -> id in self).
+    pub fn merge(&mut self, other: DBState) -> HashMap<u32, u32> {
+        let mut other_ids: Vec<u32> = other.epics.keys().chain(other.stories.keys()).copied().collect();
+        other_ids.sort();
+        let mut new_ids = HashMap::new();
+        for id in other_ids {
+            self.last_item_id += 1;
+            new_ids.insert(id, self.last_item_id);
+        }
+
+        for (id, mut epic) in other.epics {
+            epic.stories = epic.stories.iter().filter_map(|story_id| new_ids.get(story_id).copied()).collect();
+            self.epics.insert(new_ids[&id], epic);
+        }
+        for (id, story) in other.stories {
+            self.stories.insert(new_ids[&id], story);
+        }
+        for mut entry in other.history {
+            if let Some(new_id) = new_ids.get(&entry.item_id) {
+                entry.item_id = *new_id;
+                self.history.push(entry);
+            }
+        }
+        for mut comment in other.comments {
+            if let Some(new_id) = new_ids.get(&comment.item_id) {
+                comment.item_id = *new_id;
+                self.comments.push(comment);
+            }
+        }
+        for new_id in new_ids.values() {
+            self.reindex_labels(*new_id);
+        }
+        new_ids
+    }
+
+    // The thread of one epic or story, oldest first
+    pub fn comments_on(&self, item_id: u32) -> Vec<&Comment> {
+        self.comments.iter().filter(|comment| comment.item_id == item_id).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn priority_should_sort_by_urgency_and_parse() {
+        assert!(Priority::Highest > Priority::Medium && Priority::Medium > Priority::Lowest);
+        assert_eq!("high".parse::<Priority>(), Ok(Priority::High));
+        assert!("urgent".parse::<Priority>().is_err());
+        assert_eq!(Priority::default(), Priority::Medium);
+    }
+
+    #[test]
+    fn changes_should_list_differing_fields() {
+        let old = Story::new("name".to_owned(), "".to_owned());
+        let mut new = old.clone();
+        new.status = Status::Closed;
+        new.points = Some(3);
+        new.updated_at = Some(Utc::now()); //bookkeeping, not a change
+
+        assert_eq!(old.changes(&new), vec![("status", "OPEN".to_owned(), "CLOSED".to_owned()), ("points", "".to_owned(), "3".to_owned())]);
+        assert!(old.changes(&old).is_empty());
+
+        let mut epic = Epic::new("".to_owned(), "".to_owned());
+        let mut renamed = epic.clone();
+        renamed.name = "new".to_owned();
+        renamed.stories.push(1);
+        assert_eq!(epic.changes(&renamed), vec![("name", "".to_owned(), "new".to_owned())]);
+        epic.owner = Some("alice".to_owned());
+        assert_eq!(epic.changes(&renamed)[1], ("owner", "alice".to_owned(), "".to_owned()));
+    }
+
+    #[test]
+    fn changes_since_should_capture_touched_items_only() {
+        let before = state_with(vec![(1, vec![2]), (3, vec![])], vec![2], 3);
+        let mut after = before.clone();
+        after.epics.remove(&1);
+        after.stories.remove(&2);
+        after.epics.insert(4, Epic::new("new".to_owned(), "".to_owned()));
+
+        let change_set = after.changes_since(&before);
+        assert_eq!(change_set.before.epics, HashMap::from([(1, Some(before.epics[&1].clone())), (4, None)]));
+        assert_eq!(change_set.after.epics, HashMap::from([(1, None), (4, Some(after.epics[&4].clone()))]));
+        assert_eq!(change_set.before.stories, HashMap::from([(2, Some(before.stories[&2].clone()))]));
+        assert!(before.changes_since(&before).is_empty());
+
+        let mut after = before.clone();
+        after.comments.push(Comment { item_id: 3, at: Utc::now(), author: "alice".to_owned(), body: "hi".to_owned() });
+        after.saved_filters.insert("open".to_owned(), "status = open".to_owned());
+        let change_set = after.changes_since(&before);
+        assert!(change_set.before.epics.is_empty() && change_set.before.stories.is_empty());
+        assert_eq!((change_set.before.comments, change_set.after.comments), (Some(vec![]), Some(after.comments.clone())));
+        assert_eq!(change_set.before.saved_filters, BTreeMap::from([("open".to_owned(), None)]));
+    }
+
+    #[test]
+    fn points_by_status_should_sum_estimated_stories() {
+        let mut db_state = state_with(vec![(1, vec![2, 3, 4, 999])], vec![2, 3, 4], 4);
+        db_state.stories.get_mut(&2).unwrap().points = Some(3);
+        db_state.stories.get_mut(&3).unwrap().points = Some(5);
+        db_state.stories.get_mut(&3).unwrap().status = Status::Closed;
+        db_state.stories.get_mut(&4).unwrap().points = Some(8);
+        db_state.stories.get_mut(&4).unwrap().archived = true; //trashed stories don't count
+
+        assert_eq!(db_state.points_by_status(1), vec![(Status::Open, 3), (Status::InProgress, 0), (Status::Resolved, 0), (Status::Closed, 5)]);
+        assert_eq!(db_state.points_by_status(999), vec![(Status::Open, 0), (Status::InProgress, 0), (Status::Resolved, 0), (Status::Closed, 0)]);
+    }
+
+    #[test]
+    fn trash_should_list_archived_epics_and_stories_of_live_epics() {
+        let mut db_state = state_with(vec![(1, vec![2, 3]), (4, vec![5])], vec![2, 3, 5], 5);
+        db_state.stories.get_mut(&3).unwrap().archived = true;
+        db_state.epics.get_mut(&4).unwrap().archived = true;
+        db_state.stories.get_mut(&5).unwrap().archived = true;
+
+        assert_eq!(db_state.trash(), (vec![4], vec![3]));
+        assert_eq!(db_state.epic_stories(1).iter().map(|(id, _)| *id).collect::<Vec<_>>(), vec![2]);
+        assert_eq!(db_state.epic_of(5), Some(4));
+        assert_eq!(db_state.epic_of(999), None);
+    }
+
+    fn state_with(epics: Vec<(u32, Vec<u32>)>, story_ids: Vec<u32>, last_item_id: u32) -> DBState {
+        let mut db_state = DBState { last_item_id, ..Default::default() };
+        for (epic_id, stories) in epics {
+            let mut epic = Epic::new("".to_owned(), "".to_owned());
+            epic.stories = stories;
+            db_state.epics.insert(epic_id, epic);
+        }
+        for story_id in story_ids {
+            db_state.stories.insert(story_id, Story::new("".to_owned(), "".to_owned()));
+        }
+        db_state
+    }
+
+    #[test]
+    fn status_should_parse_display_and_relaxed_forms() {
+        assert_eq!("IN PROGRESS".parse::<Status>(), Ok(Status::InProgress));
+        assert_eq!("in-progress".parse::<Status>(), Ok(Status::InProgress));
+        assert_eq!("InProgress".parse::<Status>(), Ok(Status::InProgress));
+        assert_eq!("closed".parse::<Status>(), Ok(Status::Closed));
+        for status in [Status::Open, Status::InProgress, Status::Resolved, Status::Closed] {
+            assert_eq!(status.to_string().parse::<Status>(), Ok(status));
+        }
+        assert!("done".parse::<Status>().is_err());
+    }
+
+    #[test]
+    fn merge_should_renumber_ids_and_keep_links() {
+        let mut db_state = state_with(vec![(1, vec![2])], vec![2], 2);
+        let other = state_with(vec![(1, vec![3, 2]), (4, vec![])], vec![2, 3], 4);
+
+        let new_ids = db_state.merge(other);
+
+        assert_eq!(new_ids, HashMap::from([(1, 3), (2, 4), (3, 5), (4, 6)]));
+        assert_eq!(db_state.last_item_id, 6);
+        assert_eq!(db_state.epics[&1].stories, vec![2]);
+        assert_eq!(db_state.epics[&3].stories, vec![5, 4]);
+        assert!(db_state.epics[&6].stories.is_empty());
+        assert_eq!(db_state.validate(), vec![]);
+    }
+
+    #[test]
+    fn reindex_labels_should_follow_the_items() {
+        let mut db_state = state_with(vec![(1, vec![2])], vec![2], 2);
+        db_state.epics.get_mut(&1).unwrap().labels.insert("backend".to_owned());
+        db_state.stories.get_mut(&2).unwrap().labels.insert("backend".to_owned());
+        assert_eq!(db_state.validate(), vec![IntegrityIssue::StaleLabelIndex]);
+
+        db_state.reindex_labels(1);
+        db_state.reindex_labels(2);
+        assert_eq!(db_state.labelled("backend"), BTreeSet::from([1, 2]));
+        assert!(db_state.labelled("ui").is_empty());
+
+        db_state.stories.remove(&2);
+        db_state.epics.get_mut(&1).unwrap().stories.clear();
+        db_state.reindex_labels(2);
+        assert_eq!(db_state.labelled("backend"), BTreeSet::from([1]));
+        assert_eq!(db_state.validate(), vec![]);
+
+        db_state.label_index.insert("ui".to_owned(), BTreeSet::from([1]));
+        assert_eq!(db_state.repair(), vec![IntegrityIssue::StaleLabelIndex]);
+        assert_eq!(db_state.label_index, BTreeMap::from([("backend".to_owned(), BTreeSet::from([1]))]));
+    }
+
+    #[test]
+    fn validate_should_accept_consistent_state() {
+        let db_state = state_with(vec![(1, vec![2, 3]), (4, vec![])], vec![2, 3], 4);
+        assert_eq!(db_state.validate(), vec![]);
+    }
+
+    #[test]
+    fn validate_should_report_every_issue() {
+        let db_state = state_with(vec![(1, vec![2, 9, 3]), (4, vec![3])], vec![2, 3, 5], 4);
+
+        assert_eq!(db_state.validate(), vec![
+            IntegrityIssue::MissingStory { epic_id: 1, story_id: 9 },
+            IntegrityIssue::DuplicateStoryLink { story_id: 3, epic_ids: vec![1, 4] },
+            IntegrityIssue::OrphanStory { story_id: 5 },
+            IntegrityIssue::LastItemIdTooLow { last_item_id: 4, max_id: 5 },
+        ]);
+    }
+
+    #[test]
+    fn repair_should_leave_a_consistent_state() {
+        let mut db_state = state_with(vec![(1, vec![2, 9, 3, 2]), (4, vec![3])], vec![2, 3, 5], 4);
+
+        let fixed = db_state.repair();
+
+        assert_eq!(fixed.len(), 5);
+        assert_eq!(db_state.validate(), vec![]);
+        assert_eq!(db_state.epics[&1].stories, vec![2, 3]);
+        assert!(db_state.epics[&4].stories.is_empty());
+        assert_eq!(db_state.last_item_id, 6);
+        assert_eq!(db_state.epics[&6].name, RECOVERED_EPIC_NAME);
+        assert_eq!(db_state.epics[&6].stories, vec![5]);
+        assert_eq!(db_state.stories.len(), 3); //repair never drops a story
+    }
+}
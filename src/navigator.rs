@@ -5,27 +5,70 @@ use anyhow::{anyhow, Result, Context, Ok};
 //Error associated function 'downcast' Attempt to downcast the error object to a concrete type, from a dynamic trait object to a concrete type of a known size
 use std::rc::Rc;
 
-use crate::{ui::{Page, HomePage, EpicDetail, StoryDetail, Prompts}, db::JiraDatabase, models::Action};
+use crate::{ui::{Page, HomePage, EpicDetail, StoryDetail, MyStories, HistoryPage, TrashPage, SearchPage, QueryPage, SavedFiltersPage, Prompts}, db::JiraDatabase, models::{normalize_label, Action, ChangeSet}, query::Query};
 
 pub struct Navigator {
     pages: Vec<Box<dyn Page>>, //Page is a trait object, so it is wrapped in a Box pointer to provie a known size (the pointer) when the object will be dynamically dispatched at runtime, dyn is a prefix of a trait object's type
     prompts: Prompts,
-    db: Rc<JiraDatabase>
+    db: Rc<JiraDatabase>,
+    current_user: Option<String>, //who "my stories" is for and who reports new stories, see Cli::current_user
+    undo_stack: Vec<ChangeSet>, //one entry per action that changed the board this session, newest last
+    redo_stack: Vec<ChangeSet>, //entries taken off the undo stack, emptied by any new change
 }
 
 impl Navigator {
-    pub fn new(db: Rc<JiraDatabase>) -> Self {
-        Self {pages: vec![Box::new(HomePage {db: Rc::clone(&db)})], prompts: Prompts::new(), db}
+    pub fn new(db: Rc<JiraDatabase>, current_user: Option<String>) -> Self {
+        Self {pages: vec![Box::new(HomePage {db: Rc::clone(&db), label: None})], prompts: Prompts::new(), db, current_user, undo_stack: vec![], redo_stack: vec![]}
     }
 
-    pub fn get_current_page(&self) -> Option<&Box<dyn Page>> { //Function will always return the last element of the vector
-        self.pages.last()
+    pub fn get_current_page(&self) -> Option<&dyn Page> { //Function will always return the last element of the vector
+        self.pages.last().map(|page| page.as_ref())
+    }
+
+    // Undo and redo work on every page, anything else is up to the current page
+    pub fn handle_input(&self, input: &str) -> Result<Option<Action>> {
+        match input {
+            "z" => Ok(Some(Action::Undo)),
+            "y" => Ok(Some(Action::Redo)),
+            input => match self.get_current_page() {
+                Some(page) => page.handle_input(input),
+                None => Ok(None),
+            },
+        }
     }
 
     pub fn handle_action(&mut self, action: Action) -> Result<()> {
+        match action {
+            Action::Undo => {//put every item the last change touched back the way it was
+                let change_set = self.undo_stack.pop().ok_or_else(|| anyhow!("nothing to undo!"))?;
+                self.db.restore_items(&change_set.before).with_context(|| anyhow!("failed to undo!"))?;
+                self.redo_stack.push(change_set);
+            }
+            Action::Redo => {
+                let change_set = self.redo_stack.pop().ok_or_else(|| anyhow!("nothing to redo!"))?;
+                self.db.restore_items(&change_set.after).with_context(|| anyhow!("failed to redo!"))?;
+                self.undo_stack.push(change_set);
+            }
+            Action::NavigateToEpicDetail {..} | Action::NavigateToStoryDetail {..} | Action::NavigateToPreviousPage | Action::NavigateToMyStories
+            | Action::NavigateToHistory {..} | Action::NavigateToTrash | Action::Search | Action::QueryStories | Action::NavigateToSavedFilters
+            | Action::OpenSavedFilter {..} | Action::FilterByLabel {..} | Action::Exit => self.perform(action)?, //only moves between pages, nothing to undo
+            action => {//diff the board around the action, so undo doesn't need an inverse for every kind of change
+                let before = self.db.read_db()?;
+                self.perform(action)?;
+                let change_set = self.db.read_db()?.changes_since(&before);
+                if !change_set.is_empty() { //a cancelled prompt changes nothing
+                    self.undo_stack.push(change_set);
+                    self.redo_stack.clear();
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn perform(&mut self, action: Action) -> Result<()> {
         match action {
             Action::NavigateToEpicDetail {epic_id} => { //create a new EpicDetail instance and add it to the pages vector
-                self.pages.push(Box::new(EpicDetail {epic_id, db: Rc::clone(&self.db)})); //push a new Epic onto the pages Vec, which is a collection of Box pointers; Clone Reference Counter added to the Counter
+                self.pages.push(Box::new(EpicDetail {epic_id, db: Rc::clone(&self.db), label: None})); //push a new Epic onto the pages Vec, which is a collection of Box pointers; Clone Reference Counter added to the Counter
             }
             Action::NavigateToStoryDetail {epic_id, story_id} => {//create a new StoryDetail instance and add it to the pages vector
                 self.pages.push(Box::new(StoryDetail {epic_id, story_id, db: Rc::clone(&self.db)}));
@@ -33,16 +76,87 @@ impl Navigator {
             Action::NavigateToPreviousPage => { //remove the last page from the pages vector
                 if !self.pages.is_empty() {self.pages.pop();} //is_empty() is an associative function of the Slice primitive,dynamically-sized view into a contiguous sequence. Returns Bool true
             }
+            Action::NavigateToMyStories => {
+                let user = self.current_user.clone().ok_or_else(|| anyhow!("no current user, start with --user or set JIRA_USER"))?;
+                self.pages.push(Box::new(MyStories {user, db: Rc::clone(&self.db)}));
+            }
+            Action::NavigateToHistory {item_id} => {
+                self.pages.push(Box::new(HistoryPage {item_id, db: Rc::clone(&self.db)}));
+            }
+            Action::NavigateToTrash => {
+                self.pages.push(Box::new(TrashPage {db: Rc::clone(&self.db)}));
+            }
+            Action::Search => {//prompt the user for a query and show the ranked results
+                if let Some(query) = (self.prompts.search)() {
+                    self.pages.push(Box::new(SearchPage {query, db: Rc::clone(&self.db)}));
+                }
+            }
+            Action::QueryStories => {//prompt the user for a filter and list the stories matching it
+                if let Some(source) = (self.prompts.query_stories)() {
+                    let query = Query::parse(&source).map_err(|error| anyhow!("invalid filter, {}", error))?;
+                    self.pages.push(Box::new(QueryPage {name: None, source, query, db: Rc::clone(&self.db)}));
+                }
+            }
+            Action::NavigateToSavedFilters => {
+                self.pages.push(Box::new(SavedFiltersPage {db: Rc::clone(&self.db)}));
+            }
+            Action::OpenSavedFilter {name} => {
+                let source = self.db.read_db()?.saved_filters.remove(&name).ok_or_else(|| anyhow!("could not find saved filter \"{}\"!", name))?;
+                let query = Query::parse(&source).map_err(|error| anyhow!("saved filter \"{}\" is invalid, {}", name, error))?;
+                self.pages.push(Box::new(QueryPage {name: Some(name), source, query, db: Rc::clone(&self.db)}));
+            }
+            Action::SaveFilter {query} => {//prompt the user for a name and keep the filter for later
+                if let Some(name) = (self.prompts.save_filter)() {
+                    self.db.save_filter(&name, &query).with_context(|| anyhow!("failed to save filter!"))?;
+                }
+            }
+            Action::DeleteSavedFilter => {
+                if let Some(name) = (self.prompts.delete_saved_filter)() {
+                    self.db.delete_filter(&name).with_context(|| anyhow!("failed to delete saved filter!"))?;
+                }
+            }
+            Action::FilterByLabel {epic_id} => {//prompt the user for a label and reopen the current listing filtered by it
+                let known_labels: Vec<String> = self.db.read_db()?.label_index.into_keys().collect();
+                let label = (self.prompts.label)("Filter by label (leave blank to show everything)", &known_labels);
+                let label = label.map(|label| normalize_label(&label)).transpose().map_err(|error| anyhow!(error))?;
+                let page: Box<dyn Page> = match epic_id {
+                    Some(epic_id) => Box::new(EpicDetail {epic_id, db: Rc::clone(&self.db), label}),
+                    None => Box::new(HomePage {db: Rc::clone(&self.db), label}),
+                };
+                self.pages.pop(); //replace the unfiltered page, so previous still goes back where it did
+                self.pages.push(page);
+            }
+            Action::RestoreItem {item_id} => {
+                self.db.restore_item(item_id).with_context(|| anyhow!("failed to restore item!"))?;
+            }
+            Action::PurgeItem => {//prompt the user for an item in the trash and remove it for good
+                if let Some(item_id) = (self.prompts.purge_item)() {
+                    self.db.purge_item(item_id).with_context(|| anyhow!("failed to purge item!"))?;
+                }
+            }
             Action::CreateEpic => {//prompt the user to create a new epic and presist it in the database
                 let epic = (self.prompts.create_epic)(); //(self.prompts.create_epic)=Closure assigned to 'epic' variable; You need to use parentheses if the closure is a field. Fields and methods can have the same name, so you use parens to differentiate the two. You want a call to the prompts field in Navigator; Not JiraDatabase.create_epic() method
                 self.db.create_epic(epic).with_context(|| anyhow!("failed to create Epic!"))?;//if self.db.create_epic(epic) returns an Error, you can add additional context with with_context function, returns Result type
             }
             Action::UpdateEpicStatus {epic_id} => {//prompt the user to update status and persis it in the database
-                let status = (self.prompts.update_status)();
+                let status = (self.prompts.update_status)(self.db.workflow.statuses()); //epics aren't bound to the transitions, only to the statuses
                 if let Some(status) = status {
                     self.db.update_epic_status(epic_id, status).with_context( || anyhow!("failed to delete Epic!"))?;//self.db.update_epic_status = cloned JiraDatabse object with Database trait to read or write to DBState
                 }
             }
+            Action::EditEpic {epic_id} => {//prompt the user for a new name and description and persist them in the database
+                let db_state = self.db.read_db()?;
+                let epic = db_state.epics.get(&epic_id).ok_or_else(|| anyhow!("could not find epic!"))?;
+                let (name, description) = (self.prompts.edit_epic)(&epic.name, &epic.description);
+                self.db.update_epic(epic_id, name, description).with_context(|| anyhow!("failed to edit Epic!"))?;
+            }
+            Action::SetEpicOwner {epic_id} => {//prompt the user for the epic's owner and persist it in the database
+                let db_state = self.db.read_db()?;
+                let epic = db_state.epics.get(&epic_id).ok_or_else(|| anyhow!("could not find epic!"))?;
+                if let Some(owner) = (self.prompts.assign)("Owner", epic.owner.as_deref()) {
+                    self.db.set_epic_owner(epic_id, owner).with_context(|| anyhow!("failed to set epic owner!"))?;
+                }
+            }
             Action::DeleteEpic {epic_id} => {//prompt the user to to delete the epic and persist it in the database
                 if (self.prompts.delete_epic) () {
                     self.db.delete_epic(epic_id).with_context( || anyhow!("failed to delete Epic!"))?;
@@ -52,15 +166,66 @@ impl Navigator {
                 }
             }
             Action::CreateStory {epic_id} => {//prompt the user to create a new story and persist it in the database
-                let story = (self.prompts.create_story)();
+                let mut story = (self.prompts.create_story)();
+                if story.reporter.is_none() {
+                    story.reporter = self.current_user.clone();
+                }
                 self.db.create_story(story, epic_id).with_context(|| anyhow!("failed to create story!"))?;
             }
             Action::UpdateStoryStatus {story_id} => {//prompt the user to update status and persist it in the database
-                let status = (self.prompts.update_status)();
+                let db_state = self.db.read_db()?;
+                let story = db_state.stories.get(&story_id).ok_or_else(|| anyhow!("could not find story!"))?;
+                let next_statuses = self.db.workflow.next_statuses(&story.status);
+                if next_statuses.is_empty() {
+                    return Err(anyhow!("{} is a final status in the workflow!", story.status));
+                }
+                let status = (self.prompts.update_status)(&next_statuses);
                 if let Some(status) = status {
                     self.db.update_story_status(story_id, status).with_context(|| anyhow!("failed to update story!"))?;
                 }
             }
+            Action::EditStory {story_id} => {//prompt the user for a new name and description and persist them in the database
+                let db_state = self.db.read_db()?;
+                let story = db_state.stories.get(&story_id).ok_or_else(|| anyhow!("could not find story!"))?;
+                let (name, description) = (self.prompts.edit_story)(&story.name, &story.description);
+                self.db.update_story(story_id, name, description).with_context(|| anyhow!("failed to edit story!"))?;
+            }
+            Action::AssignStory {story_id} => {//prompt the user for the assignee and persist it in the database
+                let db_state = self.db.read_db()?;
+                let story = db_state.stories.get(&story_id).ok_or_else(|| anyhow!("could not find story!"))?;
+                if let Some(assignee) = (self.prompts.assign)("Assignee", story.assignee.as_deref()) {
+                    self.db.assign_story(story_id, assignee).with_context(|| anyhow!("failed to assign story!"))?;
+                }
+            }
+            Action::EstimateStory {story_id} => {//prompt the user for priority and story points and persist them in the database
+                let db_state = self.db.read_db()?;
+                let story = db_state.stories.get(&story_id).ok_or_else(|| anyhow!("could not find story!"))?;
+                let (priority, points) = (self.prompts.estimate_story)(story.priority, story.points);
+                self.db.estimate_story(story_id, priority, points).with_context(|| anyhow!("failed to estimate story!"))?;
+            }
+            Action::AddComment {item_id} => {//prompt the user for a comment and add it to the item's thread
+                if let Some(body) = (self.prompts.add_comment)() {
+                    self.db.add_comment(item_id, &body).with_context(|| anyhow!("failed to add comment!"))?;
+                }
+            }
+            Action::AddLabel {item_id} => {//prompt the user for a label and tag the item with it
+                let db_state = self.db.read_db()?;
+                let labels = db_state.labels_of(item_id).ok_or_else(|| anyhow!("could not find epic or story!"))?;
+                let suggestions: Vec<String> = db_state.label_index.keys().filter(|label| !labels.contains(*label)).cloned().collect();
+                if let Some(label) = (self.prompts.label)("Add label (leave blank to cancel)", &suggestions) {
+                    self.db.add_label(item_id, &label).with_context(|| anyhow!("failed to add label!"))?;
+                }
+            }
+            Action::RemoveLabel {item_id} => {//prompt the user for one of the item's labels and take it off
+                let db_state = self.db.read_db()?;
+                let labels: Vec<String> = db_state.labels_of(item_id).ok_or_else(|| anyhow!("could not find epic or story!"))?.iter().cloned().collect();
+                if labels.is_empty() {
+                    return Err(anyhow!("item {} has no labels to remove!", item_id));
+                }
+                if let Some(label) = (self.prompts.label)("Remove label (leave blank to cancel)", &labels) {
+                    self.db.remove_label(item_id, &label).with_context(|| anyhow!("failed to remove label!"))?;
+                }
+            }
             Action::DeleteStory {epic_id, story_id} => {//prompt the user to delete the story and persist it in the database
                 if (self.prompts.delete_story)() {
                     self.db.delete_story(epic_id, story_id).with_context(|| anyhow!("failed to delete story!"))?;
@@ -69,17 +234,28 @@ impl Navigator {
                     }
                 }
             }
+            Action::MoveStory {epic_id, story_id} => {//prompt the user for the target epic and persist the move in the database
+                if let Some(to_epic_id) = (self.prompts.move_story)() {
+                    self.db.move_story(story_id, epic_id, to_epic_id).with_context(|| anyhow!("failed to move story!"))?;
+                    if !self.pages.is_empty() {
+                        self.pages.pop(); //the story page still points at the old epic, go back to it
+                    }
+                }
+            }
             Action::Exit => {//remove all pages from the page vector
                 self.pages.clear();
             },
+            Action::Undo | Action::Redo => unreachable!("handled in handle_action"),
         }
         Ok(()) //since handle_action function returns a Result type, the above handles actions and errors, and you need to account for just returning anyting else with Ok() with the Unit () type inside
     }
     //Private functions used for testing
+    #[cfg(test)]
     fn get_page_count(&self) -> usize {
         self.pages.len()
     }
 
+    #[cfg(test)]
     fn set_prompts(&mut self, prompts: Prompts) {
         self.prompts = prompts;
     }
@@ -89,13 +265,15 @@ impl Navigator {
 //enables conditional compilation 
 //annotation on the tests module tells Rust to compile and run the test code only when you run cargo test, not when you run cargo build
 mod tests {
-    use crate::{db::test_utils::MockDB, models::{Epic, Status, Story}};
+    use std::collections::BTreeSet;
+
+    use crate::{db::test_utils::MockDB, models::{Epic, Priority, Status, Story}, workflow::Workflow};
     use super::*; //`super` keyword refers to the parent scope (outside the `tests` module)
 
     #[test]
     fn should_start_on_home_page() {
-        let db = Rc::new(JiraDatabase {database: Box::new(MockDB::new())});
-        let nav = Navigator::new(db);
+        let db = Rc::new(JiraDatabase {database: Box::new(MockDB::new()), user: None, workflow: Workflow::default()});
+        let nav = Navigator::new(db, None);
 
         assert_eq!(nav.get_page_count(), 1);
 
@@ -106,8 +284,8 @@ mod tests {
     }
     #[test]
     fn handle_action_should_navigate_pages() {
-        let db = Rc::new(JiraDatabase {database: Box::new(MockDB::new())});
-        let mut nav = Navigator::new(db);
+        let db = Rc::new(JiraDatabase {database: Box::new(MockDB::new()), user: None, workflow: Workflow::default()});
+        let mut nav = Navigator::new(db, None);
 
         nav.handle_action(Action::NavigateToEpicDetail { epic_id: 1}).unwrap();
         assert_eq!(nav.get_page_count(), 2);
@@ -147,8 +325,8 @@ mod tests {
 
     #[test]
     fn handle_action_should_clear_pages_on_exit() {
-        let db = Rc::new(JiraDatabase {database: Box::new(MockDB::new())});
-        let mut nav = Navigator::new(db);
+        let db = Rc::new(JiraDatabase {database: Box::new(MockDB::new()), user: None, workflow: Workflow::default()});
+        let mut nav = Navigator::new(db, None);
         nav.handle_action(Action::NavigateToEpicDetail {epic_id: 1}).unwrap();
         nav.handle_action(Action::NavigateToStoryDetail {epic_id: 1, story_id: 2}).unwrap();
         nav.handle_action(Action::Exit).unwrap();
@@ -157,8 +335,8 @@ mod tests {
 
     #[test]
     fn handle_action_should_handle_create_epic() {
-        let db = Rc::new(JiraDatabase {database: Box::new(MockDB::new())});
-        let mut nav = Navigator::new(Rc::clone(&db));
+        let db = Rc::new(JiraDatabase {database: Box::new(MockDB::new()), user: None, workflow: Workflow::default()});
+        let mut nav = Navigator::new(Rc::clone(&db), None);
         let mut prompts = Prompts::new();
 
         prompts.create_epic = Box::new(|| Epic::new("name".to_owned(), "description".to_owned()));
@@ -175,12 +353,15 @@ mod tests {
 
     #[test]
     fn handle_action_should_handle_update_epic() {
-        let db = Rc::new(JiraDatabase {database: Box::new(MockDB::new())});
+        let db = Rc::new(JiraDatabase {database: Box::new(MockDB::new()), user: None, workflow: Workflow::default()});
         let epic_id = db.create_epic(Epic::new("".to_owned(), "".to_owned())).unwrap();
-        let mut nav = Navigator::new(Rc::clone(&db));
+        let mut nav = Navigator::new(Rc::clone(&db), None);
         let mut prompts = Prompts::new();
 
-        prompts.update_status = Box::new(|| Some(Status::InProgress));
+        prompts.update_status = Box::new(|statuses| {
+            assert_eq!(statuses, &Status::ALL);
+            Some(Status::InProgress)
+        });
         nav.set_prompts(prompts);
         nav.handle_action(Action::UpdateEpicStatus {epic_id}).unwrap();
 
@@ -188,25 +369,44 @@ mod tests {
         assert_eq!(db_state.epics.get(&epic_id).unwrap().status, Status::InProgress);
     }
 
+    #[test]
+    fn handle_action_should_handle_edit_epic() {
+        let db = Rc::new(JiraDatabase {database: Box::new(MockDB::new()), user: None, workflow: Workflow::default()});
+        let epic_id = db.create_epic(Epic::new("nmae".to_owned(), "description".to_owned())).unwrap();
+        let mut nav = Navigator::new(Rc::clone(&db), None);
+        let mut prompts = Prompts::new();
+
+        prompts.edit_epic = Box::new(|name, description| {
+            assert_eq!(name, "nmae"); //the prompt gets the current values to show them
+            (name.replace("nmae", "name"), description.to_owned())
+        });
+        nav.set_prompts(prompts);
+        nav.handle_action(Action::EditEpic {epic_id}).unwrap();
+
+        let db_state = db.read_db().unwrap();
+        assert_eq!(db_state.epics.get(&epic_id).unwrap().name, "name".to_owned());
+        assert_eq!(db_state.epics.get(&epic_id).unwrap().description, "description".to_owned());
+    }
+
     #[test]
     fn handle_action_should_handle_delete_epic() {
-        let db = Rc::new(JiraDatabase {database: Box::new(MockDB::new())});
+        let db = Rc::new(JiraDatabase {database: Box::new(MockDB::new()), user: None, workflow: Workflow::default()});
         let epic_id = db.create_epic(Epic::new("".to_owned(), "".to_owned())).unwrap();
-        let mut nav = Navigator::new(Rc::clone(&db));
+        let mut nav = Navigator::new(Rc::clone(&db), None);
         let mut prompts = Prompts::new();
 
         prompts.delete_epic = Box::new(|| true);
         nav.set_prompts(prompts);
         nav.handle_action(Action::DeleteEpic {epic_id}).unwrap();
         let db_state = db.read_db().unwrap();
-        assert_eq!(db_state.epics.len(), 0);
+        assert!(db_state.epics[&epic_id].archived);
     }
 
     #[test]
     fn handle_action_should_handle_create_story() {
-        let db = Rc::new(JiraDatabase {database: Box::new(MockDB::new())});
+        let db = Rc::new(JiraDatabase {database: Box::new(MockDB::new()), user: None, workflow: Workflow::default()});
         let epic_id = db.create_epic(Epic::new("".to_owned(), "".to_owned())).unwrap();
-        let mut nav = Navigator::new(Rc::clone(&db));
+        let mut nav = Navigator::new(Rc::clone(&db), None);
         let mut prompts = Prompts::new();
 
         prompts.create_story = Box::new(|| Story::new("name".to_owned(), "description".to_owned()));
@@ -222,31 +422,411 @@ mod tests {
 
     #[test]
     fn handle_action_should_handle_update_story() {
-        let db = Rc::new(JiraDatabase {database: Box::new(MockDB::new())});
+        let db = Rc::new(JiraDatabase {database: Box::new(MockDB::new()), user: None, workflow: Workflow::default()});
         let epic_id = db.create_epic(Epic::new("".to_owned(), "".to_owned())).unwrap();
         let story_id = db.create_story(Story::new("".to_owned(), "".to_owned()), epic_id).unwrap();
-        let mut nav = Navigator::new(Rc::clone(&db));
+        let mut nav = Navigator::new(Rc::clone(&db), None);
         let mut prompts = Prompts::new();
 
-        prompts.update_status = Box::new(|| Some(Status::InProgress));
+        prompts.update_status = Box::new(|_| Some(Status::InProgress));
         nav.set_prompts(prompts);
         nav.handle_action(Action::UpdateStoryStatus {story_id}).unwrap();
         let db_state = db.read_db().unwrap();
         assert_eq!(db_state.stories.get(&story_id).unwrap().status, Status::InProgress);
     }
 
+    #[test]
+    fn handle_action_should_handle_edit_story() {
+        let db = Rc::new(JiraDatabase {database: Box::new(MockDB::new()), user: None, workflow: Workflow::default()});
+        let epic_id = db.create_epic(Epic::new("".to_owned(), "".to_owned())).unwrap();
+        let story_id = db.create_story(Story::new("name".to_owned(), "".to_owned()), epic_id).unwrap();
+        let mut nav = Navigator::new(Rc::clone(&db), None);
+        let mut prompts = Prompts::new();
+
+        prompts.edit_story = Box::new(|name, _| (name.to_owned(), "new description".to_owned()));
+        nav.set_prompts(prompts);
+        nav.handle_action(Action::EditStory {story_id}).unwrap();
+
+        let db_state = db.read_db().unwrap();
+        assert_eq!(db_state.stories.get(&story_id).unwrap().name, "name".to_owned());
+        assert_eq!(db_state.stories.get(&story_id).unwrap().description, "new description".to_owned());
+    }
+
+    #[test]
+    fn handle_action_should_handle_estimate_story() {
+        let db = Rc::new(JiraDatabase {database: Box::new(MockDB::new()), user: None, workflow: Workflow::default()});
+        let epic_id = db.create_epic(Epic::new("".to_owned(), "".to_owned())).unwrap();
+        let story_id = db.create_story(Story::new("".to_owned(), "".to_owned()), epic_id).unwrap();
+        let mut nav = Navigator::new(Rc::clone(&db), None);
+        let mut prompts = Prompts::new();
+
+        prompts.estimate_story = Box::new(|priority, points| {
+            assert_eq!((priority, points), (Priority::Medium, None));
+            (Priority::Highest, Some(5))
+        });
+        nav.set_prompts(prompts);
+        nav.handle_action(Action::EstimateStory {story_id}).unwrap();
+
+        let story = db.read_db().unwrap().stories[&story_id].clone();
+        assert_eq!((story.priority, story.points), (Priority::Highest, Some(5)));
+    }
+
+    #[test]
+    fn handle_action_should_handle_move_story() {
+        let db = Rc::new(JiraDatabase {database: Box::new(MockDB::new()), user: None, workflow: Workflow::default()});
+        let from_epic_id = db.create_epic(Epic::new("".to_owned(), "".to_owned())).unwrap();
+        let to_epic_id = db.create_epic(Epic::new("".to_owned(), "".to_owned())).unwrap();
+        let story_id = db.create_story(Story::new("".to_owned(), "".to_owned()), from_epic_id).unwrap();
+        let mut nav = Navigator::new(Rc::clone(&db), None);
+        let mut prompts = Prompts::new();
+
+        prompts.move_story = Box::new(move || Some(to_epic_id));
+        nav.set_prompts(prompts);
+        nav.handle_action(Action::NavigateToEpicDetail {epic_id: from_epic_id}).unwrap();
+        nav.handle_action(Action::NavigateToStoryDetail {epic_id: from_epic_id, story_id}).unwrap();
+        nav.handle_action(Action::MoveStory {epic_id: from_epic_id, story_id}).unwrap();
+
+        let db_state = db.read_db().unwrap();
+        assert!(db_state.epics.get(&from_epic_id).unwrap().stories.is_empty());
+        assert_eq!(db_state.epics.get(&to_epic_id).unwrap().stories, vec![story_id]);
+        assert_eq!(nav.get_page_count(), 2);
+    }
+
+    #[test]
+    fn handle_action_should_handle_people() {
+        let db = Rc::new(JiraDatabase {database: Box::new(MockDB::new()), user: None, workflow: Workflow::default()});
+        let epic_id = db.create_epic(Epic::new("".to_owned(), "".to_owned())).unwrap();
+        let mut nav = Navigator::new(Rc::clone(&db), Some("alice".to_owned()));
+        let mut prompts = Prompts::new();
+
+        prompts.create_story = Box::new(|| Story::new("".to_owned(), "".to_owned()));
+        prompts.assign = Box::new(|role, current| {
+            assert_eq!(current, None);
+            Some(Some(format!("{} alice", role)))
+        });
+        nav.set_prompts(prompts);
+        nav.handle_action(Action::CreateStory {epic_id}).unwrap();
+        let story_id = db.read_db().unwrap().last_item_id;
+        nav.handle_action(Action::AssignStory {story_id}).unwrap();
+        nav.handle_action(Action::SetEpicOwner {epic_id}).unwrap();
+
+        let db_state = db.read_db().unwrap();
+        assert_eq!(db_state.stories[&story_id].reporter, Some("alice".to_owned())); //left blank in the prompt
+        assert_eq!(db_state.stories[&story_id].assignee, Some("Assignee alice".to_owned()));
+        assert_eq!(db_state.epics[&epic_id].owner, Some("Owner alice".to_owned()));
+
+        nav.handle_action(Action::NavigateToMyStories).unwrap();
+        assert!(nav.get_current_page().unwrap().as_any().downcast_ref::<MyStories>().is_some());
+    }
+
+    #[test]
+    fn handle_action_should_fail_my_stories_without_current_user() {
+        let db = Rc::new(JiraDatabase {database: Box::new(MockDB::new()), user: None, workflow: Workflow::default()});
+        let mut nav = Navigator::new(db, None);
+
+        assert!(nav.handle_action(Action::NavigateToMyStories).is_err());
+        assert_eq!(nav.get_page_count(), 1);
+    }
+
+    #[test]
+    fn handle_action_should_offer_only_legal_story_statuses() {
+        let workflow = Workflow::parse(r#"{"statuses": ["OPEN", "IN PROGRESS", "CLOSED"], "transitions": {"OPEN": ["IN PROGRESS"], "IN PROGRESS": ["OPEN", "CLOSED"]}}"#).unwrap();
+        let db = Rc::new(JiraDatabase {database: Box::new(MockDB::new()), user: None, workflow});
+        let epic_id = db.create_epic(Epic::new("".to_owned(), "".to_owned())).unwrap();
+        let story_id = db.create_story(Story::new("".to_owned(), "".to_owned()), epic_id).unwrap();
+        let mut nav = Navigator::new(Rc::clone(&db), None);
+        let mut prompts = Prompts::new();
+
+        prompts.update_status = Box::new(|statuses| statuses.last().cloned()); //always picks the last one offered
+        nav.set_prompts(prompts);
+        nav.handle_action(Action::UpdateStoryStatus {story_id}).unwrap();
+        assert_eq!(db.read_db().unwrap().stories[&story_id].status, Status::InProgress);
+        nav.handle_action(Action::UpdateStoryStatus {story_id}).unwrap();
+        assert_eq!(db.read_db().unwrap().stories[&story_id].status, Status::Closed);
+        assert!(nav.handle_action(Action::UpdateStoryStatus {story_id}).is_err()); //CLOSED has no way out
+    }
+
+    #[test]
+    fn handle_action_should_add_comment() {
+        let db = Rc::new(JiraDatabase {database: Box::new(MockDB::new()), user: Some("alice".to_owned()), workflow: Workflow::default()});
+        let epic_id = db.create_epic(Epic::new("".to_owned(), "".to_owned())).unwrap();
+        let story_id = db.create_story(Story::new("".to_owned(), "".to_owned()), epic_id).unwrap();
+        let mut nav = Navigator::new(Rc::clone(&db), Some("alice".to_owned()));
+        let mut prompts = Prompts::new();
+
+        prompts.add_comment = Box::new(|| Some("looks good".to_owned()));
+        nav.set_prompts(prompts);
+        nav.handle_action(Action::AddComment {item_id: story_id}).unwrap();
+
+        let db_state = db.read_db().unwrap();
+        let comments = db_state.comments_on(story_id);
+        assert_eq!(comments.len(), 1);
+        assert_eq!((comments[0].author.as_str(), comments[0].body.as_str()), ("alice", "looks good"));
+    }
+
+    #[test]
+    fn handle_action_should_add_and_remove_labels() {
+        let db = Rc::new(JiraDatabase {database: Box::new(MockDB::new()), user: None, workflow: Workflow::default()});
+        let epic_id = db.create_epic(Epic::new("".to_owned(), "".to_owned())).unwrap();
+        let story_id = db.create_story(Story::new("".to_owned(), "".to_owned()), epic_id).unwrap();
+        let mut nav = Navigator::new(Rc::clone(&db), None);
+        let mut prompts = Prompts::new();
+
+        prompts.label = Box::new(|_, suggestions| Some(suggestions.first().cloned().unwrap_or_else(|| "backend".to_owned())));
+        nav.set_prompts(prompts);
+        assert!(nav.handle_action(Action::RemoveLabel {item_id: story_id}).is_err()); //nothing to remove yet
+        nav.handle_action(Action::AddLabel {item_id: epic_id}).unwrap();
+        nav.handle_action(Action::AddLabel {item_id: story_id}).unwrap(); //"backend" is suggested from the index
+        assert_eq!(db.read_db().unwrap().labelled("backend"), BTreeSet::from([epic_id, story_id]));
+
+        nav.handle_action(Action::RemoveLabel {item_id: story_id}).unwrap();
+        assert_eq!(db.read_db().unwrap().labelled("backend"), BTreeSet::from([epic_id]));
+        nav.handle_action(Action::Undo).unwrap();
+        assert_eq!(db.read_db().unwrap().labelled("backend"), BTreeSet::from([epic_id, story_id]));
+    }
+
+    #[test]
+    fn handle_action_should_replace_the_page_with_a_filtered_one() {
+        let db = Rc::new(JiraDatabase {database: Box::new(MockDB::new()), user: None, workflow: Workflow::default()});
+        let epic_id = db.create_epic(Epic::new("".to_owned(), "".to_owned())).unwrap();
+        let mut nav = Navigator::new(Rc::clone(&db), None);
+        let mut prompts = Prompts::new();
+
+        prompts.label = Box::new(|_, _| Some("Backend".to_owned()));
+        nav.set_prompts(prompts);
+        nav.handle_action(Action::FilterByLabel {epic_id: None}).unwrap();
+        let home_page = nav.get_current_page().unwrap().as_any().downcast_ref::<HomePage>().unwrap();
+        assert_eq!(home_page.label.as_deref(), Some("backend"));
+
+        nav.handle_action(Action::NavigateToEpicDetail {epic_id}).unwrap();
+        nav.handle_action(Action::FilterByLabel {epic_id: Some(epic_id)}).unwrap();
+        assert_eq!(nav.get_page_count(), 2);
+        let epic_detail = nav.get_current_page().unwrap().as_any().downcast_ref::<EpicDetail>().unwrap();
+        assert_eq!((epic_detail.epic_id, epic_detail.label.as_deref()), (epic_id, Some("backend")));
+
+        let mut prompts = Prompts::new();
+        prompts.label = Box::new(|_, _| None); //a blank answer clears the filter
+        nav.set_prompts(prompts);
+        nav.handle_action(Action::FilterByLabel {epic_id: Some(epic_id)}).unwrap();
+        assert!(nav.get_current_page().unwrap().as_any().downcast_ref::<EpicDetail>().unwrap().label.is_none());
+        assert!(nav.handle_action(Action::Undo).is_err()); //filtering isn't a change to the board
+    }
+
+    #[test]
+    fn handle_action_should_list_stories_matching_a_filter() {
+        let db = Rc::new(JiraDatabase {database: Box::new(MockDB::new()), user: None, workflow: Workflow::default()});
+        let mut nav = Navigator::new(Rc::clone(&db), None);
+        let mut prompts = Prompts::new();
+
+        prompts.query_stories = Box::new(|| Some("status = open AND".to_owned()));
+        nav.set_prompts(prompts);
+        let error = nav.handle_action(Action::QueryStories).unwrap_err();
+        assert_eq!(error.to_string(), "invalid filter, column 18: expected a field name, found the end of the query");
+        assert_eq!(nav.get_page_count(), 1);
+
+        let mut prompts = Prompts::new();
+        prompts.query_stories = Box::new(|| Some("status = open".to_owned()));
+        nav.set_prompts(prompts);
+        nav.handle_action(Action::QueryStories).unwrap();
+        let query_page = nav.get_current_page().unwrap().as_any().downcast_ref::<QueryPage>().unwrap();
+        assert_eq!(query_page.query, Query::parse("status = OPEN").unwrap());
+    }
+
+    #[test]
+    fn handle_action_should_save_and_open_filters() {
+        let db = Rc::new(JiraDatabase {database: Box::new(MockDB::new()), user: None, workflow: Workflow::default()});
+        let mut nav = Navigator::new(Rc::clone(&db), None);
+        let mut prompts = Prompts::new();
+
+        prompts.save_filter = Box::new(|| Some("open work".to_owned()));
+        prompts.delete_saved_filter = Box::new(|| Some("open work".to_owned()));
+        nav.set_prompts(prompts);
+        nav.handle_action(Action::SaveFilter {query: "status = open".to_owned()}).unwrap();
+        assert_eq!(db.read_db().unwrap().saved_filters["open work"], "status = open");
+
+        nav.handle_action(Action::NavigateToSavedFilters).unwrap();
+        assert!(nav.get_current_page().unwrap().as_any().downcast_ref::<SavedFiltersPage>().is_some());
+        nav.handle_action(Action::OpenSavedFilter {name: "open work".to_owned()}).unwrap();
+        let query_page = nav.get_current_page().unwrap().as_any().downcast_ref::<QueryPage>().unwrap();
+        assert_eq!((query_page.name.as_deref(), query_page.source.as_str()), (Some("open work"), "status = open"));
+        assert!(nav.handle_action(Action::OpenSavedFilter {name: "missing".to_owned()}).is_err());
+
+        nav.handle_action(Action::DeleteSavedFilter).unwrap();
+        assert!(db.read_db().unwrap().saved_filters.is_empty());
+        assert_eq!(nav.get_page_count(), 3);
+    }
+
+    #[test]
+    fn handle_action_should_push_search_results() {
+        let db = Rc::new(JiraDatabase {database: Box::new(MockDB::new()), user: None, workflow: Workflow::default()});
+        let epic_id = db.create_epic(Epic::new("login".to_owned(), "".to_owned())).unwrap();
+        let story_id = db.create_story(Story::new("login page".to_owned(), "".to_owned()), epic_id).unwrap();
+        let mut nav = Navigator::new(Rc::clone(&db), None);
+        let mut prompts = Prompts::new();
+
+        prompts.search = Box::new(|| Some("page".to_owned()));
+        nav.set_prompts(prompts);
+        nav.handle_action(Action::Search).unwrap();
+        let search_page = nav.get_current_page().unwrap().as_any().downcast_ref::<SearchPage>().unwrap();
+        assert_eq!(search_page.query, "page");
+
+        let action = nav.handle_input(&story_id.to_string()).unwrap().unwrap();
+        nav.handle_action(action).unwrap();
+        assert_eq!(nav.get_page_count(), 3);
+        assert!(nav.get_current_page().unwrap().as_any().downcast_ref::<StoryDetail>().is_some());
+
+        let mut prompts = Prompts::new();
+        prompts.search = Box::new(|| None); //a blank query stays where it was
+        nav.set_prompts(prompts);
+        nav.handle_action(Action::Search).unwrap();
+        assert_eq!(nav.get_page_count(), 3);
+    }
+
+    #[test]
+    fn handle_action_should_restore_and_purge_from_trash() {
+        let db = Rc::new(JiraDatabase {database: Box::new(MockDB::new()), user: None, workflow: Workflow::default()});
+        let epic_id = db.create_epic(Epic::new("".to_owned(), "".to_owned())).unwrap();
+        let story_id = db.create_story(Story::new("".to_owned(), "".to_owned()), epic_id).unwrap();
+        db.delete_epic(epic_id).unwrap();
+        let mut nav = Navigator::new(Rc::clone(&db), None);
+        let mut prompts = Prompts::new();
+
+        prompts.purge_item = Box::new(move || Some(epic_id));
+        nav.set_prompts(prompts);
+        nav.handle_action(Action::NavigateToTrash).unwrap();
+        assert!(nav.get_current_page().unwrap().as_any().downcast_ref::<TrashPage>().is_some());
+
+        nav.handle_action(Action::RestoreItem {item_id: epic_id}).unwrap();
+        assert!(!db.read_db().unwrap().stories[&story_id].archived);
+        assert!(nav.handle_action(Action::PurgeItem).is_err()); //restored, so no longer in the trash
+
+        db.delete_epic(epic_id).unwrap();
+        nav.handle_action(Action::PurgeItem).unwrap();
+        assert!(db.read_db().unwrap().epics.is_empty());
+        assert_eq!(nav.get_page_count(), 2);
+    }
+
+    #[test]
+    fn handle_input_should_map_global_undo_redo_keys() {
+        let db = Rc::new(JiraDatabase {database: Box::new(MockDB::new()), user: None, workflow: Workflow::default()});
+        let mut nav = Navigator::new(db, None);
+        nav.handle_action(Action::NavigateToEpicDetail {epic_id: 1}).unwrap();
+
+        assert_eq!(nav.handle_input("z").unwrap(), Some(Action::Undo));
+        assert_eq!(nav.handle_input("y").unwrap(), Some(Action::Redo));
+        assert_eq!(nav.handle_input("p").unwrap(), Some(Action::NavigateToPreviousPage));
+    }
+
+    #[test]
+    fn handle_action_should_undo_and_redo_delete_epic() {
+        let db = Rc::new(JiraDatabase {database: Box::new(MockDB::new()), user: None, workflow: Workflow::default()});
+        let epic_id = db.create_epic(Epic::new("epic".to_owned(), "".to_owned())).unwrap();
+        let story_id = db.create_story(Story::new("story".to_owned(), "".to_owned()), epic_id).unwrap();
+        let before = db.read_db().unwrap();
+        let mut nav = Navigator::new(Rc::clone(&db), None);
+        let mut prompts = Prompts::new();
+
+        prompts.delete_epic = Box::new(|| true);
+        nav.set_prompts(prompts);
+        nav.handle_action(Action::NavigateToEpicDetail {epic_id}).unwrap();
+        nav.handle_action(Action::DeleteEpic {epic_id}).unwrap();
+        assert!(db.read_db().unwrap().epics[&epic_id].archived);
+
+        nav.handle_action(Action::Undo).unwrap();
+        let db_state = db.read_db().unwrap();
+        assert_eq!(db_state.epics, before.epics);
+        assert_eq!(db_state.stories[&story_id], before.stories[&story_id]);
+
+        nav.handle_action(Action::Redo).unwrap();
+        assert!(db.read_db().unwrap().stories[&story_id].archived);
+        assert!(nav.handle_action(Action::Redo).is_err()); //nothing left to redo
+    }
+
+    #[test]
+    fn handle_action_should_undo_status_changes_and_creates_in_order() {
+        let db = Rc::new(JiraDatabase {database: Box::new(MockDB::new()), user: None, workflow: Workflow::default()});
+        let epic_id = db.create_epic(Epic::new("".to_owned(), "".to_owned())).unwrap();
+        let mut nav = Navigator::new(Rc::clone(&db), None);
+        let mut prompts = Prompts::new();
+
+        prompts.create_story = Box::new(|| Story::new("story".to_owned(), "".to_owned()));
+        prompts.update_status = Box::new(|_| Some(Status::Resolved));
+        nav.set_prompts(prompts);
+        nav.handle_action(Action::CreateStory {epic_id}).unwrap();
+        let story_id = db.read_db().unwrap().last_item_id;
+        nav.handle_action(Action::NavigateToStoryDetail {epic_id, story_id}).unwrap(); //navigation isn't undoable
+        nav.handle_action(Action::UpdateStoryStatus {story_id}).unwrap();
+
+        nav.handle_action(Action::Undo).unwrap();
+        assert_eq!(db.read_db().unwrap().stories[&story_id].status, Status::Open);
+        nav.handle_action(Action::Undo).unwrap();
+        let db_state = db.read_db().unwrap();
+        assert!(db_state.stories.is_empty());
+        assert!(db_state.epics[&epic_id].stories.is_empty());
+        assert!(nav.handle_action(Action::Undo).is_err());
+
+        nav.handle_action(Action::Redo).unwrap();
+        assert_eq!(db.read_db().unwrap().epics[&epic_id].stories, vec![story_id]); //same id as before the undo
+        nav.handle_action(Action::UpdateEpicStatus {epic_id}).unwrap(); //a new change drops what was left to redo
+        assert!(nav.handle_action(Action::Redo).is_err());
+    }
+
+    #[test]
+    fn handle_action_should_undo_comments() {
+        let db = Rc::new(JiraDatabase {database: Box::new(MockDB::new()), user: Some("alice".to_owned()), workflow: Workflow::default()});
+        let epic_id = db.create_epic(Epic::new("".to_owned(), "".to_owned())).unwrap();
+        let mut nav = Navigator::new(Rc::clone(&db), None);
+        let mut prompts = Prompts::new();
+
+        prompts.add_comment = Box::new(|| Some("looks good".to_owned()));
+        prompts.update_status = Box::new(|_| Some(Status::InProgress));
+        nav.set_prompts(prompts);
+        nav.handle_action(Action::UpdateEpicStatus {epic_id}).unwrap();
+        nav.handle_action(Action::AddComment {item_id: epic_id}).unwrap();
+
+        nav.handle_action(Action::Undo).unwrap(); //takes back the comment, not the status change before it
+        let db_state = db.read_db().unwrap();
+        assert!(db_state.comments.is_empty());
+        assert_eq!(db_state.epics[&epic_id].status, Status::InProgress);
+        nav.handle_action(Action::Redo).unwrap();
+        assert_eq!(db.read_db().unwrap().comments_on(epic_id)[0].body, "looks good");
+    }
+
+    #[test]
+    fn handle_action_should_undo_saved_filters() {
+        let db = Rc::new(JiraDatabase {database: Box::new(MockDB::new()), user: None, workflow: Workflow::default()});
+        let epic_id = db.create_epic(Epic::new("".to_owned(), "".to_owned())).unwrap();
+        let mut nav = Navigator::new(Rc::clone(&db), None);
+        let mut prompts = Prompts::new();
+
+        prompts.save_filter = Box::new(|| Some("open".to_owned()));
+        prompts.delete_saved_filter = Box::new(|| Some("open".to_owned()));
+        prompts.update_status = Box::new(|_| Some(Status::InProgress));
+        nav.set_prompts(prompts);
+        nav.handle_action(Action::UpdateEpicStatus {epic_id}).unwrap();
+        nav.handle_action(Action::SaveFilter {query: "status = open".to_owned()}).unwrap();
+        nav.handle_action(Action::DeleteSavedFilter).unwrap();
+
+        nav.handle_action(Action::Undo).unwrap();
+        assert_eq!(db.read_db().unwrap().saved_filters["open"], "status = open");
+        nav.handle_action(Action::Undo).unwrap();
+        let db_state = db.read_db().unwrap();
+        assert!(db_state.saved_filters.is_empty());
+        assert_eq!(db_state.epics[&epic_id].status, Status::InProgress); //the status change before the filters stays
+    }
+
     #[test]
     fn handle_action_should_delete_story() {
-        let db = Rc::new(JiraDatabase {database: Box::new(MockDB::new())});
+        let db = Rc::new(JiraDatabase {database: Box::new(MockDB::new()), user: None, workflow: Workflow::default()});
         let epic_id = db.create_epic(Epic::new("".to_owned(), "".to_owned())).unwrap();
         let story_id = db.create_story(Story::new("".to_owned(), "".to_owned()), epic_id).unwrap();
-        let mut nav = Navigator::new(Rc::clone(&db));
+        let mut nav = Navigator::new(Rc::clone(&db), None);
         let mut prompts = Prompts::new();
 
         prompts.delete_story = Box::new(|| true);
         nav.set_prompts(prompts);
         nav.handle_action(Action::DeleteStory {epic_id, story_id}).unwrap();
         let db_state = db.read_db().unwrap();
-        assert_eq!(db_state.stories.len(), 0);
+        assert!(db_state.stories[&story_id].archived);
     }
 }
\ No newline at end of file
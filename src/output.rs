@@ -0,0 +1,133 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use itertools::Itertools;
+use serde::Serialize;
+
+use crate::models::DBState;
+
+// Machine-readable view of the board for dashboards and jq pipelines. These structs are the public schema:
+// fields may be added, but existing ones keep their name and meaning even if models.rs changes shape.
+
+#[derive(Serialize, Debug, PartialEq, Eq)]
+pub struct EpicOutput {
+    pub id: u32,
+    pub name: String,
+    pub description: String,
+    pub status: String, //display form, e.g. "IN PROGRESS"
+    pub stories: Vec<u32>, //child story ids in the order the epic lists them
+    pub owner: Option<String>,
+    pub created_at: Option<DateTime<Utc>>, //RFC 3339, null for items older than timestamps
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize, Debug, PartialEq, Eq)]
+pub struct StoryOutput {
+    pub id: u32,
+    pub epic_id: Option<u32>, //None only for orphan stories, see DBState::validate
+    pub name: String,
+    pub description: String,
+    pub status: String,
+    pub assignee: Option<String>,
+    pub reporter: Option<String>,
+    pub priority: String, //display form, e.g. "HIGHEST"
+    pub points: Option<u32>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize, Debug, PartialEq, Eq)]
+pub struct HistoryOutput {
+    pub item_id: u32,
+    pub at: DateTime<Utc>,
+    pub user: Option<String>,
+    pub field: String,
+    pub old: String,
+    pub new: String,
+}
+
+#[derive(clap::ValueEnum, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum OutputFormat {
+    Text, //tab separated id, name and status
+    Json,
+}
+
+// Every epic not in the trash, sorted by id
+pub fn epics(db_state: &DBState) -> Vec<EpicOutput> {
+    let in_trash = |story_id: &u32| db_state.stories.get(story_id).is_some_and(|story| story.archived);
+    db_state.epics.iter().filter(|(_, epic)| !epic.archived).sorted_by_key(|(id, _)| **id).map(|(id, epic)| EpicOutput {
+        id: *id,
+        name: epic.name.clone(),
+        description: epic.description.clone(),
+        status: epic.status.to_string(),
+        stories: epic.stories.iter().filter(|id| !in_trash(id)).copied().collect(),
+        owner: epic.owner.clone(),
+        created_at: epic.created_at,
+        updated_at: epic.updated_at,
+    }).collect()
+}
+
+// The given stories, sorted by id; ids without a matching story and stories in the trash are skipped
+pub fn stories(db_state: &DBState, story_ids: &[u32]) -> Vec<StoryOutput> {
+    let parents: HashMap<u32, u32> = db_state.epics.iter()
+        .flat_map(|(epic_id, epic)| epic.stories.iter().map(move |story_id| (*story_id, *epic_id)))
+        .collect();
+
+    story_ids.iter().sorted().filter_map(|id| {
+        let story = db_state.stories.get(id).filter(|story| !story.archived)?;
+        Some(StoryOutput {
+            id: *id,
+            epic_id: parents.get(id).copied(),
+            name: story.name.clone(),
+            description: story.description.clone(),
+            status: story.status.to_string(),
+            assignee: story.assignee.clone(),
+            reporter: story.reporter.clone(),
+            priority: story.priority.to_string(),
+            points: story.points,
+            created_at: story.created_at,
+            updated_at: story.updated_at,
+        })
+    }).collect()
+}
+
+// The history of one item, oldest first
+pub fn history(db_state: &DBState, item_id: u32) -> Vec<HistoryOutput> {
+    db_state.history.iter().filter(|entry| entry.item_id == item_id).map(|entry| HistoryOutput {
+        item_id: entry.item_id,
+        at: entry.at,
+        user: entry.user.clone(),
+        field: entry.field.clone(),
+        old: entry.old.clone(),
+        new: entry.new.clone(),
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Epic, Status, Story};
+
+    #[test]
+    fn json_schema_should_be_stable() {
+        let mut db_state = DBState { last_item_id: 3, ..Default::default() };
+        let mut epic = Epic::new("epic".to_owned(), "epic description".to_owned());
+        epic.stories = vec![3, 2];
+        db_state.epics.insert(1, epic);
+        let mut story = Story::new("story".to_owned(), "".to_owned());
+        story.status = Status::InProgress;
+        story.assignee = Some("alice".to_owned());
+        story.points = Some(3);
+        story.created_at = Some("2024-05-01T12:00:00Z".parse().unwrap());
+        db_state.stories.insert(2, story);
+        db_state.stories.insert(3, Story::new("other".to_owned(), "".to_owned()));
+
+        assert_eq!(serde_json::to_value(epics(&db_state)).unwrap(), serde_json::json!([
+            {"id": 1, "name": "epic", "description": "epic description", "status": "OPEN", "stories": [3, 2], "owner": null, "created_at": null, "updated_at": null}
+        ]));
+        assert_eq!(serde_json::to_value(stories(&db_state, &[3, 2, 999])).unwrap(), serde_json::json!([
+            {"id": 2, "epic_id": 1, "name": "story", "description": "", "status": "IN PROGRESS", "assignee": "alice", "reporter": null, "priority": "MEDIUM", "points": 3, "created_at": "2024-05-01T12:00:00Z", "updated_at": null},
+            {"id": 3, "epic_id": 1, "name": "other", "description": "", "status": "OPEN", "assignee": null, "reporter": null, "priority": "MEDIUM", "points": null, "created_at": null, "updated_at": null}
+        ]));
+    }
+}
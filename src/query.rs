@@ -0,0 +1,498 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::str::FromStr;
+
+use crate::models::{normalize_label, DBState, Priority, Status, Story};
+
+// A small JQL-like filter over stories, e.g. status = "IN PROGRESS" AND epic = 3 AND label = backend
+//
+//   query      := and ("OR" and)*
+//   and        := not ("AND" not)*
+//   not        := "NOT" not | "(" query ")" | comparison
+//   comparison := field op value | field "IN" "(" value ("," value)* ")"
+//
+// Keywords and field names ignore case. A value is a word, a number or a "quoted string" (\" and \\ escape),
+// EMPTY matches a story without an epic, assignee, reporter or points. Text is compared ignoring case.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Query {
+    And(Box<Query>, Box<Query>),
+    Or(Box<Query>, Box<Query>),
+    Not(Box<Query>),
+    Compare { field: Field, op: Op, values: Vec<Value> }, //exactly one value unless op is In
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Field {
+    Id,
+    Epic,
+    Name,
+    Description,
+    Status,
+    Priority,
+    Points,
+    Assignee,
+    Reporter,
+    Label,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Op {
+    Eq,
+    NotEq,
+    Contains, //~
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    In,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Value {
+    Number(u32),
+    Text(String),
+    Status(Status),
+    Priority(Priority),
+    Empty,
+}
+
+// Where and why a query could not be parsed; columns count characters from 1
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ParseError {
+    pub column: usize,
+    pub message: String,
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "column {}: {}", self.column, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl Field {
+    const ALL: [Field; 10] = [Field::Id, Field::Epic, Field::Name, Field::Description, Field::Status, Field::Priority, Field::Points, Field::Assignee, Field::Reporter, Field::Label];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Field::Id => "id",
+            Field::Epic => "epic",
+            Field::Name => "name",
+            Field::Description => "description",
+            Field::Status => "status",
+            Field::Priority => "priority",
+            Field::Points => "points",
+            Field::Assignee => "assignee",
+            Field::Reporter => "reporter",
+            Field::Label => "label",
+        }
+    }
+
+    fn allows(&self, op: Op) -> bool {
+        match op {
+            Op::Eq | Op::NotEq | Op::In => true,
+            Op::Contains => matches!(self, Field::Name | Field::Description | Field::Assignee | Field::Reporter),
+            Op::Lt | Op::Le | Op::Gt | Op::Ge => matches!(self, Field::Id | Field::Epic | Field::Priority | Field::Points),
+        }
+    }
+
+    // Turns a value as written in the query into the field's type
+    fn value(&self, text: &str, quoted: bool) -> Result<Value, String> {
+        if !quoted && text.eq_ignore_ascii_case("EMPTY") {
+            return match self {
+                Field::Epic | Field::Points | Field::Assignee | Field::Reporter => Ok(Value::Empty),
+                field => Err(format!("{} is never empty", field)),
+            };
+        }
+        match self {
+            Field::Id | Field::Epic | Field::Points => text.parse::<u32>().map(Value::Number).map_err(|_| format!("{} needs a number, not \"{}\"", self, text)),
+            Field::Status => text.parse::<Status>().map(Value::Status),
+            Field::Priority => text.parse::<Priority>().map(Value::Priority),
+            Field::Label => normalize_label(text).map(Value::Text),
+            Field::Name | Field::Description | Field::Assignee | Field::Reporter => Ok(Value::Text(text.to_owned())),
+        }
+    }
+
+    // What the story holds for this field, None when it is empty; labels are a set and handled by Query::matches
+    fn of(&self, story_id: u32, epic_id: Option<u32>, story: &Story) -> Option<Value> {
+        match self {
+            Field::Id => Some(Value::Number(story_id)),
+            Field::Epic => epic_id.map(Value::Number),
+            Field::Name => Some(Value::Text(story.name.clone())),
+            Field::Description => Some(Value::Text(story.description.clone())),
+            Field::Status => Some(Value::Status(story.status.clone())),
+            Field::Priority => Some(Value::Priority(story.priority)),
+            Field::Points => story.points.map(Value::Number),
+            Field::Assignee => story.assignee.clone().map(Value::Text),
+            Field::Reporter => story.reporter.clone().map(Value::Text),
+            Field::Label => None,
+        }
+    }
+}
+
+impl Display for Field {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl Display for Op {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let symbol = match self {
+            Op::Eq => "=",
+            Op::NotEq => "!=",
+            Op::Contains => "~",
+            Op::Lt => "<",
+            Op::Le => "<=",
+            Op::Gt => ">",
+            Op::Ge => ">=",
+            Op::In => "IN",
+        };
+        write!(f, "{}", symbol)
+    }
+}
+
+impl Value {
+    // None when the two can't be ordered, e.g. different statuses or an EMPTY on either side
+    fn compare(&self, other: &Value) -> Option<Ordering> {
+        match (self, other) {
+            (Value::Number(a), Value::Number(b)) => Some(a.cmp(b)),
+            (Value::Priority(a), Value::Priority(b)) => Some(a.cmp(b)),
+            (Value::Text(a), Value::Text(b)) => Some(a.to_lowercase().cmp(&b.to_lowercase())),
+            (Value::Status(a), Value::Status(b)) if a == b => Some(Ordering::Equal),
+            _ => None,
+        }
+    }
+}
+
+fn equals(actual: Option<&Value>, expected: &Value) -> bool {
+    match actual {
+        None => expected == &Value::Empty,
+        Some(actual) => actual.compare(expected) == Some(Ordering::Equal),
+    }
+}
+
+impl FromStr for Query {
+    type Err = ParseError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        Query::parse(input)
+    }
+}
+
+impl Query {
+    pub fn parse(input: &str) -> Result<Query, ParseError> {
+        let mut parser = Parser { tokens: tokenize(input)?, position: 0 };
+        let query = parser.or()?;
+        match parser.next() {
+            (_, Token::End) => Ok(query),
+            (column, Token::Symbol(")")) => Err(ParseError { column, message: "unmatched \")\"".to_owned() }),
+            (column, token) => Err(ParseError { column, message: format!("expected AND or OR before {}", token) }),
+        }
+    }
+
+    pub fn matches(&self, story_id: u32, epic_id: Option<u32>, story: &Story) -> bool {
+        match self {
+            Query::And(left, right) => left.matches(story_id, epic_id, story) && right.matches(story_id, epic_id, story),
+            Query::Or(left, right) => left.matches(story_id, epic_id, story) || right.matches(story_id, epic_id, story),
+            Query::Not(query) => !query.matches(story_id, epic_id, story),
+            Query::Compare { field: Field::Label, op, values } => { //"label = x" means one of the labels is x
+                let has = |value: &Value| matches!(value, Value::Text(label) if story.labels.contains(label));
+                match op {
+                    Op::NotEq => !has(&values[0]),
+                    _ => values.iter().any(has),
+                }
+            }
+            Query::Compare { field, op, values } => {
+                let actual = field.of(story_id, epic_id, story);
+                let ordered = |wanted: &[Ordering]| actual.as_ref().and_then(|actual| actual.compare(&values[0])).is_some_and(|ordering| wanted.contains(&ordering));
+                match op {
+                    Op::Eq => equals(actual.as_ref(), &values[0]),
+                    Op::NotEq => !equals(actual.as_ref(), &values[0]),
+                    Op::In => values.iter().any(|value| equals(actual.as_ref(), value)),
+                    Op::Contains => match (&actual, &values[0]) {
+                        (Some(Value::Text(actual)), Value::Text(part)) => actual.to_lowercase().contains(&part.to_lowercase()),
+                        _ => false,
+                    },
+                    Op::Lt => ordered(&[Ordering::Less]),
+                    Op::Le => ordered(&[Ordering::Less, Ordering::Equal]),
+                    Op::Gt => ordered(&[Ordering::Greater]),
+                    Op::Ge => ordered(&[Ordering::Greater, Ordering::Equal]),
+                }
+            }
+        }
+    }
+
+    // Ids of the stories not in the trash that match, sorted by id
+    pub fn stories(&self, db_state: &DBState) -> Vec<u32> {
+        let parents: HashMap<u32, u32> = db_state.epics.iter()
+            .flat_map(|(epic_id, epic)| epic.stories.iter().map(move |story_id| (*story_id, *epic_id)))
+            .collect();
+        let mut story_ids: Vec<u32> = db_state.stories.iter()
+            .filter(|(id, story)| !story.archived && self.matches(**id, parents.get(id).copied(), story))
+            .map(|(id, _)| *id)
+            .collect();
+        story_ids.sort();
+        story_ids
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+enum Token {
+    Word(String),
+    Quoted(String),
+    Symbol(&'static str),
+    End,
+}
+
+impl Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Word(word) | Token::Quoted(word) => write!(f, "\"{}\"", word),
+            Token::Symbol(symbol) => write!(f, "\"{}\"", symbol),
+            Token::End => write!(f, "the end of the query"),
+        }
+    }
+}
+
+const SYMBOLS: [&str; 10] = ["!=", "<=", ">=", "=", "<", ">", "~", "(", ")", ","]; //two character symbols first
+
+// Splits the query into tokens, each with the column it starts at
+fn tokenize(input: &str) -> Result<Vec<(usize, Token)>, ParseError> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = vec![];
+    let mut index = 0;
+    while index < chars.len() {
+        let column = index + 1;
+        let rest: String = chars[index..].iter().take(2).collect();
+        if chars[index].is_whitespace() {
+            index += 1;
+        } else if let Some(symbol) = SYMBOLS.into_iter().find(|symbol| rest.starts_with(symbol)) {
+            tokens.push((column, Token::Symbol(symbol)));
+            index += symbol.len();
+        } else if chars[index] == '"' {
+            let mut text = String::new();
+            index += 1;
+            loop {
+                match chars.get(index) {
+                    None => return Err(ParseError { column, message: "unterminated quoted string".to_owned() }),
+                    Some('"') => break,
+                    Some('\\') if matches!(chars.get(index + 1), Some('"' | '\\')) => {
+                        text.push(chars[index + 1]);
+                        index += 1;
+                    }
+                    Some(c) => text.push(*c),
+                }
+                index += 1;
+            }
+            tokens.push((column, Token::Quoted(text)));
+            index += 1;
+        } else if chars[index] == '!' {
+            return Err(ParseError { column, message: "expected \"!=\"".to_owned() });
+        } else {
+            let word: String = chars[index..].iter().take_while(|c| !c.is_whitespace() && !"\"!=<>~(),".contains(**c)).collect();
+            index += word.chars().count();
+            tokens.push((column, Token::Word(word)));
+        }
+    }
+    tokens.push((chars.len() + 1, Token::End));
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(usize, Token)>, //always ends with Token::End
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> &(usize, Token) {
+        &self.tokens[self.position]
+    }
+
+    fn next(&mut self) -> (usize, Token) {
+        let token = self.tokens[self.position].clone();
+        if token.1 != Token::End {
+            self.position += 1;
+        }
+        token
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        let found = matches!(&self.peek().1, Token::Word(word) if word.eq_ignore_ascii_case(keyword));
+        if found {
+            self.position += 1;
+        }
+        found
+    }
+
+    fn expect_symbol(&mut self, symbol: &str, after: &str) -> Result<(), ParseError> {
+        match self.next() {
+            (_, Token::Symbol(found)) if found == symbol => Ok(()),
+            (column, token) => Err(ParseError { column, message: format!("expected \"{}\" {}, found {}", symbol, after, token) }),
+        }
+    }
+
+    fn or(&mut self) -> Result<Query, ParseError> {
+        let mut query = self.and()?;
+        while self.eat_keyword("OR") {
+            query = Query::Or(Box::new(query), Box::new(self.and()?));
+        }
+        Ok(query)
+    }
+
+    fn and(&mut self) -> Result<Query, ParseError> {
+        let mut query = self.not()?;
+        while self.eat_keyword("AND") {
+            query = Query::And(Box::new(query), Box::new(self.not()?));
+        }
+        Ok(query)
+    }
+
+    fn not(&mut self) -> Result<Query, ParseError> {
+        if self.eat_keyword("NOT") {
+            return Ok(Query::Not(Box::new(self.not()?)));
+        }
+        if self.peek().1 == Token::Symbol("(") {
+            self.next();
+            let query = self.or()?;
+            self.expect_symbol(")", "to close the group")?;
+            return Ok(query);
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> Result<Query, ParseError> {
+        let field = match self.next() {
+            (column, Token::Word(word)) => Field::ALL.into_iter().find(|field| field.name().eq_ignore_ascii_case(&word)).ok_or_else(|| {
+                let names: Vec<&str> = Field::ALL.iter().map(Field::name).collect();
+                ParseError { column, message: format!("unknown field \"{}\" (expected one of {})", word, names.join(", ")) }
+            })?,
+            (column, token) => return Err(ParseError { column, message: format!("expected a field name, found {}", token) }),
+        };
+
+        let (op_column, op) = match self.next() {
+            (column, Token::Word(word)) if word.eq_ignore_ascii_case("IN") => (column, Op::In),
+            (column, Token::Symbol(symbol)) if !matches!(symbol, "(" | ")" | ",") => (column, match symbol {
+                "=" => Op::Eq,
+                "!=" => Op::NotEq,
+                "~" => Op::Contains,
+                "<" => Op::Lt,
+                "<=" => Op::Le,
+                ">" => Op::Gt,
+                _ => Op::Ge,
+            }),
+            (column, token) => return Err(ParseError { column, message: format!("expected an operator after {}, found {}", field, token) }),
+        };
+        if !field.allows(op) {
+            return Err(ParseError { column: op_column, message: format!("{} can't be compared with {}", field, op) });
+        }
+
+        let mut values = vec![];
+        if op == Op::In {
+            self.expect_symbol("(", "after IN")?;
+            loop {
+                values.push(self.value(field, op)?);
+                if self.peek().1 != Token::Symbol(",") {
+                    break;
+                }
+                self.next();
+            }
+            self.expect_symbol(")", "to close the IN list")?;
+        } else {
+            values.push(self.value(field, op)?);
+        }
+        Ok(Query::Compare { field, op, values })
+    }
+
+    fn value(&mut self, field: Field, op: Op) -> Result<Value, ParseError> {
+        let (column, text, quoted) = match self.next() {
+            (column, Token::Word(word)) => (column, word, false),
+            (column, Token::Quoted(text)) => (column, text, true),
+            (column, token) => return Err(ParseError { column, message: format!("expected a value after {}, found {}", op, token) }),
+        };
+        let value = field.value(&text, quoted).map_err(|message| ParseError { column, message })?;
+        if value == Value::Empty && !matches!(op, Op::Eq | Op::NotEq | Op::In) {
+            return Err(ParseError { column, message: format!("EMPTY can't be compared with {}", op) });
+        }
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Epic;
+
+    fn compare(field: Field, op: Op, value: Value) -> Query {
+        Query::Compare { field, op, values: vec![value] }
+    }
+
+    #[test]
+    fn parse_should_build_the_ast_with_and_binding_tighter_than_or() {
+        let query = Query::parse(r#"status = "IN PROGRESS" and epic = 3 OR NOT (label = Backend) AND points >= 5"#).unwrap();
+        assert_eq!(query, Query::Or(
+            Box::new(Query::And(
+                Box::new(compare(Field::Status, Op::Eq, Value::Status(Status::InProgress))),
+                Box::new(compare(Field::Epic, Op::Eq, Value::Number(3))),
+            )),
+            Box::new(Query::And(
+                Box::new(Query::Not(Box::new(compare(Field::Label, Op::Eq, Value::Text("backend".to_owned()))))),
+                Box::new(compare(Field::Points, Op::Ge, Value::Number(5))),
+            )),
+        ));
+
+        let query = Query::parse(r#"assignee IN (alice, "bob \"b\"", EMPTY)"#).unwrap();
+        assert_eq!(query, Query::Compare { field: Field::Assignee, op: Op::In, values: vec![Value::Text("alice".to_owned()), Value::Text("bob \"b\"".to_owned()), Value::Empty] });
+    }
+
+    #[test]
+    fn parse_should_report_errors_with_columns() {
+        let error = |input: &str| Query::parse(input).unwrap_err();
+
+        assert_eq!(error("status = done"), ParseError { column: 10, message: "unknown status \"done\" (expected OPEN, IN PROGRESS, RESOLVED or CLOSED)".to_owned() });
+        assert_eq!(error("epic = 3 AND").to_string(), "column 13: expected a field name, found the end of the query");
+        assert_eq!(error("owner = alice").column, 1);
+        assert_eq!(error("status > OPEN"), ParseError { column: 8, message: "status can't be compared with >".to_owned() });
+        assert_eq!(error("name = \"open").column, 8);
+        assert_eq!(error("epic = 3 epic = 4").message, "expected AND or OR before \"epic\"");
+        assert_eq!(error("(epic = 3").column, 10);
+        assert_eq!(error("epic = 3)").message, "unmatched \")\"");
+        assert_eq!(error("points ! 3").column, 8);
+        assert_eq!(error("points < EMPTY").column, 10);
+        assert_eq!(error("status = EMPTY").message, "status is never empty");
+        assert_eq!(error("épic = 1").column, 1); //columns count characters, not bytes
+        assert_eq!(error("name = é AND id = x").column, 19);
+    }
+
+    #[test]
+    fn stories_should_return_matching_stories_outside_the_trash() {
+        let mut db_state = DBState { last_item_id: 5, ..Default::default() };
+        let mut epic = Epic::new("".to_owned(), "".to_owned());
+        epic.stories = vec![2, 3, 4];
+        db_state.epics.insert(1, epic);
+        let mut story = Story::new("Login page".to_owned(), "".to_owned());
+        story.status = Status::InProgress;
+        story.labels.insert("backend".to_owned());
+        story.points = Some(5);
+        db_state.stories.insert(2, story);
+        let mut story = Story::new("Logout".to_owned(), "".to_owned());
+        story.assignee = Some("Alice".to_owned());
+        story.priority = Priority::High;
+        db_state.stories.insert(3, story);
+        let mut trashed = Story::new("Login".to_owned(), "".to_owned());
+        trashed.archived = true;
+        db_state.stories.insert(4, trashed);
+        db_state.stories.insert(5, Story::new("orphan".to_owned(), "".to_owned()));
+
+        let stories = |input: &str| Query::parse(input).unwrap().stories(&db_state);
+        assert_eq!(stories(r#"status = "in progress" AND epic = 1 AND label = backend"#), vec![2]);
+        assert_eq!(stories("name ~ LOG"), vec![2, 3]);
+        assert_eq!(stories("assignee = alice OR points > 3"), vec![2, 3]);
+        assert_eq!(stories("assignee = EMPTY"), vec![2, 5]);
+        assert_eq!(stories("priority >= high"), vec![3]);
+        assert_eq!(stories("label != backend AND NOT epic IN (2, 3)"), vec![3, 5]);
+        assert_eq!(stories("epic = EMPTY OR id < 3"), vec![2, 5]);
+    }
+}
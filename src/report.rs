@@ -0,0 +1,183 @@
+use std::fmt::Write;
+
+use anyhow::{anyhow, Result};
+
+use crate::models::{DBState, Epic, Status, Story};
+
+#[derive(clap::ValueEnum, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ReportFormat {
+    Markdown,
+    Html, //one self-contained file, styles inlined
+}
+
+// An epic with its stories split into one group per status, in the order the epic lists them
+struct EpicReport<'a> {
+    epic_id: u32,
+    epic: &'a Epic,
+    groups: Vec<(Status, Vec<(u32, &'a Story)>)>,
+}
+
+impl<'a> EpicReport<'a> {
+    fn new(db_state: &'a DBState, epic_id: u32) -> Result<Self> {
+        let epic = db_state.epics.get(&epic_id).filter(|epic| !epic.archived).ok_or_else(|| anyhow!("could not find epic {}!", epic_id))?;
+        let groups = Status::ALL.iter().map(|status| {
+            let stories = db_state.epic_stories(epic_id).into_iter() //missing and trashed stories are skipped like on EpicDetail
+                .filter(|(_, story)| &story.status == status)
+                .collect();
+            (status.clone(), stories)
+        }).collect();
+        Ok(Self { epic_id, epic, groups })
+    }
+
+    fn story_count(&self) -> usize {
+        self.groups.iter().map(|(_, stories)| stories.len()).sum()
+    }
+}
+
+// Renders the epic, its description and its stories grouped by status, with a count per group
+pub fn epic_report(db_state: &DBState, epic_id: u32, format: ReportFormat) -> Result<String> {
+    let report = EpicReport::new(db_state, epic_id)?;
+    Ok(match format {
+        ReportFormat::Markdown => markdown(&report),
+        ReportFormat::Html => html(&report),
+    })
+}
+
+// Backslash-escapes Markdown punctuation and turns the HTML metacharacters into entities, so user text renders as typed
+fn escape_markdown(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '\\' | '`' | '*' | '_' | '{' | '}' | '[' | ']' | '(' | ')' | '#' | '+' | '-' | '.' | '!' | '|' | '~' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn markdown(report: &EpicReport) -> String {
+    let cell = |text: &str| escape_markdown(text).replace('\n', "<br>"); //keeps table rows on one line
+    let mut out = String::new();
+
+    writeln!(out, "# {} (epic {})\n", escape_markdown(&report.epic.name), report.epic_id).unwrap();
+    if !report.epic.description.is_empty() {
+        writeln!(out, "{}\n", escape_markdown(&report.epic.description)).unwrap();
+    }
+    writeln!(out, "**Status:** {} | **Stories:** {}", report.epic.status, report.story_count()).unwrap();
+
+    for (status, stories) in &report.groups {
+        writeln!(out, "\n## {} ({})\n", status, stories.len()).unwrap();
+        if stories.is_empty() {
+            writeln!(out, "_No stories._").unwrap();
+            continue;
+        }
+        writeln!(out, "| id | name | description |\n| --- | --- | --- |").unwrap();
+        for (id, story) in stories {
+            writeln!(out, "| {} | {} | {} |", id, cell(&story.name), cell(&story.description)).unwrap();
+        }
+    }
+    out
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+fn html(report: &EpicReport) -> String {
+    let title = format!("{} (epic {})", escape_html(&report.epic.name), report.epic_id);
+    let mut out = String::new();
+
+    writeln!(out, "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>", title).unwrap();
+    writeln!(out, "<style>\nbody {{ font-family: sans-serif; margin: 2em; }}\ntable {{ border-collapse: collapse; width: 100%; }}\nth, td {{ border: 1px solid #ccc; padding: 4px 8px; text-align: left; vertical-align: top; }}\ntd.description {{ white-space: pre-wrap; }}\n</style>\n</head>\n<body>").unwrap();
+    writeln!(out, "<h1>{}</h1>", title).unwrap();
+    if !report.epic.description.is_empty() {
+        writeln!(out, "<p>{}</p>", escape_html(&report.epic.description).replace('\n', "<br>")).unwrap();
+    }
+    writeln!(out, "<p><strong>Status:</strong> {} | <strong>Stories:</strong> {}</p>", report.epic.status, report.story_count()).unwrap();
+
+    for (status, stories) in &report.groups {
+        writeln!(out, "<h2>{} ({})</h2>", status, stories.len()).unwrap();
+        if stories.is_empty() {
+            writeln!(out, "<p><em>No stories.</em></p>").unwrap();
+            continue;
+        }
+        writeln!(out, "<table>\n<tr><th>id</th><th>name</th><th>description</th></tr>").unwrap();
+        for (id, story) in stories {
+            writeln!(out, "<tr><td>{}</td><td>{}</td><td class=\"description\">{}</td></tr>", id, escape_html(&story.name), escape_html(&story.description)).unwrap();
+        }
+        writeln!(out, "</table>").unwrap();
+    }
+    writeln!(out, "</body>\n</html>").unwrap();
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn db_state() -> DBState {
+        let mut db_state = DBState { last_item_id: 4, ..Default::default() };
+        let mut epic = Epic::new("Checkout".to_owned(), "Card & wallet payments".to_owned());
+        epic.stories = vec![4, 2, 3, 999];
+        db_state.epics.insert(1, epic);
+        let mut closed = Story::new("Visa *debit* [beta]".to_owned(), "# not a heading_".to_owned());
+        closed.status = Status::Closed;
+        db_state.stories.insert(2, closed);
+        db_state.stories.insert(3, Story::new("Pipe | in name".to_owned(), "line 1\nline 2".to_owned()));
+        db_state.stories.insert(4, Story::new("<script>".to_owned(), "".to_owned()));
+        db_state
+    }
+
+    #[test]
+    fn markdown_report_should_group_stories_by_status_and_escape_user_text() {
+        let report = epic_report(&db_state(), 1, ReportFormat::Markdown).unwrap();
+
+        assert_eq!(report, "# Checkout (epic 1)
+
+Card &amp; wallet payments
+
+**Status:** OPEN | **Stories:** 3
+
+## OPEN (2)
+
+| id | name | description |
+| --- | --- | --- |
+| 4 | &lt;script&gt; |  |
+| 3 | Pipe \\| in name | line 1<br>line 2 |
+
+## IN PROGRESS (0)
+
+_No stories._
+
+## RESOLVED (0)
+
+_No stories._
+
+## CLOSED (1)
+
+| id | name | description |
+| --- | --- | --- |
+| 2 | Visa \\*debit\\* \\[beta\\] | \\# not a heading\\_ |
+");
+    }
+
+    #[test]
+    fn html_report_should_escape_and_count() {
+        let report = epic_report(&db_state(), 1, ReportFormat::Html).unwrap();
+
+        assert!(report.starts_with("<!DOCTYPE html>"));
+        assert!(report.contains("<p>Card &amp; wallet payments</p>"));
+        assert!(report.contains("<h2>OPEN (2)</h2>"));
+        assert!(report.contains("<h2>CLOSED (1)</h2>"));
+        assert!(report.contains("<td>&lt;script&gt;</td>"));
+        assert!(!report.contains("<script>"));
+
+        assert!(epic_report(&db_state(), 999, ReportFormat::Html).is_err());
+    }
+}
@@ -0,0 +1,119 @@
+use std::cmp::Reverse;
+
+use itertools::Itertools;
+
+use crate::models::DBState;
+
+// How much a query word counts for, by the best field it is found in
+const NAME_WEIGHT: u32 = 3;
+const DESCRIPTION_WEIGHT: u32 = 2;
+const COMMENT_WEIGHT: u32 = 1;
+const PHRASE_BONUS: u32 = 3; //the whole query appears in the name as typed, e.g. "login page"
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct SearchHit {
+    pub epic_id: u32, //the epic itself, or the epic the story belongs to
+    pub story_id: Option<u32>, //None when the hit is the epic
+    pub name: String,
+    pub score: u32,
+    pub matched: &'static str, //the best field any query word was found in: "name", "description" or "comment"
+}
+
+impl SearchHit {
+    pub fn item_id(&self) -> u32 {
+        self.story_id.unwrap_or(self.epic_id)
+    }
+}
+
+// Every epic and story (not in the trash) whose name, description or comments contain all words of the query,
+// ignoring case. Best matches first, ties sorted by id
+pub fn search(db_state: &DBState, query: &str) -> Vec<SearchHit> {
+    let words: Vec<String> = query.split_whitespace().map(str::to_lowercase).collect();
+    if words.is_empty() {
+        return vec![];
+    }
+    let phrase = words.join(" ");
+
+    let live_epics = db_state.epics.iter().filter(|(_, epic)| !epic.archived);
+    let mut hits = vec![];
+    for (epic_id, epic) in live_epics {
+        let items = std::iter::once((*epic_id, None, &epic.name, &epic.description))
+            .chain(db_state.epic_stories(*epic_id).into_iter().map(|(story_id, story)| (story_id, Some(story_id), &story.name, &story.description)));
+        for (item_id, story_id, name, description) in items {
+            let name_lower = name.to_lowercase();
+            let description = description.to_lowercase();
+            let comments = db_state.comments_on(item_id).iter().map(|comment| comment.body.to_lowercase()).join("\n");
+
+            let mut score = 0;
+            let mut best = 0;
+            for word in &words {
+                let weight = [(NAME_WEIGHT, &name_lower), (DESCRIPTION_WEIGHT, &description), (COMMENT_WEIGHT, &comments)].into_iter()
+                    .find(|(_, text)| text.contains(word.as_str()))
+                    .map_or(0, |(weight, _)| weight);
+                if weight == 0 { //every word has to match somewhere
+                    score = 0;
+                    break;
+                }
+                score += weight;
+                best = best.max(weight);
+            }
+            if score == 0 {
+                continue;
+            }
+            if name_lower.contains(&phrase) {
+                score += PHRASE_BONUS;
+            }
+            let matched = match best {
+                NAME_WEIGHT => "name",
+                DESCRIPTION_WEIGHT => "description",
+                _ => "comment",
+            };
+            hits.push(SearchHit { epic_id: *epic_id, story_id, name: name.clone(), score, matched });
+        }
+    }
+    hits.sort_by_key(|hit| (Reverse(hit.score), hit.item_id()));
+    hits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Comment, Epic, Story};
+
+    fn board() -> DBState {
+        let mut db_state = DBState { last_item_id: 0, ..Default::default() };
+        let mut epic = Epic::new("Login".to_owned(), "everything about signing in".to_owned());
+        epic.stories = vec![2, 3, 4];
+        db_state.epics.insert(1, epic);
+        db_state.stories.insert(2, Story::new("Reset password".to_owned(), "from the login page".to_owned()));
+        db_state.stories.insert(3, Story::new("Login page layout".to_owned(), "".to_owned()));
+        let mut trashed = Story::new("Old login page".to_owned(), "".to_owned());
+        trashed.archived = true;
+        db_state.stories.insert(4, trashed);
+        db_state.comments.push(Comment { item_id: 1, at: chrono::Utc::now(), author: "alice".to_owned(), body: "Blocked on the PASSWORD policy".to_owned() });
+        db_state.last_item_id = 4;
+        db_state
+    }
+
+    #[test]
+    fn search_should_rank_name_over_description_over_comments() {
+        let db_state = board();
+
+        let hits = search(&db_state, "login PAGE");
+        assert_eq!(hits.iter().map(|hit| (hit.item_id(), hit.score)).collect::<Vec<_>>(), vec![(3, 9), (2, 4)]);
+        assert_eq!((hits[0].epic_id, hits[0].story_id, hits[0].matched), (1, Some(3), "name"));
+        assert_eq!(hits[1].matched, "description");
+
+        let hits = search(&db_state, "password");
+        assert_eq!(hits.iter().map(|hit| (hit.item_id(), hit.matched)).collect::<Vec<_>>(), vec![(2, "name"), (1, "comment")]);
+        assert_eq!(hits[1].story_id, None);
+    }
+
+    #[test]
+    fn search_should_need_every_word_and_skip_the_trash() {
+        let db_state = board();
+        assert!(search(&db_state, "login nowhere").is_empty());
+        assert!(search(&db_state, "old").is_empty());
+        assert!(search(&db_state, "   ").is_empty());
+    }
+}
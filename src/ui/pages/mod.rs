@@ -6,7 +6,9 @@ use anyhow::Result;
 use anyhow::anyhow;
 
 use crate::db::JiraDatabase;
-use crate::models::Action;
+use crate::models::{Action, DBState, Status};
+use crate::query::Query;
+use crate::search::{search, SearchHit};
 
 mod page_helpers;
 use page_helpers::*;
@@ -14,22 +16,37 @@ use page_helpers::*;
 pub trait Page {
     fn draw_page(&self) -> Result<()>;
     fn handle_input(&self, input: &str) -> Result<Option<Action>>;
+    #[allow(dead_code)] // only used by the tests to downcast the current page
     fn as_any(&self) -> &dyn Any;//Any itself can be used to get a TypeId; &dyn Any (a borrowed trait object), it has the is and downcast_ref methods, to test if the contained value is of a given type, and to get a reference to the inner value as a type.
 }
 
 pub struct HomePage {
     pub db: Rc<JiraDatabase>,
+    pub label: Option<String>, //only list epics with this label, set by Action::FilterByLabel
+}
+
+impl HomePage {
+    fn epic_ids(&self, db_state: &DBState) -> Vec<u32> { //sorted by id; deleted epics are listed on the trash page instead
+        let is_live = |id: &u32| db_state.epics.get(id).is_some_and(|epic| !epic.archived);
+        match &self.label {
+            Some(label) => db_state.labelled(label).into_iter().filter(is_live).collect(), //the index is already sorted
+            None => db_state.epics.keys().copied().filter(is_live).sorted().collect(),
+        }
+    }
 }
 
 impl Page for HomePage {
     fn draw_page(&self) -> Result<()> {
         println!("----------------------------- EPICS -----------------------------");
+        if let Some(label) = &self.label {
+            println!("label: {}", label);
+        }
         println!("     id     |               name               |      status      ");
 
         //TODO: print out epics column contents using get_column_string(). Also make sure epics are sorted by id
-        let epics = self.db.read_db()?.epics; //make a copy of epics field from DBState and assign it to the variable 'epics'(remember, DBState is a Struct, and epics is a Hashmap)
-        for id in epics.keys().sorted() { //sorted function comes from IterTools module; sorts epics by value of index, which is the 'key' of the epics Hashmap
-            let epic = &epics[id];//brackets indicate you are looking for a particular index in the hashmap held in 'epics', which is a copy of DBState::epics
+        let db_state = self.db.read_db()?;
+        for id in self.epic_ids(&db_state) {
+            let epic = &db_state.epics[&id];
             let id_col = get_column_string(&id.to_string(),11);//11 = twelve space inside id column header (remember: indexes start at zero, so length of 11 = 12 distinct spaces)
             let name_col = get_column_string(&epic.name, 32);
             let status_col = get_column_string(&epic.status.to_string(), 17);
@@ -39,20 +56,25 @@ impl Page for HomePage {
         println!();
         println!();
 
-        println!("[q] quit | [c] create epic | [:id:] navigate to epic");
+        println!("[q] quit | [c] create epic | [m] my stories | [t] trash | [/] search | [j] filter stories | [v] saved filters | [f] filter by label | [z] undo | [y] redo | [:id:] navigate to epic");
         
         Ok(())
     }
 
     fn handle_input(&self, input: &str) -> Result<Option<Action>> {
         //todo!() // match against the user input and return the corresponding action. If the user input was invalid return None
-        let epics = self.db.read_db()?.epics;//make a copy of the 'epics' field from the DB State struct. 'epics' is a hashmap of indexed Epics.
         match input {//these are the letters at bottom of the homepage that correspond to fields in Action Enums in 'models.rs'
             "q" => Ok(Some(Action::Exit)),//Returns 'Ok' because function return type is a Result that could return an Option representing an Action enum variant
             "c" => Ok(Some(Action::CreateEpic)),
+            "m" => Ok(Some(Action::NavigateToMyStories)),
+            "t" => Ok(Some(Action::NavigateToTrash)),
+            "/" => Ok(Some(Action::Search)),
+            "j" => Ok(Some(Action::QueryStories)),
+            "v" => Ok(Some(Action::NavigateToSavedFilters)),
+            "f" => Ok(Some(Action::FilterByLabel {epic_id: None})),
             input => {
                 if let Ok(epic_id) = input.parse::<u32>() {//parse function parses string into another type. With the tubrofish operator, we tell the compiler expect a u32 to be assigned to Ok(epic_id) action
-                    if epics.contains_key(&epic_id) { //function from std Hashmap module, returns True if key is found within Hashmap, in this case, the entered epic_id by the user
+                    if self.epic_ids(&self.db.read_db()?).contains(&epic_id) { //only the epics listed on this page
                         return Ok(Some(Action::NavigateToEpicDetail {epic_id}));
                     }
                 }
@@ -68,7 +90,20 @@ impl Page for HomePage {
 
 pub struct EpicDetail {
     pub epic_id: u32,
-    pub db: Rc<JiraDatabase>
+    pub db: Rc<JiraDatabase>,
+    pub label: Option<String>, //only list stories with this label, set by Action::FilterByLabel
+}
+
+impl EpicDetail {
+    // Most urgent first, ties sorted by id. Skips dangling story ids (DBState::validate reports these) and the trash
+    fn story_ids(&self, db_state: &DBState) -> Vec<u32> {
+        let labelled = self.label.as_ref().map(|label| db_state.labelled(label));
+        db_state.epic_stories(self.epic_id).into_iter()
+            .filter(|(id, _)| labelled.as_ref().is_none_or(|labelled| labelled.contains(id)))
+            .sorted_by_key(|(id, story)| (std::cmp::Reverse(story.priority), *id))
+            .map(|(id, _)| id)
+            .collect()
+    }
 }
 
 impl Page for EpicDetail {
@@ -77,33 +112,47 @@ impl Page for EpicDetail {
         let epic = db_state.epics.get(&self.epic_id).ok_or_else(|| anyhow!("could not find epic!"))?;//epic=temp variable to save epic from copy of db_state
 
         println!("------------------------------ EPIC ------------------------------");
-        println!("  id  |     name     |         description         |    status    ");
+        println!("  id  |     name     |         description         |    status    |    owner    ");
 
         //Todo: print out epiocs using get_column_string()
         let id_col = get_column_string(&self.epic_id.to_string(), 5); //&self=EpicDetail struct, 5=width of possible epic id, up to 99,998
         let name_col = get_column_string(&epic.name, 12);//&epic = temp variable to hold epic from db_state variable, which is a reference count of JiraDatabase, a Database trait object
         let desc_col = get_column_string(&epic.description, 27);//'Description' field of Epic struct is a String, and can be printed because String have the Display trait
         let status_col = get_column_string(&epic.status.to_string(), 13);//Status is an Enum, so each variant is converted to a String, which has the Display trait, using to_owned()
-        println!("{} | {} | {} | {}", id_col, name_col, desc_col, status_col);
+        let owner_col = get_column_string(epic.owner.as_deref().unwrap_or(""), 12);
+        println!("{} | {} | {} | {} | {}", id_col, name_col, desc_col, status_col, owner_col);
+
+        let points_by_status = db_state.points_by_status(self.epic_id);
+        let total_points: u32 = points_by_status.iter().map(|(_, points)| points).sum();
+        let remaining_points: u32 = points_by_status.iter().filter(|(status, _)| matches!(status, Status::Open | Status::InProgress)).map(|(_, points)| points).sum();
+        println!("points: {} total | {} remaining", total_points, remaining_points);
+        println!("created: {} | updated: {}", format_time(epic.created_at), format_time(epic.updated_at));
+        println!("labels: {}", format_labels(&epic.labels));
 
         println!();
 
         println!("---------------------------- STORIES ----------------------------");
-        println!("     id     |               name               |      status      ");        
-        //Todo: print out stories using get_column_string(). ALso make sure to sort stories by id
-        let stories = &db_state.stories;
-        for id in epic.stories.iter().sorted() {
-            let story = &stories[id];
-            let id_col = get_column_string(&id.to_string(), 11);
-            let name_col = get_column_string(&story.name, 32);
-            let status_col = get_column_string(&story.status.to_string(), 17);
-            println!("{} | {} | {}", id_col, name_col, status_col);
+        if let Some(label) = &self.label {
+            println!("label: {}", label);
         }
-        
+        println!("  id   |           name           |     status    |  priority | points |   assignee  ");
+        //Todo: print out stories using get_column_string()
+        for id in self.story_ids(&db_state) {
+            let story = &db_state.stories[&id];
+            let id_col = get_column_string(&id.to_string(), 6);
+            let name_col = get_column_string(&story.name, 24);
+            let status_col = get_column_string(&story.status.to_string(), 13);
+            let priority_col = get_column_string(&story.priority.to_string(), 9);
+            let points_col = get_column_string(&story.points.map(|points| points.to_string()).unwrap_or_default(), 6);
+            let assignee_col = get_column_string(story.assignee.as_deref().unwrap_or(""), 12);
+            println!("{} | {} | {} | {} | {} | {}", id_col, name_col, status_col, priority_col, points_col, assignee_col);
+        }
+
         println!();
+        println!("points by status: {}", points_by_status.iter().map(|(status, points)| format!("{} {}", status, points)).join(" | "));
         println!();
 
-        println!("[p] previous | [u] update epic | [c] create story | [:id:] navigate to story");
+        println!("[p] previous | [u] update epic | [e] edit epic | [o] set owner | [l] add label | [r] remove label | [f] filter by label | [h] history | [c] create story | [z] undo | [y] redo | [:id:] navigate to story");
 
         Ok(())
     }
@@ -111,16 +160,22 @@ impl Page for EpicDetail {
     fn handle_input(&self, input: &str) -> Result<Option<Action>> {
         //todo!()//match against the user input and return the corresponding action. If the user input was invalid, return None
         let db_state = self.db.read_db()?;//make a copy of the 'stories' field from the DB State struct. 'stories' is a hashmap of indexed Epics; ? propagates DBState, or an Error, not a Result type
-        let stories = db_state.stories;//therefor, if DBState is returned in the last line, and not a Result type, you can reference 'stories' field of DBState struct
+        let story_ids = self.story_ids(&db_state);//only the stories listed on the page, so another epic's story can't be opened from here
 
         match input {
             "p" => Ok(Some(Action::NavigateToPreviousPage)),
             "u" => Ok(Some(Action::UpdateEpicStatus {epic_id: self.epic_id})),
+            "e" => Ok(Some(Action::EditEpic {epic_id: self.epic_id})),
+            "o" => Ok(Some(Action::SetEpicOwner {epic_id: self.epic_id})),
+            "l" => Ok(Some(Action::AddLabel {item_id: self.epic_id})),
+            "r" => Ok(Some(Action::RemoveLabel {item_id: self.epic_id})),
+            "f" => Ok(Some(Action::FilterByLabel {epic_id: Some(self.epic_id)})),
+            "h" => Ok(Some(Action::NavigateToHistory {item_id: self.epic_id})),
             "d" => Ok(Some(Action::DeleteEpic {epic_id: self.epic_id})),
             "c" => Ok(Some(Action::CreateStory {epic_id: self.epic_id})),
             input => {
                 if let Ok(story_id) = input.parse::<u32>() {//if the input is a number, match to the 'stories' Vector in the Epic struct
-                    if stories.contains_key(&story_id) {// go to next step if there is a match between input number and 'stories' Vector value
+                    if story_ids.contains(&story_id) {// go to next step if there is a match between input number and 'stories' Vector value
                         return Ok(Some(Action::NavigateToStoryDetail {epic_id: self.epic_id, story_id}));
                     }
                 }
@@ -155,11 +210,26 @@ impl Page for StoryDetail {
         let status_col = get_column_string(&story.status.to_string(),13);
         println!("{} | {} | {} | {}", id_col, name_col, desc_col, status_col);
 
+        println!();
+        println!("assignee: {} | reporter: {}", story.assignee.as_deref().unwrap_or("-"), story.reporter.as_deref().unwrap_or("-"));
+        println!("priority: {} | points: {}", story.priority, story.points.map(|points| points.to_string()).unwrap_or_else(|| "-".to_owned()));
+        println!("created: {} | updated: {}", format_time(story.created_at), format_time(story.updated_at));
+        println!("labels: {}", format_labels(&story.labels));
+
+        println!();
+
+        println!("---------------------------- COMMENTS ----------------------------");
+        for comment in db_state.comments_on(self.story_id) {
+            println!("{} | {}", format_time(Some(comment.at)), comment.author);
+            for line in comment.body.lines() {
+                println!("    {}", line);
+            }
+        }
 
         println!();
         println!();
 
-        println!("[p] previous | [u] update story | [d] delete story");
+        println!("[p] previous | [u] update story | [e] edit story | [a] assign story | [s] estimate story | [m] move story | [l] add label | [r] remove label | [c] comment | [h] history | [d] delete story | [z] undo | [y] redo");
 
         Ok(())
     }
@@ -169,7 +239,15 @@ impl Page for StoryDetail {
         match input {
             "p" => Ok(Some(Action::NavigateToPreviousPage)),
             "u" => Ok(Some(Action::UpdateStoryStatus {story_id: self.story_id})),//self = StoryDetail struct
+            "e" => Ok(Some(Action::EditStory {story_id: self.story_id})),
+            "a" => Ok(Some(Action::AssignStory {story_id: self.story_id})),
+            "s" => Ok(Some(Action::EstimateStory {story_id: self.story_id})),
+            "l" => Ok(Some(Action::AddLabel {item_id: self.story_id})),
+            "r" => Ok(Some(Action::RemoveLabel {item_id: self.story_id})),
+            "c" => Ok(Some(Action::AddComment {item_id: self.story_id})),
+            "h" => Ok(Some(Action::NavigateToHistory {item_id: self.story_id})),
             "d" => Ok(Some(Action::DeleteStory {epic_id: self.epic_id, story_id: self.story_id})),
+            "m" => Ok(Some(Action::MoveStory {epic_id: self.epic_id, story_id: self.story_id})),
             _ => { Ok(None) }
         }
     }
@@ -177,11 +255,332 @@ impl Page for StoryDetail {
     fn as_any(&self) -> &dyn Any { self}
 }
 
+// The audit log of one epic or story, oldest change first
+pub struct HistoryPage {
+    pub item_id: u32,
+    pub db: Rc<JiraDatabase>
+}
+
+impl Page for HistoryPage {
+    fn draw_page(&self) -> Result<()> {
+        let history = self.db.read_db()?.history;
+
+        println!("---------------------------- HISTORY OF {} ----------------------------", self.item_id);
+        println!("       when       |     who     |    field    |               change               ");
+
+        for entry in history.iter().filter(|entry| entry.item_id == self.item_id) {
+            let when_col = get_column_string(&format_time(Some(entry.at)), 17);
+            let who_col = get_column_string(entry.user.as_deref().unwrap_or("-"), 11);
+            let field_col = get_column_string(&entry.field, 11);
+            let change_col = get_column_string(&format!("{} -> {}", entry.old, entry.new), 35);
+            println!("{} | {} | {} | {}", when_col, who_col, field_col, change_col);
+        }
+
+        println!();
+        println!();
+
+        println!("[p] previous");
+
+        Ok(())
+    }
+
+    fn handle_input(&self, input: &str) -> Result<Option<Action>> {
+        match input {
+            "p" => Ok(Some(Action::NavigateToPreviousPage)),
+            _ => Ok(None),
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+// Every story assigned to the current user, across all epics
+pub struct MyStories {
+    pub user: String,
+    pub db: Rc<JiraDatabase>
+}
+
+impl MyStories {
+    fn story_ids(&self) -> Result<Vec<(u32, u32)>> { //(epic id, story id) pairs, sorted by story id
+        let db_state = self.db.read_db()?;
+        Ok(db_state.epics.iter()
+            .flat_map(|(epic_id, epic)| epic.stories.iter().map(move |story_id| (*epic_id, *story_id)))
+            .filter(|(_, story_id)| db_state.stories.get(story_id).is_some_and(|story| !story.archived && story.assignee.as_deref() == Some(self.user.as_str())))
+            .sorted_by_key(|(_, story_id)| *story_id)
+            .collect())
+    }
+}
+
+impl Page for MyStories {
+    fn draw_page(&self) -> Result<()> {
+        let stories = self.db.read_db()?.stories;
+
+        println!("-------------------------- MY STORIES ({}) --------------------------", self.user);
+        println!("     id     |               name               |      status      |  epic  ");
+
+        for (epic_id, story_id) in self.story_ids()? {
+            let story = &stories[&story_id];
+            let id_col = get_column_string(&story_id.to_string(), 11);
+            let name_col = get_column_string(&story.name, 32);
+            let status_col = get_column_string(&story.status.to_string(), 17);
+            println!("{} | {} | {} | {}", id_col, name_col, status_col, epic_id);
+        }
+
+        println!();
+        println!();
+
+        println!("[p] previous | [:id:] navigate to story");
+
+        Ok(())
+    }
+
+    fn handle_input(&self, input: &str) -> Result<Option<Action>> {
+        match input {
+            "p" => Ok(Some(Action::NavigateToPreviousPage)),
+            input => {
+                if let Ok(story_id) = input.parse::<u32>() { //only the stories listed on this page
+                    if let Some((epic_id, story_id)) = self.story_ids()?.into_iter().find(|(_, id)| *id == story_id) {
+                        return Ok(Some(Action::NavigateToStoryDetail {epic_id, story_id}));
+                    }
+                }
+                Ok(None)
+            }
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+// Epics and stories matching a search, best match first
+pub struct SearchPage {
+    pub query: String,
+    pub db: Rc<JiraDatabase>
+}
+
+impl SearchPage {
+    fn hits(&self) -> Result<Vec<SearchHit>> {
+        Ok(search(&self.db.read_db()?, &self.query))
+    }
+}
+
+impl Page for SearchPage {
+    fn draw_page(&self) -> Result<()> {
+        println!("------------------------- SEARCH: {} -------------------------", self.query);
+        println!("     id     |               name               |   kind   |  epic  |   matched   ");
+
+        for hit in self.hits()? {
+            let id_col = get_column_string(&hit.item_id().to_string(), 11);
+            let name_col = get_column_string(&hit.name, 32);
+            let kind_col = get_column_string(if hit.story_id.is_some() { "story" } else { "epic" }, 8);
+            let epic_col = get_column_string(&hit.epic_id.to_string(), 6);
+            println!("{} | {} | {} | {} | {}", id_col, name_col, kind_col, epic_col, hit.matched);
+        }
+
+        println!();
+        println!();
+
+        println!("[p] previous | [:id:] open result");
+
+        Ok(())
+    }
+
+    fn handle_input(&self, input: &str) -> Result<Option<Action>> {
+        match input {
+            "p" => Ok(Some(Action::NavigateToPreviousPage)),
+            input => {
+                if let Ok(item_id) = input.parse::<u32>() { //only the results listed on this page
+                    if let Some(hit) = self.hits()?.into_iter().find(|hit| hit.item_id() == item_id) {
+                        return Ok(Some(match hit.story_id {
+                            Some(story_id) => Action::NavigateToStoryDetail {epic_id: hit.epic_id, story_id},
+                            None => Action::NavigateToEpicDetail {epic_id: hit.epic_id},
+                        }));
+                    }
+                }
+                Ok(None)
+            }
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+// The stories matching a filter written in the query language
+pub struct QueryPage {
+    pub name: Option<String>, //set when the filter was opened from SavedFiltersPage
+    pub source: String, //the filter as typed
+    pub query: Query,
+    pub db: Rc<JiraDatabase>
+}
+
+impl QueryPage {
+    fn story_ids(&self) -> Result<Vec<(u32, u32)>> { //(epic id, story id) pairs, sorted by story id; stories without an epic can't be opened
+        let db_state = self.db.read_db()?;
+        Ok(self.query.stories(&db_state).into_iter().filter_map(|story_id| Some((db_state.epic_of(story_id)?, story_id))).collect())
+    }
+}
+
+impl Page for QueryPage {
+    fn draw_page(&self) -> Result<()> {
+        let stories = self.db.read_db()?.stories;
+
+        match &self.name {
+            Some(name) => println!("------------------------- {} -------------------------\n{}", name, self.source),
+            None => println!("------------------------- FILTER: {} -------------------------", self.source),
+        }
+        println!("     id     |               name               |      status      |  epic  ");
+
+        for (epic_id, story_id) in self.story_ids()? {
+            let story = &stories[&story_id];
+            let id_col = get_column_string(&story_id.to_string(), 11);
+            let name_col = get_column_string(&story.name, 32);
+            let status_col = get_column_string(&story.status.to_string(), 17);
+            println!("{} | {} | {} | {}", id_col, name_col, status_col, epic_id);
+        }
+
+        println!();
+        println!();
+
+        println!("[p] previous | [s] save filter | [:id:] navigate to story");
+
+        Ok(())
+    }
+
+    fn handle_input(&self, input: &str) -> Result<Option<Action>> {
+        match input {
+            "p" => Ok(Some(Action::NavigateToPreviousPage)),
+            "s" => Ok(Some(Action::SaveFilter {query: self.source.clone()})),
+            input => {
+                if let Ok(story_id) = input.parse::<u32>() { //only the stories listed on this page
+                    if let Some((epic_id, story_id)) = self.story_ids()?.into_iter().find(|(_, id)| *id == story_id) {
+                        return Ok(Some(Action::NavigateToStoryDetail {epic_id, story_id}));
+                    }
+                }
+                Ok(None)
+            }
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+// Every saved filter, sorted by name; each opens as a QueryPage
+pub struct SavedFiltersPage {
+    pub db: Rc<JiraDatabase>
+}
+
+impl Page for SavedFiltersPage {
+    fn draw_page(&self) -> Result<()> {
+        let saved_filters = self.db.read_db()?.saved_filters;
+
+        println!("-------------------------- SAVED FILTERS --------------------------");
+        println!("  #  |          name          |                 filter                 ");
+
+        for (number, (name, query)) in saved_filters.iter().enumerate() {
+            let number_col = get_column_string(&(number + 1).to_string(), 4);
+            let name_col = get_column_string(name, 22);
+            let query_col = get_column_string(query, 40);
+            println!("{} | {} | {}", number_col, name_col, query_col);
+        }
+
+        println!();
+        println!();
+
+        println!("[p] previous | [x] delete filter | [:#:] open filter");
+
+        Ok(())
+    }
+
+    fn handle_input(&self, input: &str) -> Result<Option<Action>> {
+        match input {
+            "p" => Ok(Some(Action::NavigateToPreviousPage)),
+            "x" => Ok(Some(Action::DeleteSavedFilter)),
+            input => {
+                if let Some(index) = input.parse::<usize>().ok().and_then(|number| number.checked_sub(1)) { //numbered from 1 in the order listed
+                    if let Some(name) = self.db.read_db()?.saved_filters.into_keys().nth(index) {
+                        return Ok(Some(Action::OpenSavedFilter {name}));
+                    }
+                }
+                Ok(None)
+            }
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+// Deleted epics and stories, until they are restored or purged
+pub struct TrashPage {
+    pub db: Rc<JiraDatabase>
+}
+
+impl Page for TrashPage {
+    fn draw_page(&self) -> Result<()> {
+        let db_state = self.db.read_db()?;
+        let (epic_ids, story_ids) = db_state.trash();
+
+        println!("------------------------------ TRASH ------------------------------");
+        println!("     id     |               name               |       kind       |  epic  ");
+
+        for epic_id in epic_ids {
+            let epic = &db_state.epics[&epic_id];
+            let id_col = get_column_string(&epic_id.to_string(), 11);
+            let name_col = get_column_string(&epic.name, 32);
+            let kind_col = get_column_string(&format!("epic ({})", epic.stories.len()), 16); //wide enough for the story count
+            println!("{} | {} | {} |", id_col, name_col, kind_col);
+        }
+        for story_id in story_ids {
+            let story = &db_state.stories[&story_id];
+            let id_col = get_column_string(&story_id.to_string(), 11);
+            let name_col = get_column_string(&story.name, 32);
+            let kind_col = get_column_string("story", 16);
+            println!("{} | {} | {} | {}", id_col, name_col, kind_col, db_state.epic_of(story_id).map(|id| id.to_string()).unwrap_or_default());
+        }
+
+        println!();
+        println!();
+
+        println!("[p] previous | [x] purge item | [z] undo | [y] redo | [:id:] restore item");
+
+        Ok(())
+    }
+
+    fn handle_input(&self, input: &str) -> Result<Option<Action>> {
+        match input {
+            "p" => Ok(Some(Action::NavigateToPreviousPage)),
+            "x" => Ok(Some(Action::PurgeItem)),
+            input => {
+                if let Ok(item_id) = input.parse::<u32>() { //only the items listed on this page
+                    let (epic_ids, story_ids) = self.db.read_db()?.trash();
+                    if epic_ids.contains(&item_id) || story_ids.contains(&item_id) {
+                        return Ok(Some(Action::RestoreItem {item_id}));
+                    }
+                }
+                Ok(None)
+            }
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::{db::test_utils::MockDB};
     use crate::models::{Epic, Story};
+    use crate::workflow::Workflow;
 
     mod home_page {
         use super::*;
@@ -189,28 +588,28 @@ mod tests {
         #[test]
         fn draw_page_should_not_throw_error() {
             let db = Rc::new(JiraDatabase 
-                {database: Box::new(MockDB::new())}
+                {database: Box::new(MockDB::new()), user: None, workflow: Workflow::default()}
             );
-            let page = HomePage {db};
+            let page = HomePage {db, label: None};
             assert_eq!(page.draw_page().is_ok(), true);
         }
 
         #[test]
         fn handle_input_should_not_throw_error() {
-            let db = Rc::new(JiraDatabase {database: Box::new(MockDB::new())});
-            let page = HomePage {db};
+            let db = Rc::new(JiraDatabase {database: Box::new(MockDB::new()), user: None, workflow: Workflow::default()});
+            let page = HomePage {db, label: None};
             assert_eq!(page.handle_input("").is_ok(), true);
         }
 
         #[test]
         fn handle_input_should_return_the_correct_actions() {
-            let db = Rc::new(JiraDatabase { database: Box::new(MockDB::new())});
+            let db = Rc::new(JiraDatabase { database: Box::new(MockDB::new()), user: None, workflow: Workflow::default()});
 
             let epic = Epic::new("".to_owned(), "".to_owned());
 
             let epic_id = db.create_epic(epic).unwrap();
             
-            let page = HomePage {db};
+            let page = HomePage {db, label: None};
 
             let q = "q";
             let c = "c";
@@ -222,6 +621,12 @@ mod tests {
 
             assert_eq!(page.handle_input(q).unwrap(), Some(Action::Exit));
             assert_eq!(page.handle_input(c).unwrap(), Some(Action::CreateEpic));
+            assert_eq!(page.handle_input("m").unwrap(), Some(Action::NavigateToMyStories));
+            assert_eq!(page.handle_input("t").unwrap(), Some(Action::NavigateToTrash));
+            assert_eq!(page.handle_input("/").unwrap(), Some(Action::Search));
+            assert_eq!(page.handle_input("j").unwrap(), Some(Action::QueryStories));
+            assert_eq!(page.handle_input("v").unwrap(), Some(Action::NavigateToSavedFilters));
+            assert_eq!(page.handle_input("f").unwrap(), Some(Action::FilterByLabel {epic_id: None}));
             assert_eq!(page.handle_input(&valid_epic_id).unwrap(), Some(Action::NavigateToEpicDetail {epic_id: 1 }));
             assert_eq!(page.handle_input(invalid_epic_id).unwrap(), None);
             assert_eq!(page.handle_input(junk_input).unwrap(), None);
@@ -229,6 +634,19 @@ mod tests {
             assert_eq!(page.handle_input(input_with_trailing_white_spaces).unwrap(), None);
 
         }
+
+        #[test]
+        fn handle_input_should_only_navigate_to_filtered_epics() {
+            let db = Rc::new(JiraDatabase {database: Box::new(MockDB::new()), user: None, workflow: Workflow::default()});
+            let tagged = db.create_epic(Epic::new("".to_owned(), "".to_owned())).unwrap();
+            let untagged = db.create_epic(Epic::new("".to_owned(), "".to_owned())).unwrap();
+            db.add_label(tagged, "backend").unwrap();
+            let page = HomePage {db, label: Some("backend".to_owned())};
+
+            assert!(page.draw_page().is_ok());
+            assert_eq!(page.handle_input(&tagged.to_string()).unwrap(), Some(Action::NavigateToEpicDetail {epic_id: tagged}));
+            assert_eq!(page.handle_input(&untagged.to_string()).unwrap(), None);
+        }
     }
 
     mod epic_detail_page {
@@ -236,33 +654,45 @@ mod tests {
 
         #[test]
         fn draw_page_should_not_throw_error() {
-            let db = Rc::new(JiraDatabase {database: Box::new(MockDB::new())});
+            let db = Rc::new(JiraDatabase {database: Box::new(MockDB::new()), user: None, workflow: Workflow::default()});
             let epic_id = db.create_epic(Epic::new("".to_owned(), "".to_owned())).unwrap();
-            let page = EpicDetail {epic_id, db};
+            let page = EpicDetail {epic_id, db, label: None};
             assert_eq!(page.draw_page().is_ok(), true);
         }
 
         #[test]
         fn handle_input_should_not_throw_error() {
-            let db = Rc::new(JiraDatabase{database: Box::new(MockDB::new())});
+            let db = Rc::new(JiraDatabase{database: Box::new(MockDB::new()), user: None, workflow: Workflow::default()});
             let epic_id = db.create_epic(Epic::new("".to_owned(), "".to_owned())).unwrap();
-            let page = EpicDetail {epic_id, db};
+            let page = EpicDetail {epic_id, db, label: None};
             assert_eq!(page.handle_input("").is_ok(), true);
         }
 
+        #[test]
+        fn draw_page_should_not_panic_on_missing_story() {
+            let db = Rc::new(JiraDatabase {database: Box::new(MockDB::new()), user: None, workflow: Workflow::default()});
+            let epic_id = db.create_epic(Epic::new("".to_owned(), "".to_owned())).unwrap();
+            let mut db_state = db.read_db().unwrap();
+            db_state.epics.get_mut(&epic_id).unwrap().stories.push(999);
+            db.database.write_db(&db_state).unwrap();
+
+            let page = EpicDetail {epic_id, db, label: None};
+            assert!(page.draw_page().is_ok());
+        }
+
         #[test]
         fn draw_page_should_throw_error_for_invalid_epic_id() {
-            let db = Rc::new(JiraDatabase{database: Box::new(MockDB::new())});
-            let page = EpicDetail{epic_id: 999, db};
+            let db = Rc::new(JiraDatabase{database: Box::new(MockDB::new()), user: None, workflow: Workflow::default()});
+            let page = EpicDetail{epic_id: 999, db, label: None};
             assert_eq!(page.draw_page().is_err(), true);
         }
 
         #[test]
         fn handle_input_should_return_the_correct_actions() {
-            let db = Rc::new(JiraDatabase{database: Box::new(MockDB::new())});
+            let db = Rc::new(JiraDatabase{database: Box::new(MockDB::new()), user: None, workflow: Workflow::default()});
             let epic_id = db.create_epic(Epic::new("".to_owned(), "".to_owned())).unwrap();
             let story_id = db.create_story(Story::new("".to_owned(), "".to_owned()), epic_id).unwrap();
-            let page = EpicDetail {epic_id, db};
+            let page = EpicDetail {epic_id, db, label: None};
 
             let p = "p";
             let u = "u";
@@ -275,6 +705,12 @@ mod tests {
 
             assert_eq!(page.handle_input(p).unwrap(), Some(Action::NavigateToPreviousPage));
             assert_eq!(page.handle_input(u).unwrap(), Some(Action::UpdateEpicStatus {epic_id: 1}));
+            assert_eq!(page.handle_input("e").unwrap(), Some(Action::EditEpic {epic_id: 1}));
+            assert_eq!(page.handle_input("o").unwrap(), Some(Action::SetEpicOwner {epic_id: 1}));
+            assert_eq!(page.handle_input("l").unwrap(), Some(Action::AddLabel {item_id: 1}));
+            assert_eq!(page.handle_input("r").unwrap(), Some(Action::RemoveLabel {item_id: 1}));
+            assert_eq!(page.handle_input("f").unwrap(), Some(Action::FilterByLabel {epic_id: Some(1)}));
+            assert_eq!(page.handle_input("h").unwrap(), Some(Action::NavigateToHistory {item_id: 1}));
             assert_eq!(page.handle_input(d).unwrap(), Some(Action::DeleteEpic {epic_id: 1}));
             assert_eq!(page.handle_input(c).unwrap(), Some(Action::CreateStory {epic_id: 1}));
             assert_eq!(page.handle_input(&story_id.to_string()).unwrap(), Some(Action::NavigateToStoryDetail{epic_id: 1, story_id: 2}));
@@ -284,6 +720,26 @@ mod tests {
             assert_eq!(page.handle_input(input_with_trailing_white_spaces).unwrap(), None);
 
         }
+
+        #[test]
+        fn handle_input_should_only_navigate_to_listed_stories() {
+            let db = Rc::new(JiraDatabase {database: Box::new(MockDB::new()), user: None, workflow: Workflow::default()});
+            let epic_id = db.create_epic(Epic::new("".to_owned(), "".to_owned())).unwrap();
+            let tagged = db.create_story(Story::new("".to_owned(), "".to_owned()), epic_id).unwrap();
+            let untagged = db.create_story(Story::new("".to_owned(), "".to_owned()), epic_id).unwrap();
+            let other_epic_id = db.create_epic(Epic::new("".to_owned(), "".to_owned())).unwrap();
+            let other_story = db.create_story(Story::new("".to_owned(), "".to_owned()), other_epic_id).unwrap();
+            db.add_label(tagged, "ui").unwrap();
+
+            let page = EpicDetail {epic_id, db: Rc::clone(&db), label: None};
+            assert_eq!(page.handle_input(&untagged.to_string()).unwrap(), Some(Action::NavigateToStoryDetail {epic_id, story_id: untagged}));
+            assert_eq!(page.handle_input(&other_story.to_string()).unwrap(), None); //belongs to another epic
+
+            let page = EpicDetail {epic_id, db, label: Some("ui".to_owned())};
+            assert!(page.draw_page().is_ok());
+            assert_eq!(page.handle_input(&tagged.to_string()).unwrap(), Some(Action::NavigateToStoryDetail {epic_id, story_id: tagged}));
+            assert_eq!(page.handle_input(&untagged.to_string()).unwrap(), None); //filtered out
+        }
     }
 
     mod story_detail_page {
@@ -291,16 +747,18 @@ mod tests {
 
         #[test]
         fn draw_page_should_not_throw_error() {
-            let db = Rc::new(JiraDatabase { database: Box::new(MockDB::new()) });
+            let db = Rc::new(JiraDatabase { database: Box::new(MockDB::new()), user: Some("alice".to_owned()), workflow: Workflow::default() });
             let epic_id = db.create_epic(Epic::new("".to_owned(), "".to_owned())).unwrap();
             let story_id = db.create_story(Story::new("".to_owned(), "".to_owned()), epic_id).unwrap();
+            db.add_comment(story_id, "first line\nsecond line").unwrap();
+            db.add_label(story_id, "backend").unwrap();
             let page = StoryDetail {epic_id, story_id, db};
             assert_eq!(page.draw_page().is_ok(), true);
         }
 
         #[test]
         fn handle_input_should_not_throw_error() {
-            let db = Rc::new(JiraDatabase {database: Box::new(MockDB::new())});
+            let db = Rc::new(JiraDatabase {database: Box::new(MockDB::new()), user: None, workflow: Workflow::default()});
             let epic_id = db.create_epic(Epic::new("".to_owned(), "".to_owned())).unwrap();
             let story_id = db.create_story(Story::new("".to_owned(), "".to_owned()), epic_id).unwrap();
             let page = StoryDetail { epic_id, story_id, db };
@@ -309,7 +767,7 @@ mod tests {
 
         #[test]
         fn draw_page_should_throw_error_for_invalid_story_id() {
-            let db = Rc::new(JiraDatabase {database: Box::new(MockDB::new())});
+            let db = Rc::new(JiraDatabase {database: Box::new(MockDB::new()), user: None, workflow: Workflow::default()});
             let epic_id = db.create_epic(Epic::new("".to_owned(), "".to_owned())).unwrap();
             let _ = db.create_story(Story::new("".to_owned(), "".to_owned()), epic_id).unwrap();
             let page = StoryDetail {epic_id, story_id:999, db};
@@ -318,7 +776,7 @@ mod tests {
 
         #[test]
         fn handle_input_should_return_the_correct_action() {
-            let db = Rc::new(JiraDatabase {database: Box::new(MockDB::new())});
+            let db = Rc::new(JiraDatabase {database: Box::new(MockDB::new()), user: None, workflow: Workflow::default()});
             let epic_id = db.create_epic(Epic::new("".to_owned(), "".to_owned())).unwrap();
             let story_id = db.create_story(Story::new("".to_owned(), "".to_owned()), epic_id).unwrap();
             let page = StoryDetail {epic_id, story_id, db};
@@ -332,11 +790,186 @@ mod tests {
 
             assert_eq!(page.handle_input(p).unwrap(), Some(Action::NavigateToPreviousPage));
             assert_eq!(page.handle_input(u).unwrap(), Some(Action::UpdateStoryStatus {story_id}));
+            assert_eq!(page.handle_input("e").unwrap(), Some(Action::EditStory {story_id}));
+            assert_eq!(page.handle_input("a").unwrap(), Some(Action::AssignStory {story_id}));
+            assert_eq!(page.handle_input("s").unwrap(), Some(Action::EstimateStory {story_id}));
+            assert_eq!(page.handle_input("l").unwrap(), Some(Action::AddLabel {item_id: story_id}));
+            assert_eq!(page.handle_input("r").unwrap(), Some(Action::RemoveLabel {item_id: story_id}));
+            assert_eq!(page.handle_input("c").unwrap(), Some(Action::AddComment {item_id: story_id}));
+            assert_eq!(page.handle_input("h").unwrap(), Some(Action::NavigateToHistory {item_id: story_id}));
             assert_eq!(page.handle_input(d).unwrap(), Some(Action::DeleteStory {epic_id, story_id}));
+            assert_eq!(page.handle_input("m").unwrap(), Some(Action::MoveStory {epic_id, story_id}));
             assert_eq!(page.handle_input(some_number).unwrap(), None);
             assert_eq!(page.handle_input(junk_input).unwrap(), None);
             assert_eq!(page.handle_input(junk_input_with_valid_prefix).unwrap(), None);
             assert_eq!(page.handle_input(input_with_trailing_white_spaces).unwrap(), None);
         }
     }
-}
\ No newline at end of file
+
+    mod history_page {
+        use super::*;
+
+        #[test]
+        fn draw_page_should_not_throw_error() {
+            let db = Rc::new(JiraDatabase {database: Box::new(MockDB::new()), user: None, workflow: Workflow::default()});
+            let epic_id = db.create_epic(Epic::new("".to_owned(), "".to_owned())).unwrap();
+            db.update_epic_status(epic_id, crate::models::Status::Closed).unwrap();
+            let page = HistoryPage {item_id: epic_id, db};
+            assert!(page.draw_page().is_ok());
+        }
+
+        #[test]
+        fn handle_input_should_return_the_correct_actions() {
+            let db = Rc::new(JiraDatabase {database: Box::new(MockDB::new()), user: None, workflow: Workflow::default()});
+            let page = HistoryPage {item_id: 1, db};
+            assert_eq!(page.handle_input("p").unwrap(), Some(Action::NavigateToPreviousPage));
+            assert_eq!(page.handle_input("1").unwrap(), None);
+        }
+    }
+
+    mod my_stories_page {
+        use super::*;
+
+        #[test]
+        fn draw_page_should_not_throw_error() {
+            let db = Rc::new(JiraDatabase {database: Box::new(MockDB::new()), user: None, workflow: Workflow::default()});
+            let epic_id = db.create_epic(Epic::new("".to_owned(), "".to_owned())).unwrap();
+            let story_id = db.create_story(Story::new("".to_owned(), "".to_owned()), epic_id).unwrap();
+            db.assign_story(story_id, Some("alice".to_owned())).unwrap();
+            let page = MyStories {user: "alice".to_owned(), db};
+            assert!(page.draw_page().is_ok());
+        }
+
+        #[test]
+        fn handle_input_should_only_navigate_to_own_stories() {
+            let db = Rc::new(JiraDatabase {database: Box::new(MockDB::new()), user: None, workflow: Workflow::default()});
+            let epic_id = db.create_epic(Epic::new("".to_owned(), "".to_owned())).unwrap();
+            let mine = db.create_story(Story::new("".to_owned(), "".to_owned()), epic_id).unwrap();
+            let theirs = db.create_story(Story::new("".to_owned(), "".to_owned()), epic_id).unwrap();
+            db.assign_story(mine, Some("alice".to_owned())).unwrap();
+            db.assign_story(theirs, Some("bob".to_owned())).unwrap();
+            let page = MyStories {user: "alice".to_owned(), db};
+
+            assert_eq!(page.handle_input("p").unwrap(), Some(Action::NavigateToPreviousPage));
+            assert_eq!(page.handle_input(&mine.to_string()).unwrap(), Some(Action::NavigateToStoryDetail {epic_id, story_id: mine}));
+            assert_eq!(page.handle_input(&theirs.to_string()).unwrap(), None);
+            assert_eq!(page.handle_input("999").unwrap(), None);
+        }
+    }
+
+    mod search_page {
+        use super::*;
+
+        #[test]
+        fn draw_page_should_not_throw_error() {
+            let db = Rc::new(JiraDatabase {database: Box::new(MockDB::new()), user: None, workflow: Workflow::default()});
+            let epic_id = db.create_epic(Epic::new("login".to_owned(), "".to_owned())).unwrap();
+            db.create_story(Story::new("login page".to_owned(), "".to_owned()), epic_id).unwrap();
+            let page = SearchPage {query: "login".to_owned(), db};
+            assert!(page.draw_page().is_ok());
+        }
+
+        #[test]
+        fn handle_input_should_open_the_selected_result() {
+            let db = Rc::new(JiraDatabase {database: Box::new(MockDB::new()), user: None, workflow: Workflow::default()});
+            let epic_id = db.create_epic(Epic::new("login".to_owned(), "".to_owned())).unwrap();
+            let story_id = db.create_story(Story::new("".to_owned(), "the login page".to_owned()), epic_id).unwrap();
+            let other_story_id = db.create_story(Story::new("".to_owned(), "".to_owned()), epic_id).unwrap();
+            let page = SearchPage {query: "login".to_owned(), db};
+
+            assert_eq!(page.handle_input("p").unwrap(), Some(Action::NavigateToPreviousPage));
+            assert_eq!(page.handle_input(&epic_id.to_string()).unwrap(), Some(Action::NavigateToEpicDetail {epic_id}));
+            assert_eq!(page.handle_input(&story_id.to_string()).unwrap(), Some(Action::NavigateToStoryDetail {epic_id, story_id}));
+            assert_eq!(page.handle_input(&other_story_id.to_string()).unwrap(), None);
+        }
+    }
+
+    mod query_page {
+        use super::*;
+
+        #[test]
+        fn draw_page_should_not_throw_error() {
+            let db = Rc::new(JiraDatabase {database: Box::new(MockDB::new()), user: None, workflow: Workflow::default()});
+            let epic_id = db.create_epic(Epic::new("".to_owned(), "".to_owned())).unwrap();
+            db.create_story(Story::new("".to_owned(), "".to_owned()), epic_id).unwrap();
+            let page = QueryPage {name: None, source: "status = open".to_owned(), query: Query::parse("status = open").unwrap(), db};
+            assert!(page.draw_page().is_ok());
+        }
+
+        #[test]
+        fn handle_input_should_only_navigate_to_matching_stories() {
+            let db = Rc::new(JiraDatabase {database: Box::new(MockDB::new()), user: None, workflow: Workflow::default()});
+            let epic_id = db.create_epic(Epic::new("".to_owned(), "".to_owned())).unwrap();
+            let open = db.create_story(Story::new("".to_owned(), "".to_owned()), epic_id).unwrap();
+            let closed = db.create_story(Story::new("".to_owned(), "".to_owned()), epic_id).unwrap();
+            db.update_story_status(closed, Status::Closed).unwrap();
+            let page = QueryPage {name: None, source: "status = open".to_owned(), query: Query::parse("status = open").unwrap(), db};
+
+            assert_eq!(page.handle_input("p").unwrap(), Some(Action::NavigateToPreviousPage));
+            assert_eq!(page.handle_input("s").unwrap(), Some(Action::SaveFilter {query: "status = open".to_owned()}));
+            assert_eq!(page.handle_input(&open.to_string()).unwrap(), Some(Action::NavigateToStoryDetail {epic_id, story_id: open}));
+            assert_eq!(page.handle_input(&closed.to_string()).unwrap(), None);
+        }
+    }
+
+    mod saved_filters_page {
+        use super::*;
+
+        #[test]
+        fn draw_page_should_not_throw_error() {
+            let db = Rc::new(JiraDatabase {database: Box::new(MockDB::new()), user: None, workflow: Workflow::default()});
+            db.save_filter("open", "status = open").unwrap();
+            let page = SavedFiltersPage {db};
+            assert!(page.draw_page().is_ok());
+        }
+
+        #[test]
+        fn handle_input_should_open_filters_by_number() {
+            let db = Rc::new(JiraDatabase {database: Box::new(MockDB::new()), user: None, workflow: Workflow::default()});
+            db.save_filter("open", "status = open").unwrap();
+            db.save_filter("backend", "label = backend").unwrap();
+            let page = SavedFiltersPage {db};
+
+            assert_eq!(page.handle_input("p").unwrap(), Some(Action::NavigateToPreviousPage));
+            assert_eq!(page.handle_input("x").unwrap(), Some(Action::DeleteSavedFilter));
+            assert_eq!(page.handle_input("1").unwrap(), Some(Action::OpenSavedFilter {name: "backend".to_owned()}));
+            assert_eq!(page.handle_input("2").unwrap(), Some(Action::OpenSavedFilter {name: "open".to_owned()}));
+            assert_eq!(page.handle_input("0").unwrap(), None);
+            assert_eq!(page.handle_input("3").unwrap(), None);
+        }
+    }
+
+    mod trash_page {
+        use super::*;
+
+        #[test]
+        fn draw_page_should_not_throw_error() {
+            let db = Rc::new(JiraDatabase {database: Box::new(MockDB::new()), user: None, workflow: Workflow::default()});
+            let epic_id = db.create_epic(Epic::new("".to_owned(), "".to_owned())).unwrap();
+            let story_id = db.create_story(Story::new("".to_owned(), "".to_owned()), epic_id).unwrap();
+            db.delete_story(epic_id, story_id).unwrap();
+            db.delete_epic(db.create_epic(Epic::new("".to_owned(), "".to_owned())).unwrap()).unwrap();
+            let page = TrashPage {db};
+            assert!(page.draw_page().is_ok());
+        }
+
+        #[test]
+        fn handle_input_should_only_restore_items_in_the_trash() {
+            let db = Rc::new(JiraDatabase {database: Box::new(MockDB::new()), user: None, workflow: Workflow::default()});
+            let epic_id = db.create_epic(Epic::new("".to_owned(), "".to_owned())).unwrap();
+            let story_id = db.create_story(Story::new("".to_owned(), "".to_owned()), epic_id).unwrap();
+            let live_story_id = db.create_story(Story::new("".to_owned(), "".to_owned()), epic_id).unwrap();
+            db.delete_story(epic_id, story_id).unwrap();
+            let page = TrashPage {db: Rc::clone(&db)};
+
+            assert_eq!(page.handle_input("p").unwrap(), Some(Action::NavigateToPreviousPage));
+            assert_eq!(page.handle_input("x").unwrap(), Some(Action::PurgeItem));
+            assert_eq!(page.handle_input(&story_id.to_string()).unwrap(), Some(Action::RestoreItem {item_id: story_id}));
+            assert_eq!(page.handle_input(&live_story_id.to_string()).unwrap(), None);
+
+            db.delete_epic(epic_id).unwrap(); //the story now goes with its epic
+            assert_eq!(page.handle_input(&story_id.to_string()).unwrap(), None);
+            assert_eq!(page.handle_input(&epic_id.to_string()).unwrap(), Some(Action::RestoreItem {item_id: epic_id}));
+        }
+    }
+}
@@ -1,4 +1,19 @@
+use std::collections::BTreeSet;
+
+use chrono::{DateTime, Local, Utc};
 use ellipse::Ellipse;
+use itertools::Itertools;
+
+pub fn format_time(time: Option<DateTime<Utc>>) -> String { //local time to the minute, "-" for items older than timestamps
+    time.map(|time| time.with_timezone(&Local).format("%Y-%m-%d %H:%M").to_string()).unwrap_or_else(|| "-".to_owned())
+}
+
+pub fn format_labels(labels: &BTreeSet<String>) -> String { //"-" when there are none
+    match labels.is_empty() {
+        true => "-".to_owned(),
+        false => labels.iter().join(", "),
+    }
+}
 
 pub fn get_column_string(text: &str, width: usize) -> String {
     //todo!() use the truncate_ellipse function from the ellipse crate
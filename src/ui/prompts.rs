@@ -1,11 +1,32 @@
-use crate::{models::{Epic, Story, Status}, io_utils::get_user_input};
+use crate::{models::{Epic, IntegrityIssue, Priority, Story, Status, RECOVERED_EPIC_NAME}, io_utils::get_user_input};
+
+pub type EditDetailsPrompt = dyn Fn(&str, &str) -> (String, String); //takes the current name and description, returns the new ones
+pub type RepairPrompt = dyn Fn(&[IntegrityIssue]) -> bool; //shows the problems found and asks whether to repair them
+pub type EstimatePrompt = dyn Fn(Priority, Option<u32>) -> (Priority, Option<u32>); //takes the current priority and points, returns the new ones
+pub type StatusPrompt = dyn Fn(&[Status]) -> Option<Status>; //offers only the given statuses, None cancels
+pub type AssignPrompt = dyn Fn(&str, Option<&str>) -> Option<Option<String>>; //takes the role and the current person, None keeps them, Some(None) clears
+pub type LabelPrompt = dyn Fn(&str, &[String]) -> Option<String>; //takes the question and the labels to suggest, None is a blank answer
 
 pub struct Prompts {
     pub create_epic: Box<dyn Fn() -> Epic>,
     pub create_story: Box<dyn Fn() -> Story>,
     pub delete_epic: Box<dyn Fn() -> bool>,
     pub delete_story: Box<dyn Fn() -> bool>,
-    pub update_status: Box<dyn Fn() ->Option<Status>>,
+    pub move_story: Box<dyn Fn() -> Option<u32>>,
+    pub purge_item: Box<dyn Fn() -> Option<u32>>,
+    pub add_comment: Box<dyn Fn() -> Option<String>>,
+    pub label: Box<LabelPrompt>,
+    pub search: Box<dyn Fn() -> Option<String>>,
+    pub query_stories: Box<dyn Fn() -> Option<String>>,
+    pub save_filter: Box<dyn Fn() -> Option<String>>,
+    pub delete_saved_filter: Box<dyn Fn() -> Option<String>>,
+    pub update_status: Box<StatusPrompt>,
+    pub edit_epic: Box<EditDetailsPrompt>,
+    pub edit_story: Box<EditDetailsPrompt>,
+    pub assign: Box<AssignPrompt>,
+    pub estimate_story: Box<EstimatePrompt>,
+    pub recover_from_backup: Box<dyn Fn() -> bool>,
+    pub repair_database: Box<RepairPrompt>,
 }
 
 impl Prompts {
@@ -15,7 +36,21 @@ impl Prompts {
             create_story: Box::new(create_story_prompt),
             delete_epic: Box::new(delete_epic_prompt),
             delete_story: Box::new(delete_story_prompt),
+            move_story: Box::new(move_story_prompt),
+            purge_item: Box::new(purge_item_prompt),
+            add_comment: Box::new(add_comment_prompt),
+            label: Box::new(label_prompt),
+            search: Box::new(search_prompt),
+            query_stories: Box::new(query_stories_prompt),
+            save_filter: Box::new(save_filter_prompt),
+            delete_saved_filter: Box::new(delete_saved_filter_prompt),
             update_status: Box::new(update_status_prompt),
+            edit_epic: Box::new(edit_epic_prompt),
+            edit_story: Box::new(edit_story_prompt),
+            assign: Box::new(assign_prompt),
+            estimate_story: Box::new(estimate_story_prompt),
+            recover_from_backup: Box::new(recover_from_backup_prompt),
+            repair_database: Box::new(repair_database_prompt),
         }
     }
 }
@@ -26,7 +61,10 @@ fn create_epic_prompt() -> Epic {
     let epic_name = get_user_input();//create variable to store first user input
     println!("Epic Description: ");
     let epic_desc = get_user_input();
-    let epic = Epic::new(epic_name.trim().to_owned(), epic_desc.trim().to_owned());//instantiate new Epic with user input assigned to particular struct fields
+    println!("Epic Owner (leave blank for none): ");
+    let epic_owner = get_user_input();
+    let mut epic = Epic::new(epic_name.trim().to_owned(), epic_desc.trim().to_owned());//instantiate new Epic with user input assigned to particular struct fields
+    epic.owner = person(&epic_owner);
     epic
 }
 
@@ -36,13 +74,130 @@ fn create_story_prompt() -> Story {
  let story_name = get_user_input();
  println!("Story Description: ");
  let story_desc = get_user_input();
- let story = Story::new(story_name.trim().to_owned(), story_desc.trim().to_owned());
+ println!("Assignee (leave blank for unassigned): ");
+ let assignee = get_user_input();
+ println!("Reporter (leave blank for yourself): ");
+ let reporter = get_user_input();
+ let mut story = Story::new(story_name.trim().to_owned(), story_desc.trim().to_owned());
+ story.assignee = person(&assignee);
+ story.reporter = person(&reporter); //the navigator fills in the current user when this is None
  story
 
 }
+fn edit_epic_prompt(name: &str, description: &str) -> (String, String) {
+    println!("----------------------------");
+    println!("Epic Name (leave blank to keep \"{}\"): ", name);
+    let epic_name = get_user_input();
+    println!("Epic Description (leave blank to keep \"{}\"): ", description);
+    let epic_desc = get_user_input();
+    (keep_if_blank(&epic_name, name), keep_if_blank(&epic_desc, description))
+}
+
+fn edit_story_prompt(name: &str, description: &str) -> (String, String) {
+    println!("----------------------------");
+    println!("Story Name (leave blank to keep \"{}\"): ", name);
+    let story_name = get_user_input();
+    println!("Story Description (leave blank to keep \"{}\"): ", description);
+    let story_desc = get_user_input();
+    (keep_if_blank(&story_name, name), keep_if_blank(&story_desc, description))
+}
+
+fn assign_prompt(role: &str, current: Option<&str>) -> Option<Option<String>> {
+    println!("----------------------------");
+    println!("{} (currently {}; leave blank to keep, - to clear): ", role, current.unwrap_or("nobody"));
+    let input = get_user_input();
+    match input.trim() {
+        "" => None,
+        "-" => Some(None),
+        name => Some(Some(name.to_owned())),
+    }
+}
+
+fn estimate_story_prompt(priority: Priority, points: Option<u32>) -> (Priority, Option<u32>) {
+    println!("----------------------------");
+    println!("Priority (1 - LOWEST, 2 - LOW, 3 - MEDIUM, 4 - HIGH, 5 - HIGHEST; leave blank to keep {}): ", priority);
+    let new_priority = get_user_input();
+    let current_points = points.map(|points| points.to_string()).unwrap_or_else(|| "unestimated".to_owned());
+    println!("Story Points (leave blank to keep {}, - to clear): ", current_points);
+    let new_points = get_user_input();
+
+    let priority = match new_priority.trim().parse::<usize>() { //anything else keeps the current priority
+        Ok(index @ 1..=5) => Priority::ALL[index - 1],
+        _ => priority,
+    };
+    let points = match new_points.trim() {
+        "-" => None,
+        input => input.parse::<u32>().ok().or(points),
+    };
+    (priority, points)
+}
+
+fn add_comment_prompt() -> Option<String> {
+    println!("----------------------------");
+    println!("Comment (leave blank to cancel): ");
+    let comment = get_user_input();
+    Some(comment.trim().to_owned()).filter(|comment| !comment.is_empty())
+}
+
+fn label_prompt(question: &str, suggestions: &[String]) -> Option<String> {
+    println!("----------------------------");
+    if !suggestions.is_empty() {
+        println!("Labels: {}", suggestions.join(", "));
+    }
+    println!("{}: ", question);
+    let label = get_user_input();
+    Some(label.trim().to_owned()).filter(|label| !label.is_empty())
+}
+
+fn search_prompt() -> Option<String> {
+    println!("----------------------------");
+    println!("Search epics and stories (leave blank to cancel): ");
+    let query = get_user_input();
+    Some(query.trim().to_owned()).filter(|query| !query.is_empty())
+}
+
+fn query_stories_prompt() -> Option<String> {
+    println!("----------------------------");
+    println!("Filter stories, e.g. status = \"IN PROGRESS\" AND label = backend (leave blank to cancel): ");
+    let query = get_user_input();
+    Some(query.trim().to_owned()).filter(|query| !query.is_empty())
+}
+
+fn save_filter_prompt() -> Option<String> {
+    println!("----------------------------");
+    println!("Save filter as (a saved filter with the same name is replaced; leave blank to cancel): ");
+    let name = get_user_input();
+    Some(name.trim().to_owned()).filter(|name| !name.is_empty())
+}
+
+fn delete_saved_filter_prompt() -> Option<String> {
+    println!("----------------------------");
+    println!("Delete which saved filter (name): ");
+    let name = get_user_input();
+    let name = Some(name.trim().to_owned()).filter(|name| !name.is_empty())?;
+    println!("Are you sure you want to delete the saved filter \"{}\"? [Y/N]:", name);
+    let input = get_user_input();
+    if input.trim().eq("Y") {
+        return Some(name);
+    }
+    None
+}
+
+fn person(input: &str) -> Option<String> { //blank means nobody
+    Some(input.trim().to_owned()).filter(|name| !name.is_empty())
+}
+
+fn keep_if_blank(input: &str, current: &str) -> String {
+    let input = input.trim();
+    if input.is_empty() {
+        return current.to_owned();
+    }
+    input.to_owned()
+}
+
 fn delete_epic_prompt() -> bool {
     println!("----------------------------");
-    println!("Are you sure you want to delete this epic? All stories in this epic will also be deleted [Y/N]:");
+    println!("Are you sure you want to delete this epic? The epic and its stories will be moved to the trash [Y/N]:");
     let input = get_user_input();
     if input.trim().eq("Y") {//.eq() method tests for self and other values to be equal, and is used by ==
 
@@ -60,27 +215,57 @@ fn delete_story_prompt() -> bool {
     }
     false
 }
-fn update_status_prompt() -> Option<Status>{
+fn recover_from_backup_prompt() -> bool {
+    println!("----------------------------");
+    println!("The database file could not be read. Do you want to restore it from the last backup? [Y/N]:");
+    let input = get_user_input();
+    if input.trim().eq("Y") {
+        return true;
+    }
+    false
+}
+
+fn move_story_prompt() -> Option<u32> {
+    println!("----------------------------");
+    println!("Move story to epic (id): ");
+    let epic_id = get_user_input();
+    epic_id.trim().parse::<u32>().ok() //anything that isn't an id cancels the move
+}
+
+fn purge_item_prompt() -> Option<u32> {
+    println!("----------------------------");
+    println!("Purge which item from the trash (id): ");
+    let item_id = get_user_input().trim().parse::<u32>().ok()?; //anything that isn't an id cancels the purge
+    println!("Are you sure you want to permanently delete item {}? Purging an epic also purges its stories [Y/N]:", item_id);
+    let input = get_user_input();
+    if input.trim().eq("Y") {
+        return Some(item_id);
+    }
+    None
+}
+
+fn repair_database_prompt(issues: &[IntegrityIssue]) -> bool {
+    println!("----------------------------");
+    println!("The database has {} integrity problem(s):", issues.len());
+    for issue in issues {
+        println!("  - {}", issue);
+    }
+    println!("Do you want to repair them now? Orphan stories will be moved into a \"{}\" epic [Y/N]:", RECOVERED_EPIC_NAME);
+    let input = get_user_input();
+    if input.trim().eq("Y") {
+        return true;
+    }
+    false
+}
+
+fn update_status_prompt(statuses: &[Status]) -> Option<Status>{
     println!("----------------------------");
-    println!("New Status (1 - OPEN, 2 - IN-PROGRESS, 3 - RESOLVED, 4 - CLOSED");
+    let choices: Vec<String> = statuses.iter().enumerate().map(|(index, status)| format!("{} - {}", index + 1, status)).collect();
+    println!("New Status ({})", choices.join(", ")); //the workflow decides what is offered, see Workflow::next_statuses
     let status = get_user_input();
-    let status = status.trim().parse::<u8>(); //prase function turns &str into u8 integers with tuberfish syntax
+    let status = status.trim().parse::<usize>(); //prase function turns &str into usize integers with tuberfish syntax
     if let Ok(status) = status {
-        match status {
-            1 => {
-                return Some(Status::Open);
-            }
-            2 => {
-                return Some(Status::InProgress);
-            }  
-            3 => {
-                return Some(Status::Resolved);
-            }
-            4 => {
-                return Some(Status::Closed);
-            }
-            _ => return None
-            }
-        }
-        None //Don't forget to return None with a Option
-    }
\ No newline at end of file
+        return statuses.get(status.checked_sub(1)?).cloned(); //0 or a number past the list is not a choice
+    }
+    None //Don't forget to return None with a Option
+}
\ No newline at end of file
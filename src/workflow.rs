@@ -0,0 +1,162 @@
+use std::collections::BTreeMap;
+use std::io::ErrorKind;
+
+use anyhow::{anyhow, Context, Result};
+use itertools::Itertools;
+use serde::Deserialize;
+
+use crate::models::Status;
+
+// Which statuses a story can use and which status changes are allowed. Loaded from a JSON file like
+// { "statuses": ["OPEN", "IN PROGRESS", "CLOSED"], "transitions": { "OPEN": ["IN PROGRESS"], "IN PROGRESS": ["OPEN", "CLOSED"] } }
+// Statuses are written the way they are displayed (anything Status::from_str accepts); a status missing from
+// "transitions" is final. Without a file every status can change into every other, like before workflows existed.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Workflow {
+    statuses: Vec<Status>, //in the order the prompt offers them
+    transitions: Vec<(Status, Status)>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct WorkflowFile {
+    statuses: Vec<String>,
+    #[serde(default)]
+    transitions: BTreeMap<String, Vec<String>>,
+}
+
+impl Default for Workflow {
+    fn default() -> Self {
+        let transitions = Status::ALL.iter().cartesian_product(Status::ALL.iter())
+            .filter(|(from, to)| from != to)
+            .map(|(from, to)| (from.clone(), to.clone()))
+            .collect();
+        Self { statuses: Status::ALL.to_vec(), transitions }
+    }
+}
+
+impl Workflow {
+    // A missing file means the default workflow, a file that can't be parsed is an error
+    pub fn load(file_path: &str) -> Result<Self> {
+        match std::fs::read_to_string(file_path) {
+            Ok(content) => Self::parse(&content).with_context(|| format!("workflow file {} is invalid", file_path)),
+            Err(error) if error.kind() == ErrorKind::NotFound => Ok(Self::default()),
+            Err(error) => Err(error).with_context(|| format!("could not read workflow file {}", file_path)),
+        }
+    }
+
+    // Only the built-in statuses exist, so every name has to parse with Status::from_str (case and punctuation don't
+    // matter, "in-progress" is IN PROGRESS); an unknown name such as "BLOCKED" is an error rather than a new status
+    pub fn parse(content: &str) -> Result<Self> {
+        let file: WorkflowFile = serde_json::from_str(content)?;
+        let status = |name: &str| name.parse::<Status>().map_err(|error| anyhow!(error));
+
+        let statuses: Vec<Status> = file.statuses.iter().map(|name| status(name)).collect::<Result<_>>()?;
+        if statuses.is_empty() {
+            return Err(anyhow!("the workflow lists no statuses"));
+        }
+        if let Some((_, duplicate)) = statuses.iter().enumerate().find(|(index, status)| statuses[..*index].contains(status)) {
+            return Err(anyhow!("status {} is listed twice", duplicate));
+        }
+
+        let mut transitions = vec![];
+        for (from, targets) in &file.transitions {
+            let from = status(from)?;
+            for to in targets {
+                let to = status(to)?;
+                if let Some(unlisted) = [&from, &to].into_iter().find(|status| !statuses.contains(*status)) {
+                    return Err(anyhow!("transition {} -> {} uses status {}, which is not in \"statuses\"", from, to, unlisted));
+                }
+                if from != to && !transitions.contains(&(from.clone(), to.clone())) {
+                    transitions.push((from.clone(), to));
+                }
+            }
+        }
+        Ok(Self { statuses, transitions })
+    }
+
+    pub fn statuses(&self) -> &[Status] {
+        &self.statuses
+    }
+
+    // Staying in the same status is always allowed, it isn't a change
+    pub fn allows(&self, from: &Status, to: &Status) -> bool {
+        from == to || self.transitions.iter().any(|(allowed_from, allowed_to)| allowed_from == from && allowed_to == to)
+    }
+
+    // The statuses a story in 'from' can move to, in workflow order
+    pub fn next_statuses(&self, from: &Status) -> Vec<Status> {
+        self.statuses.iter().filter(|to| *to != from && self.allows(from, to)).cloned().collect()
+    }
+
+    pub fn check_transition(&self, from: &Status, to: &Status) -> Result<()> {
+        if self.allows(from, to) {
+            return Ok(());
+        }
+        let next = self.next_statuses(from);
+        match next.is_empty() {
+            true => Err(anyhow!("the workflow doesn't allow {} -> {}, {} is a final status", from, to, from)),
+            false => Err(anyhow!("the workflow doesn't allow {} -> {} (allowed: {})", from, to, next.iter().join(", "))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WORKFLOW: &str = r#"{
+        "statuses": ["OPEN", "IN PROGRESS", "RESOLVED", "CLOSED"],
+        "transitions": {
+            "OPEN": ["IN PROGRESS", "CLOSED"],
+            "in-progress": ["OPEN", "RESOLVED"],
+            "RESOLVED": ["IN PROGRESS", "CLOSED"]
+        }
+    }"#;
+
+    #[test]
+    fn parse_should_read_statuses_and_transitions() {
+        let workflow = Workflow::parse(WORKFLOW).unwrap();
+
+        assert_eq!(workflow.statuses(), &Status::ALL);
+        assert_eq!(workflow.next_statuses(&Status::Open), vec![Status::InProgress, Status::Closed]);
+        assert_eq!(workflow.next_statuses(&Status::InProgress), vec![Status::Open, Status::Resolved]);
+        assert!(workflow.next_statuses(&Status::Closed).is_empty());
+        assert!(workflow.allows(&Status::Closed, &Status::Closed));
+
+        let error = workflow.check_transition(&Status::Closed, &Status::Open).unwrap_err().to_string();
+        assert_eq!(error, "the workflow doesn't allow CLOSED -> OPEN, CLOSED is a final status");
+        let error = workflow.check_transition(&Status::Open, &Status::Resolved).unwrap_err().to_string();
+        assert_eq!(error, "the workflow doesn't allow OPEN -> RESOLVED (allowed: IN PROGRESS, CLOSED)");
+    }
+
+    #[test]
+    fn parse_should_reject_inconsistent_files() {
+        assert!(Workflow::parse(r#"{"statuses": []}"#).is_err());
+        assert!(Workflow::parse(r#"{"statuses": ["OPEN", "open"]}"#).is_err());
+        assert!(Workflow::parse(r#"{"statuses": ["OPEN", "BLOCKED"]}"#).is_err());
+        assert!(Workflow::parse(r#"{"statuses": ["OPEN"], "transitions": {"OPEN": ["CLOSED"]}}"#).is_err());
+        assert!(Workflow::parse(r#"{"statuses": ["OPEN"], "transition": {}}"#).is_err()); //typos aren't silently ignored
+    }
+
+    #[test]
+    fn load_should_fall_back_to_default_without_a_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let file_path = dir.path().join("workflow.json");
+        let workflow = Workflow::load(file_path.to_str().unwrap()).unwrap();
+        assert_eq!(workflow, Workflow::default());
+        assert!(Status::ALL.iter().all(|from| Status::ALL.iter().all(|to| workflow.allows(from, to))));
+
+        std::fs::write(&file_path, "not json").unwrap();
+        assert!(Workflow::load(file_path.to_str().unwrap()).is_err());
+    }
+
+    #[test]
+    fn shipped_workflow_should_match_the_default() {
+        let workflow = Workflow::load(concat!(env!("CARGO_MANIFEST_DIR"), "/data/workflow.json")).unwrap();
+        let default = Workflow::default();
+
+        assert_eq!(workflow.statuses(), default.statuses());
+        assert!(Status::ALL.iter().all(|from| Status::ALL.iter().all(|to| workflow.allows(from, to) == default.allows(from, to))));
+    }
+}